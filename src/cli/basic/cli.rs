@@ -100,6 +100,7 @@ fn create_cli_app() -> Command {
                     .arg(
                         Arg::new("N").help("number of migration steps to rollback (default is 1)").value_parser(clap::value_parser!(u32)))
                 ),
+            Command::new("seaorm-plan").about("show what pending SeaORM migrations would do, without applying them"),
             Command::new("recover-file-list").about("recover file list from s3")
                 .args([
                     arg!("account", 'a', "account", "the account name", true).value_name("account"),
@@ -358,6 +359,29 @@ ZO_CALCULATE_STATS_STEP_LIMIT_SECS=3600 ./openobserve reset -c stream-stats
                 return Err(anyhow::anyhow!("missing sub command"));
             }
         },
+        "seaorm-plan" => {
+            let plans = infra::table::plan().await?;
+            if plans.is_empty() {
+                println!("No pending migrations");
+            }
+            for plan in plans {
+                println!("{}", plan.name);
+                match plan.statements {
+                    Some(statements) if statements.is_empty() => {
+                        println!("    -- no statements on this database")
+                    }
+                    Some(statements) => {
+                        for statement in statements {
+                            println!("    {statement};");
+                        }
+                    }
+                    None => println!("    -- statements not available"),
+                }
+                if let Some(rows) = plan.estimated_rows {
+                    println!("    -- estimated rows: {rows}");
+                }
+            }
+        }
         "recover-file-list" => {
             let account = command
                 .get_one::<String>("account")
@@ -741,6 +765,16 @@ mod tests {
         assert_eq!(sub_sub_matches.get_one::<u32>("N"), None);
     }
 
+    #[test]
+    fn test_seaorm_plan_command() {
+        let app = create_test_app();
+        let matches = app
+            .try_get_matches_from(["openobserve", "seaorm-plan"])
+            .unwrap();
+        let (name, _) = matches.subcommand().unwrap();
+        assert_eq!(name, "seaorm-plan");
+    }
+
     #[test]
     fn test_recover_file_list_command_parsing() {
         let app = create_test_app();
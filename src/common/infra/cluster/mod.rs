@@ -609,6 +609,16 @@ pub async fn get_cached_online_ingester_nodes() -> Option<Vec<Node>> {
     get_cached_nodes(|node| node.status == NodeStatus::Online && node.is_ingester()).await
 }
 
+#[inline]
+pub async fn get_cached_online_compactor_nodes() -> Option<Vec<Node>> {
+    get_cached_nodes(|node| node.status == NodeStatus::Online && node.is_compactor()).await
+}
+
+#[inline]
+pub async fn get_cached_online_alert_manager_nodes() -> Option<Vec<Node>> {
+    get_cached_nodes(|node| node.status == NodeStatus::Online && node.is_alert_manager()).await
+}
+
 #[inline]
 pub async fn get_cached_schedulable_ingester_nodes() -> Option<Vec<Node>> {
     get_cached_nodes(|node| {
@@ -31,7 +31,7 @@ use config::{
 };
 use dashmap::DashMap;
 use hashbrown::HashMap;
-use infra::table::short_urls::ShortUrlRecord;
+use infra::table::{sessions::CachedSession, short_urls::ShortUrlRecord};
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use vector_enrichment::TableRegistry;
@@ -96,7 +96,8 @@ pub static PIPELINE_STREAM_MAPPING: Lazy<RwAHashMap<String, StreamParams>> =
     Lazy::new(Default::default);
 
 pub static SCHEDULED_PIPELINES: Lazy<RwAHashMap<String, Pipeline>> = Lazy::new(Default::default);
-pub static USER_SESSIONS: Lazy<RwHashMap<String, String>> = Lazy::new(Default::default);
+pub static USER_SESSIONS: Lazy<RwHashMap<String, CachedSession>> = Lazy::new(Default::default);
+pub static USER_SESSIONS_LAST_USED: Lazy<RwHashMap<String, i64>> = Lazy::new(Default::default);
 pub static SHORT_URLS: Lazy<RwHashMap<String, ShortUrlRecord>> = Lazy::new(DashMap::default);
 pub static USER_ROLES_CACHE: Lazy<RwAHashMap<String, CachedUserRoles>> =
     Lazy::new(Default::default);
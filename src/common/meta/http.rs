@@ -13,7 +13,10 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use actix_web::{HttpResponse as ActixHttpResponse, http::StatusCode};
+use actix_web::{
+    HttpResponse as ActixHttpResponse,
+    http::{StatusCode, header},
+};
 use infra::errors;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
@@ -139,6 +142,18 @@ impl HttpResponse {
         ActixHttpResponse::Conflict().json(Self::error(StatusCode::CONFLICT, error.to_string()))
     }
 
+    /// Send a TooManyRequests response in json format, asking the client to
+    /// retry after `retry_after` seconds, and associate the provided error
+    /// as `error` field.
+    pub fn too_many_requests(error: impl ToString, retry_after: u64) -> ActixHttpResponse {
+        ActixHttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .json(Self::error(
+                StatusCode::TOO_MANY_REQUESTS,
+                error.to_string(),
+            ))
+    }
+
     /// Send a NotFound response in json format and associate the
     /// provided error as `error` field.
     pub fn not_found(error: impl ToString) -> ActixHttpResponse {
@@ -185,6 +200,13 @@ mod tests {
         assert_eq!(err.message, errcode.get_message());
     }
 
+    #[test]
+    fn test_too_many_requests() {
+        let response = HttpResponse::too_many_requests("slow down", 30);
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), "30");
+    }
+
     #[test]
     fn test_http_response_with_trace_id() {
         let mut response = HttpResponse::message(http::StatusCode::OK, "test");
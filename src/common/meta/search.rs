@@ -18,6 +18,8 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 pub const CAPPED_RESULTS_MSG: &str = "Warn: results are capped to meet default limit";
+pub const MAX_RESULT_SIZE_CAPPED_MSG: &str =
+    "Warn: requested size is capped to meet max result size";
 
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema, Default)]
 pub struct CachedQueryResponse {
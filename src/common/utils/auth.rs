@@ -900,6 +900,7 @@ pub async fn extract_auth_str(req: &HttpRequest) -> String {
             let session_key = access_token.strip_prefix("session ").unwrap().to_string();
             match crate::service::db::session::get(&session_key).await {
                 Ok(token) => {
+                    crate::service::session::touch_session(&session_key).await;
                     format!("Bearer {token}")
                 }
                 Err(_) => access_token,
@@ -946,10 +947,15 @@ pub fn extract_basic_auth_str(req: &HttpRequest) -> String {
         } else if access_token.starts_with("session") {
             let session_key = access_token.strip_prefix("session ").unwrap().to_string();
             match USER_SESSIONS.get(&session_key) {
-                Some(token) => {
-                    format!("Bearer {}", *token)
+                Some(session) if !session.is_expired(config::utils::time::now_micros()) => {
+                    // this is sync, so the session is touched in the background
+                    let access_token = format!("Bearer {}", session.access_token);
+                    tokio::spawn(async move {
+                        crate::service::session::touch_session(&session_key).await;
+                    });
+                    access_token
                 }
-                None => access_token,
+                _ => access_token,
             }
         } else {
             format!("Bearer {access_token}")
@@ -1095,6 +1101,7 @@ pub async fn extract_auth_expiry_and_user_id(
                 return (None, None);
             }
         };
+        crate::service::session::touch_session(session_key).await;
         let exp = decode(&stripped_bearer_token).await;
         let bearer_full_token = format!("Bearer {stripped_bearer_token}");
         let user_id = get_user_email_from_auth_str(&bearer_full_token).await;
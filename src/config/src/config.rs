@@ -497,6 +497,7 @@ pub struct Config {
     pub health_check: HealthCheck,
     pub encryption: Encryption,
     pub enrichment_table: EnrichmentTable,
+    pub recommendations: Recommendations,
 }
 
 #[derive(Serialize, EnvConfig, Default)]
@@ -641,6 +642,17 @@ pub struct Auth {
     pub cookie_same_site_lax: bool,
     #[env_config(name = "ZO_COOKIE_SECURE_ONLY", default = false)]
     pub cookie_secure_only: bool,
+    #[env_config(name = "ZO_SESSION_TTL", default = 2592000)] // seconds, 30 days, 0 = no expiry
+    pub session_ttl: i64,
+    #[env_config(name = "ZO_SESSION_CLEANUP_INTERVAL", default = 86400)] // seconds, 0 = disabled
+    pub session_cleanup_interval: i64,
+    #[env_config(name = "ZO_SESSION_RETENTION_DAYS", default = 30)] // days
+    pub session_retention_days: i64,
+    // Interval of refreshing the active session metrics, which count the sessions in the database
+    #[env_config(name = "ZO_SESSION_METRICS_INTERVAL", default = 300)] // seconds, 0 = disabled
+    pub session_metrics_interval: i64,
+    #[env_config(name = "ZO_MAX_SESSIONS_PER_USER", default = 0)] // 0 = unlimited
+    pub max_sessions_per_user: usize,
     #[env_config(name = "ZO_EXT_AUTH_SALT", default = "openobserve")]
     pub ext_auth_salt: String,
     #[env_config(name = "O2_ACTION_SERVER_TOKEN")]
@@ -1362,19 +1374,16 @@ pub struct Limit {
     pub query_ingester_timeout: u64,
     #[env_config(name = "ZO_QUERY_DEFAULT_LIMIT", default = 1000)]
     pub query_default_limit: i64,
+    // Upper bound on the size of a search request, 0 = no limit. Sizes of 0 or below already
+    // fall back to ZO_QUERY_DEFAULT_LIMIT.
+    #[env_config(name = "ZO_QUERY_MAX_RESULT_SIZE", default = 0)]
+    pub query_max_result_size: i64,
     #[env_config(name = "ZO_QUERY_VALUES_DEFAULT_NUM", default = 10)]
     pub query_values_default_num: i64,
     #[env_config(name = "ZO_QUERY_PARTITION_BY_SECS", default = 1)] // seconds
     pub query_partition_by_secs: usize,
     #[env_config(name = "ZO_QUERY_GROUP_BASE_SPEED", default = 768)] // MB/s/core
     pub query_group_base_speed: usize,
-    // Default Config: Run Query Recommendation Analysis for last one hour for every hour
-    #[env_config(name = "ZO_QUERY_RECOMMENDATION_DURATION", default = 3600000000)] // microseconds
-    pub query_recommendation_duration: i64,
-    #[env_config(name = "ZO_QUERY_RECOMMENDATION_INTERVAL", default = 3600)] // seconds
-    pub query_recommendation_analysis_interval: i64,
-    #[env_config(name = "ZO_QUERY_RECOMMENDATION_TOP_K", default = 128)]
-    pub query_recommendation_top_k: usize,
     #[env_config(name = "ZO_INGEST_ALLOWED_UPTO", default = 5)] // in hours - in past
     pub ingest_allowed_upto: i64,
     #[env_config(name = "ZO_INGEST_ALLOWED_IN_FUTURE", default = 24)] // in hours - in future
@@ -1609,6 +1618,12 @@ pub struct Limit {
         help = "Default data type for LongText compliant DB's"
     )]
     pub db_text_data_type: String,
+    #[env_config(
+        name = "ZO_META_MIGRATION_PAGE_SIZE",
+        default = 1000,
+        help = "Number of rows per page read by the data migrations of the meta store"
+    )]
+    pub meta_migration_page_size: u64,
     #[env_config(
         name = "ZO_MAX_DASHBOARD_SERIES",
         default = 100,
@@ -2186,6 +2201,106 @@ pub struct EnrichmentTable {
     pub merge_interval: u64,
 }
 
+#[derive(Serialize, EnvConfig, Default)]
+pub struct Recommendations {
+    // Opt-in: when off, recommendations are never scheduled or run, and their API is not served
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_ENABLED", default = false)]
+    pub enabled: bool,
+    // Default Config: Run Query Recommendation Analysis for last one hour for every hour
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_INTERVAL", default = 3600)] // seconds
+    pub interval: i64,
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_DURATION", default = 3600000000)] // microseconds
+    pub duration: i64,
+    // Number of the most expensive queries analysed per run
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_TOP_K", default = 128)]
+    pub max_queries: usize,
+    // Which queries of the usage are analysed, the first TOP_K in this order: p95 for the
+    // slowest, count for the most frequent, impact for the ones that spent the most time waiting,
+    // their p95 latency times their count
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_USAGE_ORDER_BY", default = "p95")]
+    pub usage_order_by: String,
+    // Analyses the queries run by dashboard panels as a source of their own, tagged with their
+    // dashboard and panel, next to the other queries. Off, all queries are analysed together.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_DASHBOARD_QUERIES", default = false)]
+    pub dashboard_queries: bool,
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_TIMEOUT", default = 300)] // seconds, 0 = no limit
+    pub timeout: u64,
+    // Runs the recommendation searches at low priority, in the background work group, so they
+    // yield to the searches of users. When off they run along with the searches of users.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_LOW_PRIORITY", default = true)]
+    pub low_priority: bool,
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_CACHE_TTL", default = 60)] // seconds, 0 = off
+    pub cache_ttl: i64,
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_DISMISS_COOLDOWN", default = 604800)] // seconds
+    pub dismiss_cooldown: i64,
+    // Minimum time between two manual runs of an org. Scheduled runs aren't limited.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_RUN_COOLDOWN", default = 300)] // seconds, 0 = off
+    pub run_cooldown: i64,
+    // Random spread added to or removed from the time until each scheduled run, as a percentage
+    // of it, so the runs of many clusters don't hit the search backend at the same time.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_JITTER_PERCENT", default = 10)]
+    pub jitter_percent: u64,
+    // Longest delay between scheduled runs when backing off after failed runs.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_MAX_BACKOFF", default = 86400)]
+    // seconds, 0 = off
+    pub max_backoff: i64,
+    // Scheduled runs are paused for BREAKER_COOLDOWN after this many failed runs in a row, then
+    // a single run tests whether the search backend recovered.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_BREAKER_THRESHOLD", default = 5)] // 0 = off
+    pub breaker_threshold: u32,
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_BREAKER_COOLDOWN", default = 21600)] // seconds
+    pub breaker_cooldown: i64,
+    // Streams with fewer records are not analysed, their recommendations would be noise.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_MIN_STREAM_RECORDS", default = 10000)]
+    // 0 = off
+    pub min_stream_records: i64,
+    // Example values of its target field added to the payload of each recommendation, at the
+    // cost of one more query per field. Fields with sensitive names, such as passwords or
+    // tokens, are never sampled.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_SAMPLE_VALUES", default = 0)] // 0 = off
+    pub sample_values: usize,
+    // Splits the window of a run into chunks of this many seconds when counting the distinct
+    // values of the recommended fields, so that no single query scans the whole window of a
+    // huge stream. The largest count of any chunk is used, which can underestimate fields whose
+    // values change over time. 0 counts the whole window at once.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_DISTINCT_CHUNK_INTERVAL", default = 0)] // seconds
+    pub distinct_chunk_interval: i64,
+    // Most streams whose distinct values are computed at the same time by a run, so that many
+    // recommended streams don't flood the search backend.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_DISTINCT_CONCURRENCY", default = 4)]
+    pub distinct_concurrency: usize,
+    // Queries whose p95 latency grew by more than this percentage since the previous run are
+    // flagged as latency regressions.
+    #[env_config(
+        name = "ZO_QUERY_RECOMMENDATION_LATENCY_REGRESSION_PERCENT",
+        default = 20
+    )]
+    // 0 = off
+    pub latency_regression_percent: u64,
+    // Most recommendations emitted per run, the ones of highest impact. The others are dropped
+    // for the run, they come back in a later run if they are still relevant.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_MAX_PER_RUN", default = 50)] // 0 = no limit
+    pub max_recommendations_per_run: usize,
+    // Open latency regressions at least this old are closed by the next run when their query no
+    // longer shows up in the analysed window, or is fast again.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_AUTO_RESOLVE_AFTER", default = 86400)]
+    // seconds, 0 = off
+    pub auto_resolve_after: i64,
+    // Comma separated streams of the meta org that are never analysed. A trailing `*` matches
+    // every stream starting with the given prefix.
+    #[env_config(
+        name = "ZO_QUERY_RECOMMENDATION_EXCLUDED_STREAMS",
+        default = "usage,stats,triggers,errors,data_retention_usage,query_recommendations"
+    )]
+    pub excluded_streams: String,
+    // Comma separated regions and clusters recommendation searches are routed to. Empty means
+    // the local cluster. Only honoured when super cluster is enabled.
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_REGIONS", default = "")]
+    pub regions: String,
+    #[env_config(name = "ZO_QUERY_RECOMMENDATION_CLUSTERS", default = "")]
+    pub clusters: String,
+}
+
 pub fn init() -> Config {
     if let Err(e) = load_config() {
         log::error!("Failed to load config {e}");
@@ -2279,6 +2394,11 @@ pub fn init() -> Config {
         panic!("inverted index config error: {e}");
     }
 
+    // check query recommendations config
+    if let Err(e) = check_recommendations_config(&cfg) {
+        panic!("query recommendations config error: {e}");
+    }
+
     cfg
 }
 
@@ -2995,6 +3115,94 @@ fn check_s3_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+fn check_recommendations_config(cfg: &Config) -> Result<(), anyhow::Error> {
+    let cfg = &cfg.recommendations;
+    if cfg.interval < 1 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_INTERVAL must be at least 1 second"
+        ));
+    }
+    if cfg.duration < 1 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_DURATION must be a positive number of microseconds"
+        ));
+    }
+    if cfg.max_queries == 0 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_TOP_K must be at least 1"
+        ));
+    }
+    if !["p95", "count", "impact"].contains(&cfg.usage_order_by.to_lowercase().as_str()) {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_USAGE_ORDER_BY must be one of p95, count or impact, got {}",
+            cfg.usage_order_by
+        ));
+    }
+    for (name, value) in [
+        ("ZO_QUERY_RECOMMENDATION_CACHE_TTL", cfg.cache_ttl),
+        (
+            "ZO_QUERY_RECOMMENDATION_DISMISS_COOLDOWN",
+            cfg.dismiss_cooldown,
+        ),
+        ("ZO_QUERY_RECOMMENDATION_RUN_COOLDOWN", cfg.run_cooldown),
+        ("ZO_QUERY_RECOMMENDATION_MAX_BACKOFF", cfg.max_backoff),
+        (
+            "ZO_QUERY_RECOMMENDATION_BREAKER_COOLDOWN",
+            cfg.breaker_cooldown,
+        ),
+        (
+            "ZO_QUERY_RECOMMENDATION_AUTO_RESOLVE_AFTER",
+            cfg.auto_resolve_after,
+        ),
+    ] {
+        if value < 0 {
+            return Err(anyhow::anyhow!(
+                "{name} must be a number of seconds, or 0 to turn it off, got {value}"
+            ));
+        }
+    }
+    if cfg.min_stream_records < 0 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_MIN_STREAM_RECORDS must be a number of records, or 0 to turn it off, got {}",
+            cfg.min_stream_records
+        ));
+    }
+    if cfg.jitter_percent > 100 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_JITTER_PERCENT must be between 0 and 100, got {}",
+            cfg.jitter_percent
+        ));
+    }
+    if cfg.sample_values > 100 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_SAMPLE_VALUES must be at most 100, got {}",
+            cfg.sample_values
+        ));
+    }
+    if cfg.distinct_concurrency == 0 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_DISTINCT_CONCURRENCY must be at least 1"
+        ));
+    }
+    if cfg.distinct_chunk_interval < 0 {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_DISTINCT_CHUNK_INTERVAL must be a positive number of seconds or 0, got {}",
+            cfg.distinct_chunk_interval
+        ));
+    }
+    if let Some(pattern) = cfg
+        .excluded_streams
+        .split(',')
+        .map(str::trim)
+        .find(|pattern| pattern.trim_end_matches('*').contains('*'))
+    {
+        return Err(anyhow::anyhow!(
+            "ZO_QUERY_RECOMMENDATION_EXCLUDED_STREAMS only supports `*` at the end of a stream name, got {pattern}"
+        ));
+    }
+    Ok(())
+}
+
 fn check_pipeline_config(cfg: &mut Config) -> Result<(), anyhow::Error> {
     // pipeline
     if cfg.pipeline.remote_stream_wal_dir.is_empty() {
@@ -3195,6 +3403,52 @@ mod tests {
         assert_eq!(cfg.compact.data_retention_days, 10);
         assert_eq!(cfg.limit.req_cols_per_record_limit, 1000);
 
+        check_recommendations_config(&cfg).unwrap();
+        cfg.recommendations.interval = 0;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.interval = 3600;
+        cfg.recommendations.duration = 0;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.duration = 3600000000;
+        cfg.recommendations.max_queries = 0;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.max_queries = 128;
+        cfg.recommendations.usage_order_by = "Impact".to_string();
+        check_recommendations_config(&cfg).unwrap();
+        cfg.recommendations.usage_order_by = "latency".to_string();
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.usage_order_by = "p95".to_string();
+        cfg.recommendations.run_cooldown = -1;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.run_cooldown = 0;
+        cfg.recommendations.jitter_percent = 101;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.jitter_percent = 10;
+        cfg.recommendations.sample_values = 101;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.sample_values = 5;
+        check_recommendations_config(&cfg).unwrap();
+        cfg.recommendations.sample_values = 0;
+        cfg.recommendations.distinct_chunk_interval = -1;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.distinct_chunk_interval = 3600;
+        check_recommendations_config(&cfg).unwrap();
+        cfg.recommendations.distinct_chunk_interval = 0;
+        cfg.recommendations.distinct_concurrency = 0;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.distinct_concurrency = 4;
+        cfg.recommendations.max_backoff = -1;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.max_backoff = 86400;
+        cfg.recommendations.breaker_cooldown = -1;
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.breaker_cooldown = 21600;
+        cfg.recommendations.excluded_streams = "usage, k8s_*".to_string();
+        check_recommendations_config(&cfg).unwrap();
+        cfg.recommendations.excluded_streams = "usage,*_logs".to_string();
+        assert!(check_recommendations_config(&cfg).is_err());
+        cfg.recommendations.excluded_streams = "usage".to_string();
+
         cfg.compact.data_retention_days = 2;
         let ret = check_compact_config(&mut cfg);
         assert!(ret.is_err());
@@ -15,7 +15,10 @@
 
 use proto::cluster_rpc::{self, IndexInfo, QueryIdentifier, SearchInfo, SuperClusterInfo};
 
-use crate::meta::{search::default_use_cache, stream::StreamType};
+use crate::meta::{
+    search::{SearchEventType, SearchPriority, default_use_cache},
+    stream::StreamType,
+};
 
 #[derive(Debug, Clone)]
 pub struct Request {
@@ -33,6 +36,7 @@ pub struct Request {
     pub use_cache: bool,
     pub overwrite_cache: bool,
     pub histogram_interval: i64,
+    pub search_priority: SearchPriority,
 }
 
 impl Default for Request {
@@ -52,6 +56,7 @@ impl Default for Request {
             use_cache: default_use_cache(),
             overwrite_cache: false,
             histogram_interval: 0,
+            search_priority: SearchPriority::default(),
         }
     }
 }
@@ -84,6 +89,7 @@ impl Request {
             use_cache: default_use_cache(),
             overwrite_cache,
             histogram_interval,
+            search_priority: SearchPriority::default(),
         }
     }
 
@@ -103,6 +109,21 @@ impl Request {
     pub fn set_use_cache(&mut self, use_cache: bool) {
         self.use_cache = use_cache;
     }
+
+    pub fn set_search_priority(&mut self, search_priority: SearchPriority) {
+        self.search_priority = search_priority;
+    }
+
+    /// Returns true if the search runs in the background work group, which is the case for low
+    /// priority searches and for the background search event types, such as alerts and reports.
+    pub fn is_background_task(&self) -> bool {
+        self.search_priority == SearchPriority::Low
+            || self
+                .search_event_type
+                .as_ref()
+                .and_then(|st| SearchEventType::try_from(st.as_str()).ok())
+                .is_some_and(|st| st.is_background())
+    }
 }
 
 impl From<FlightSearchRequest> for Request {
@@ -122,6 +143,7 @@ impl From<FlightSearchRequest> for Request {
             use_cache: req.search_info.use_cache,
             overwrite_cache: req.search_info.clear_cache,
             histogram_interval: req.search_info.histogram_interval,
+            search_priority: SearchPriority::default(),
         }
     }
 }
@@ -167,3 +189,28 @@ impl From<FlightSearchRequest> for cluster_rpc::FlightSearchRequest {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_background_task() {
+        let mut req = Request {
+            search_event_type: Some(SearchEventType::UI.to_string()),
+            ..Default::default()
+        };
+        assert!(!req.is_background_task());
+
+        // low priority searches are queued in the background work group, behind user searches
+        req.set_search_priority(SearchPriority::Low);
+        assert!(req.is_background_task());
+
+        let req = Request {
+            search_event_type: Some(SearchEventType::Alerts.to_string()),
+            ..Default::default()
+        };
+        assert!(req.is_background_task());
+        assert!(!Request::default().is_background_task());
+    }
+}
@@ -73,8 +73,13 @@ pub struct Request {
     pub use_cache: bool,
     #[serde(default)]
     pub clear_cache: bool,
+    /// Runs the search on a single querier, the local node when it is one, instead of fanning
+    /// it out. The whole file list is still searched, but data that is only in the ingesters'
+    /// WAL is not.
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub local_mode: Option<bool>,
+    #[serde(default)]
+    pub search_priority: SearchPriority,
 }
 
 pub fn default_use_cache() -> bool {
@@ -108,6 +113,118 @@ impl std::fmt::Display for RequestEncoding {
     }
 }
 
+/// Scheduling priority of a search. Low priority searches run in the background work group,
+/// like alerts and reports, so they queue behind and yield to the searches of users.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchPriority {
+    #[default]
+    Normal,
+    Low,
+}
+
+impl std::fmt::Display for SearchPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Normal => write!(f, "normal"),
+            Self::Low => write!(f, "low"),
+        }
+    }
+}
+
+/// How the total number of hits of a query is counted. Serialized as `false`, `true` or the
+/// maximum number of hits to count, so requests sending a bool keep working.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrackTotalHits {
+    /// The total is not counted.
+    #[default]
+    Off,
+    /// Hits are counted up to the given number, which is cheaper than an exact count on large
+    /// streams. A total equal to the limit is a lower bound.
+    UpTo(i64),
+    /// Every hit is counted.
+    Exact,
+}
+
+impl TrackTotalHits {
+    /// Creates the mode from the `track_total_hits` and `track_total_hits_limit` fields of a
+    /// [`cluster_rpc::SearchQuery`].
+    pub fn from_proto(track_total_hits: bool, limit: i64) -> Self {
+        match (track_total_hits, limit) {
+            (false, _) => Self::Off,
+            (true, limit) if limit > 0 => Self::UpTo(limit),
+            (true, _) => Self::Exact,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Off)
+    }
+
+    /// Returns the maximum number of hits counted, or None if the count isn't capped.
+    pub fn limit(&self) -> Option<i64> {
+        match self {
+            Self::UpTo(limit) => Some(*limit),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a `total` counted in this mode is the exact number of hits, or None if
+    /// hits aren't counted.
+    pub fn is_exact(&self, total: usize) -> Option<bool> {
+        match self {
+            Self::Off => None,
+            Self::UpTo(limit) => Some((total as i64) < *limit),
+            Self::Exact => Some(true),
+        }
+    }
+}
+
+impl From<bool> for TrackTotalHits {
+    fn from(track_total_hits: bool) -> Self {
+        if track_total_hits {
+            Self::Exact
+        } else {
+            Self::Off
+        }
+    }
+}
+
+impl Serialize for TrackTotalHits {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Off => serializer.serialize_bool(false),
+            Self::UpTo(limit) => serializer.serialize_i64(*limit),
+            Self::Exact => serializer.serialize_bool(true),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackTotalHits {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Bool(bool),
+            Limit(i64),
+        }
+        match Value::deserialize(deserializer)? {
+            Value::Bool(v) => Ok(v.into()),
+            Value::Limit(0) => Ok(Self::Off),
+            Value::Limit(limit) if limit > 0 => Ok(Self::UpTo(limit)),
+            Value::Limit(limit) => Err(serde::de::Error::custom(format!(
+                "track_total_hits must be a bool or a positive number, got {limit}"
+            ))),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 #[schema(as = SearchQuery)]
 pub struct Query {
@@ -124,8 +241,10 @@ pub struct Query {
     pub quick_mode: bool,
     #[serde(default)]
     pub query_type: String,
+    /// `false`, `true` for an exact count, or the maximum number of hits to count.
     #[serde(default)]
-    pub track_total_hits: bool,
+    #[schema(value_type = Object)]
+    pub track_total_hits: TrackTotalHits,
     #[serde(default)]
     pub uses_zo_fn: bool,
     #[serde(default)]
@@ -164,7 +283,7 @@ impl Default for Query {
             end_time: 0,
             quick_mode: false,
             query_type: "".to_string(),
-            track_total_hits: false,
+            track_total_hits: TrackTotalHits::Off,
             uses_zo_fn: false,
             query_fn: None,
             action_id: None,
@@ -178,7 +297,31 @@ impl Default for Query {
     }
 }
 
+impl Query {
+    /// Creates the query of a system search over `[start_time, end_time)`, returning every
+    /// matching row. All other fields keep their defaults and can be overridden afterwards.
+    pub fn internal(sql: impl Into<String>, start_time: i64, end_time: i64) -> Self {
+        Query {
+            sql: sql.into(),
+            size: -1,
+            start_time,
+            end_time,
+            ..Default::default()
+        }
+    }
+}
+
 impl Request {
+    /// Creates the request of a system search, such as the ones run by background jobs, with
+    /// [`Query::internal`]. The result cache is not used.
+    pub fn internal(sql: impl Into<String>, start_time: i64, end_time: i64) -> Self {
+        Request {
+            query: Query::internal(sql, start_time, end_time),
+            use_cache: false,
+            ..Default::default()
+        }
+    }
+
     #[inline]
     pub fn decode(&mut self) -> Result<(), std::io::Error> {
         match self.encoding {
@@ -230,6 +373,9 @@ pub struct Response {
     pub function_error: Vec<String>,
     #[serde(default)]
     pub is_partial: bool,
+    /// Whether `total` is the exact number of hits, set when the query tracks total hits.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub total_exact: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub histogram_interval: Option<i64>, // seconds, for histogram
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -390,6 +536,28 @@ impl ResponseTook {
     }
 }
 
+/// Converts a non-negative number, or a string holding one, to an u64.
+fn value_as_u64(value: &json::Value) -> Option<u64> {
+    match value {
+        json::Value::Number(n) => n.as_u64().or_else(|| {
+            n.as_f64()
+                .filter(|v| v.is_finite() && *v >= 0.0)
+                .map(|v| v as u64)
+        }),
+        json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Converts a number, or a string holding one, to an f64.
+fn value_as_f64(value: &json::Value) -> Option<f64> {
+    match value {
+        json::Value::Number(n) => n.as_f64(),
+        json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
 impl Response {
     pub fn new(from: i64, size: i64) -> Self {
         Response {
@@ -409,6 +577,7 @@ impl Response {
             trace_id: "".to_string(),
             function_error: Vec::new(),
             is_partial: false,
+            total_exact: None,
             histogram_interval: None,
             new_start_time: None,
             new_end_time: None,
@@ -440,6 +609,58 @@ impl Response {
             .collect();
     }
 
+    /// Returns the value of the `alias` column of the first hit as an u64, for single row
+    /// aggregate queries. Returns None if there are no hits, the value is missing or null, or it
+    /// is not a non-negative number or a string holding one.
+    pub fn scalar_u64(&self, alias: &str) -> Option<u64> {
+        value_as_u64(self.hits.first()?.get(alias)?)
+    }
+
+    /// Returns the value of the `alias` column of the first hit as an f64, for single row
+    /// aggregate queries. Returns None if there are no hits, the value is missing or null, or it
+    /// is not a number or a string holding one.
+    pub fn scalar_f64(&self, alias: &str) -> Option<f64> {
+        value_as_f64(self.hits.first()?.get(alias)?)
+    }
+
+    /// Returns the values of the `name` column, one per hit in the order of the hits. The value
+    /// is null for the hits without the column.
+    pub fn column(&self, name: &str) -> Vec<json::Value> {
+        self.hits
+            .iter()
+            .map(|hit| hit.get(name).cloned().unwrap_or(json::Value::Null))
+            .collect()
+    }
+
+    /// Returns the values of the `name` column as u64s, one per hit, converted like
+    /// [`Self::scalar_u64`]. The value is None for the hits without the column or with a
+    /// value that doesn't convert.
+    pub fn column_u64(&self, name: &str) -> Vec<Option<u64>> {
+        self.hits
+            .iter()
+            .map(|hit| hit.get(name).and_then(value_as_u64))
+            .collect()
+    }
+
+    /// Returns the values of the `name` column as f64s, one per hit, converted like
+    /// [`Self::scalar_f64`]. The value is None for the hits without the column or with a
+    /// value that doesn't convert.
+    pub fn column_f64(&self, name: &str) -> Vec<Option<f64>> {
+        self.hits
+            .iter()
+            .map(|hit| hit.get(name).and_then(value_as_f64))
+            .collect()
+    }
+
+    /// Returns the values of the `name` column as strings, one per hit. The value is None for
+    /// the hits without the column or with a value that is not a string.
+    pub fn column_str(&self, name: &str) -> Vec<Option<&str>> {
+        self.hits
+            .iter()
+            .map(|hit| hit.get(name).and_then(|v| v.as_str()))
+            .collect()
+    }
+
     pub fn add_hit(&mut self, hit: &json::Value) {
         self.hits.push(hit.to_owned());
         self.total += 1;
@@ -504,6 +725,10 @@ impl Response {
         }
     }
 
+    pub fn set_total_exact(&mut self, val: Option<bool>) {
+        self.total_exact = val;
+    }
+
     pub fn set_histogram_interval(&mut self, val: Option<i64>) {
         self.histogram_interval = val;
     }
@@ -602,6 +827,19 @@ pub struct SearchPartitionResponse {
     pub is_histogram_eligible: bool,
 }
 
+/// Estimated cost of running a query, computed from stream metadata without running it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QueryCostEstimate {
+    /// Number of streams the query reads.
+    pub streams: usize,
+    /// Estimated number of records scanned.
+    pub records: usize,
+    /// Estimated uncompressed bytes scanned.
+    pub original_size: usize,
+    /// Number of partitions the query would be split into.
+    pub partitions: usize,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, ToSchema)]
 pub struct SearchHistoryRequest {
     pub org_id: Option<String>,
@@ -650,7 +888,7 @@ impl SearchHistoryRequest {
                 end_time: self.end_time,
                 quick_mode: false,
                 query_type: "".to_string(),
-                track_total_hits: false,
+                track_total_hits: TrackTotalHits::Off,
                 uses_zo_fn: false,
                 query_fn: None,
                 action_id: None,
@@ -670,6 +908,7 @@ impl SearchHistoryRequest {
             use_cache: default_use_cache(),
             clear_cache: false,
             local_mode: None,
+            search_priority: Default::default(),
         };
         Ok(search_req)
     }
@@ -866,7 +1105,8 @@ impl From<Query> for cluster_rpc::SearchQuery {
             size: query.size as i32,
             start_time: query.start_time,
             end_time: query.end_time,
-            track_total_hits: query.track_total_hits,
+            track_total_hits: query.track_total_hits.is_enabled(),
+            track_total_hits_limit: query.track_total_hits.limit().unwrap_or_default(),
             uses_zo_fn: query.uses_zo_fn,
             query_fn: query.query_fn.unwrap_or_default(),
             action_id: query.action_id.unwrap_or_default(),
@@ -1029,6 +1269,9 @@ pub struct SearchEventContext {
     pub dashboard_folder_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none", rename = "folder_name")]
     pub dashboard_folder_name: Option<String>,
+    /// Set on searches run by OpenObserve itself, naming the component that ran them.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub system_source: Option<String>,
 }
 
 impl SearchEventContext {
@@ -1046,6 +1289,13 @@ impl SearchEventContext {
         }
     }
 
+    pub fn with_system(system_source: &str) -> Self {
+        Self {
+            system_source: Some(system_source.to_string()),
+            ..Default::default()
+        }
+    }
+
     pub fn with_report(report_key: Option<String>) -> Self {
         Self {
             report_key,
@@ -1091,6 +1341,7 @@ impl From<proto::cluster_rpc::SearchEventContext> for SearchEventContext {
             dashboard_name: proto_sec.dashboard_name,
             dashboard_folder_id: proto_sec.dashboard_folder_id,
             dashboard_folder_name: proto_sec.dashboard_folder_name,
+            system_source: proto_sec.system_source,
         }
     }
 }
@@ -1106,6 +1357,7 @@ impl From<SearchEventContext> for proto::cluster_rpc::SearchEventContext {
             dashboard_name: sec.dashboard_name,
             dashboard_folder_id: sec.dashboard_folder_id,
             dashboard_folder_name: sec.dashboard_folder_name,
+            system_source: sec.system_source,
         }
     }
 }
@@ -1254,7 +1506,7 @@ impl MultiStreamRequest {
                     end_time: query.end_time.unwrap_or(self.end_time),
                     quick_mode: self.quick_mode,
                     query_type: self.query_type.clone(),
-                    track_total_hits: self.track_total_hits,
+                    track_total_hits: self.track_total_hits.into(),
                     uses_zo_fn: self.uses_zo_fn,
                     query_fn,
                     action_id: None,
@@ -1274,6 +1526,7 @@ impl MultiStreamRequest {
                 use_cache: default_use_cache(),
                 clear_cache: false,
                 local_mode: None,
+                search_priority: Default::default(),
             });
         }
         res
@@ -1874,6 +2127,125 @@ mod tests {
         assert_eq!(res.total, 11);
     }
 
+    #[test]
+    fn test_response_scalars() {
+        let mut res = Response::default();
+        assert_eq!(res.scalar_u64("cnt"), None);
+        assert_eq!(res.scalar_f64("cnt"), None);
+
+        res.add_hit(&json::json!({
+            "cnt": 42,
+            "avg": 1.5,
+            "str": "7",
+            "neg": -3,
+            "null": null,
+            "obj": {"a": 1}
+        }));
+        res.add_hit(&json::json!({"cnt": 1}));
+        assert_eq!(res.scalar_u64("cnt"), Some(42));
+        assert_eq!(res.scalar_u64("avg"), Some(1));
+        assert_eq!(res.scalar_u64("str"), Some(7));
+        assert_eq!(res.scalar_u64("neg"), None);
+        assert_eq!(res.scalar_u64("null"), None);
+        assert_eq!(res.scalar_u64("obj"), None);
+        assert_eq!(res.scalar_u64("missing"), None);
+        assert_eq!(res.scalar_f64("cnt"), Some(42.0));
+        assert_eq!(res.scalar_f64("avg"), Some(1.5));
+        assert_eq!(res.scalar_f64("str"), Some(7.0));
+        assert_eq!(res.scalar_f64("neg"), Some(-3.0));
+        assert_eq!(res.scalar_f64("null"), None);
+    }
+
+    #[test]
+    fn test_response_columns() {
+        let mut res = Response::default();
+        assert!(res.column("cnt").is_empty());
+        assert!(res.column_u64("cnt").is_empty());
+
+        res.add_hit(&json::json!({"cnt": 42, "host": "a", "took": 1.5}));
+        res.add_hit(&json::json!({"cnt": "7", "host": 3, "took": "2.5"}));
+        res.add_hit(&json::json!({"cnt": -3, "took": null}));
+        res.add_hit(&json::json!({"host": "d", "took": {"a": 1}}));
+
+        assert_eq!(
+            res.column("host"),
+            vec![
+                json::json!("a"),
+                json::json!(3),
+                json::Value::Null,
+                json::json!("d")
+            ]
+        );
+        assert_eq!(res.column_u64("cnt"), vec![Some(42), Some(7), None, None]);
+        assert_eq!(
+            res.column_f64("cnt"),
+            vec![Some(42.0), Some(7.0), Some(-3.0), None]
+        );
+        assert_eq!(
+            res.column_f64("took"),
+            vec![Some(1.5), Some(2.5), None, None]
+        );
+        assert_eq!(
+            res.column_str("host"),
+            vec![Some("a"), None, None, Some("d")]
+        );
+
+        // a column no hit has still has one value per hit
+        assert_eq!(res.column("missing"), vec![json::Value::Null; 4]);
+        assert_eq!(res.column_u64("missing"), vec![None; 4]);
+        assert_eq!(res.column_str("missing"), vec![None; 4]);
+    }
+
+    #[test]
+    fn test_search_event_context_system_source() {
+        let ctx = SearchEventContext::with_system("query_recommendations");
+        let value = json::to_value(&ctx).unwrap();
+        assert_eq!(
+            value,
+            json::json!({"system_source": "query_recommendations"})
+        );
+
+        let proto_ctx: proto::cluster_rpc::SearchEventContext = ctx.clone().into();
+        assert_eq!(
+            proto_ctx.system_source.as_deref(),
+            Some("query_recommendations")
+        );
+        assert_eq!(SearchEventContext::from(proto_ctx), ctx);
+    }
+
+    #[test]
+    fn test_internal_request() {
+        let req = Request::internal("SELECT * FROM t", 10, 20);
+        assert_eq!(req.query.sql, "SELECT * FROM t");
+        assert_eq!(req.query.from, 0);
+        assert_eq!(req.query.size, -1);
+        assert_eq!(req.query.start_time, 10);
+        assert_eq!(req.query.end_time, 20);
+        assert_eq!(req.query.track_total_hits, TrackTotalHits::Off);
+        assert_eq!(req.encoding, RequestEncoding::Empty);
+        assert!(!req.use_cache);
+        assert!(req.search_type.is_none());
+        assert!(req.local_mode.is_none());
+        assert_eq!(req.search_priority, SearchPriority::Normal);
+    }
+
+    #[test]
+    fn test_search_priority_serde() {
+        let req: Request =
+            json::from_value(json::json!({"query": {"sql": "SELECT * FROM t"}})).unwrap();
+        assert_eq!(req.search_priority, SearchPriority::Normal);
+
+        let req: Request = json::from_value(
+            json::json!({"query": {"sql": "SELECT * FROM t"}, "search_priority": "low"}),
+        )
+        .unwrap();
+        assert_eq!(req.search_priority, SearchPriority::Low);
+        assert_eq!(
+            json::to_value(&req).unwrap()["search_priority"],
+            json::json!("low")
+        );
+    }
+
     #[test]
     fn test_request_encoding() {
         let req = json::json!(
@@ -1894,6 +2266,29 @@ mod tests {
         assert_eq!(req.query.sql, "select * from test");
     }
 
+    #[test]
+    fn test_request_encoding_round_trip() {
+        let sql = "SELECT count(*) FROM \"default\" WHERE log LIKE '%a+b/c=d%'";
+        let mut encoded = Request::internal(base64::encode_url(sql), 0, 10);
+        encoded.encoding = RequestEncoding::Base64;
+        let mut plain = Request::internal(sql, 0, 10);
+        encoded.decode().unwrap();
+        plain.decode().unwrap();
+        assert_eq!(encoded.query.sql, plain.query.sql);
+        assert_eq!(encoded.encoding, RequestEncoding::Empty);
+        // decoding twice is a no-op
+        encoded.decode().unwrap();
+        assert_eq!(encoded.query.sql, plain.query.sql);
+    }
+
+    #[test]
+    fn test_request_encoding_malformed() {
+        let mut req = Request::internal("not base64!", 0, 10);
+        req.encoding = RequestEncoding::Base64;
+        let err = req.decode().unwrap_err();
+        assert!(err.to_string().contains("base64 decode error"));
+    }
+
     #[test]
     fn test_request_no_encoding() {
         let req = json::json!(
@@ -1938,7 +2333,7 @@ mod tests {
         assert_eq!(query.end_time, 0);
         assert!(!query.quick_mode);
         assert_eq!(query.query_type, "");
-        assert!(!query.track_total_hits);
+        assert_eq!(query.track_total_hits, TrackTotalHits::Off);
         assert!(!query.uses_zo_fn);
         assert!(query.query_fn.is_none());
         assert!(query.action_id.is_none());
@@ -2375,7 +2770,7 @@ mod tests {
             size: 20,
             start_time: 100,
             end_time: 200,
-            track_total_hits: true,
+            track_total_hits: TrackTotalHits::Exact,
             uses_zo_fn: true,
             query_fn: Some("test_fn".to_string()),
             action_id: Some("action123".to_string()),
@@ -2393,6 +2788,7 @@ mod tests {
         assert_eq!(cluster_query.start_time, 100);
         assert_eq!(cluster_query.end_time, 200);
         assert!(cluster_query.track_total_hits);
+        assert_eq!(cluster_query.track_total_hits_limit, 0);
         assert!(cluster_query.uses_zo_fn);
         assert_eq!(cluster_query.query_fn, "test_fn");
         assert_eq!(cluster_query.action_id, "action123");
@@ -2400,6 +2796,51 @@ mod tests {
         assert_eq!(cluster_query.histogram_interval, 3600);
     }
 
+    #[test]
+    fn test_track_total_hits_serde() {
+        let cases = [
+            ("false", TrackTotalHits::Off),
+            ("true", TrackTotalHits::Exact),
+            ("0", TrackTotalHits::Off),
+            ("10000", TrackTotalHits::UpTo(10000)),
+        ];
+        for (value, expected) in cases {
+            let query: Query =
+                json::from_str(&format!(r#"{{"sql": "", "track_total_hits": {value}}}"#)).unwrap();
+            assert_eq!(query.track_total_hits, expected);
+        }
+        assert!(json::from_str::<Query>(r#"{"sql": "", "track_total_hits": -1}"#).is_err());
+
+        assert_eq!(json::to_string(&TrackTotalHits::Off).unwrap(), "false");
+        assert_eq!(json::to_string(&TrackTotalHits::Exact).unwrap(), "true");
+        assert_eq!(json::to_string(&TrackTotalHits::UpTo(5)).unwrap(), "5");
+    }
+
+    #[test]
+    fn test_track_total_hits_modes() {
+        let query = Query {
+            track_total_hits: TrackTotalHits::UpTo(1000),
+            ..Default::default()
+        };
+        let cluster_query: cluster_rpc::SearchQuery = query.into();
+        assert!(cluster_query.track_total_hits);
+        assert_eq!(cluster_query.track_total_hits_limit, 1000);
+        assert_eq!(
+            TrackTotalHits::from_proto(
+                cluster_query.track_total_hits,
+                cluster_query.track_total_hits_limit
+            ),
+            TrackTotalHits::UpTo(1000)
+        );
+        assert_eq!(TrackTotalHits::from_proto(true, 0), TrackTotalHits::Exact);
+        assert_eq!(TrackTotalHits::from_proto(false, 1000), TrackTotalHits::Off);
+
+        assert_eq!(TrackTotalHits::Off.is_exact(10), None);
+        assert_eq!(TrackTotalHits::Exact.is_exact(10), Some(true));
+        assert_eq!(TrackTotalHits::UpTo(1000).is_exact(999), Some(true));
+        assert_eq!(TrackTotalHits::UpTo(1000).is_exact(1000), Some(false));
+    }
+
     #[test]
     fn test_scan_stats_conversions() {
         let stats = ScanStats {
@@ -2534,7 +2975,7 @@ mod tests {
         assert_eq!(first_req.query.size, 25);
         assert!(first_req.query.quick_mode);
         assert_eq!(first_req.query.query_type, "test");
-        assert!(first_req.query.track_total_hits);
+        assert_eq!(first_req.query.track_total_hits, TrackTotalHits::Exact);
         assert!(first_req.query.uses_zo_fn);
         assert!(first_req.query.skip_wal);
         assert_eq!(first_req.encoding, RequestEncoding::Base64);
@@ -392,6 +392,28 @@ pub struct StreamStats {
     pub index_size: f64,
 }
 
+/// How much data a stream holds, summarized from its [`StreamStats`]. A stream with no data has
+/// all fields zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct StreamSize {
+    pub doc_count: i64,
+    /// Uncompressed size of the stream's data, in bytes.
+    pub storage_bytes: i64,
+    pub min_ts: i64,
+    pub max_ts: i64,
+}
+
+impl From<&StreamStats> for StreamSize {
+    fn from(stats: &StreamStats) -> Self {
+        Self {
+            doc_count: stats.doc_num,
+            storage_bytes: stats.storage_size as i64,
+            min_ts: stats.doc_time_min,
+            max_ts: stats.doc_time_max,
+        }
+    }
+}
+
 impl StreamStats {
     /// Returns true iff [start, end] time range intersects with the stream's
     /// time range.
@@ -658,6 +658,27 @@ pub static META_NUM_USERS_TOTAL: Lazy<IntGaugeVec> = Lazy::new(|| {
     )
     .expect("Metric created")
 });
+pub static META_NUM_ACTIVE_SESSIONS_TOTAL: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "meta_num_active_sessions_total",
+            "Metadata active session total",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+pub static META_NUM_ACTIVE_SESSIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new("meta_num_active_sessions", "Metadata active session nums")
+            .namespace(NAMESPACE)
+            .const_labels(create_const_labels()),
+        &["organization"],
+    )
+    .expect("Metric created")
+});
 pub static META_NUM_USERS: Lazy<IntGaugeVec> = Lazy::new(|| {
     IntGaugeVec::new(
         Opts::new("meta_num_users", "Metadata user nums")
@@ -1297,6 +1318,20 @@ pub static SELF_REPORTING_QUEUE_DEPTH: Lazy<IntGaugeVec> = Lazy::new(|| {
     .expect("Metric created")
 });
 
+// query recommendations metrics
+pub static QUERY_RECOMMENDATIONS_BREAKER_STATE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    IntGaugeVec::new(
+        Opts::new(
+            "query_recommendations_breaker_state",
+            "State of the circuit breaker of the scheduled query recommendation runs: 0 closed, 1 half open, 2 open",
+        )
+        .namespace(NAMESPACE)
+        .const_labels(create_const_labels()),
+        &[],
+    )
+    .expect("Metric created")
+});
+
 fn register_metrics(registry: &Registry) {
     // http latency
     registry
@@ -1474,6 +1509,12 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(META_NUM_USERS.clone()))
         .expect("Metric registered");
+    registry
+        .register(Box::new(META_NUM_ACTIVE_SESSIONS_TOTAL.clone()))
+        .expect("Metric registered");
+    registry
+        .register(Box::new(META_NUM_ACTIVE_SESSIONS.clone()))
+        .expect("Metric registered");
     registry
         .register(Box::new(META_NUM_FUNCTIONS.clone()))
         .expect("Metric registered");
@@ -1637,6 +1678,11 @@ fn register_metrics(registry: &Registry) {
     registry
         .register(Box::new(SELF_REPORTING_QUEUE_DEPTH.clone()))
         .expect("Metric registered");
+
+    // query recommendations metrics
+    registry
+        .register(Box::new(QUERY_RECOMMENDATIONS_BREAKER_STATE.clone()))
+        .expect("Metric registered");
 }
 
 pub fn create_const_labels() -> HashMap<String, String> {
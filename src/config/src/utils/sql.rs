@@ -18,15 +18,16 @@ use std::ops::ControlFlow;
 use hashbrown::HashSet;
 use sqlparser::{
     ast::{
-        DuplicateTreatment, Expr, Function, FunctionArg, FunctionArgExpr, FunctionArgumentList,
-        FunctionArguments, GroupByExpr, Query, SelectItem, SetExpr, SetQuantifier, Statement,
-        TableFactor, Visit, Visitor,
+        BinaryOperator, DuplicateTreatment, Expr, Function, FunctionArg, FunctionArgExpr,
+        FunctionArgumentList, FunctionArguments, GroupByExpr, Query, SelectItem, SetExpr,
+        SetQuantifier, Statement, TableFactor, UnaryOperator, Value, Visit, Visitor,
+        visit_expressions_mut,
     },
     dialect::GenericDialect,
     parser::Parser,
 };
 
-use crate::TIMESTAMP_COL_NAME;
+use crate::{INDEX_FIELD_NAME_FOR_ALL, TIMESTAMP_COL_NAME};
 
 pub const AGGREGATE_UDF_LIST: [&str; 17] = [
     "min",
@@ -122,6 +123,40 @@ pub fn is_timestamp_selected(query: &str) -> Result<bool, sqlparser::parser::Par
     Ok(false)
 }
 
+/// Returns a canonical fingerprint of a query, so that queries which only differ in literal values
+/// or formatting map to the same string.
+///
+/// Literals are replaced with `?`, IN lists of any length collapse to a single placeholder, and
+/// whitespace is normalized by re-rendering the parsed statement. Unparseable SQL is returned
+/// as-is.
+pub fn fingerprint_sql(sql: &str) -> String {
+    let mut statements = match Parser::parse_sql(&GenericDialect {}, sql) {
+        Ok(statements) => statements,
+        Err(_) => return sql.to_string(),
+    };
+    let _ = visit_expressions_mut(&mut statements, |expr| {
+        match expr {
+            Expr::Value(value) => value.value = Value::Placeholder("?".to_string()),
+            Expr::InList { list, .. } => {
+                *list = vec![Expr::value(Value::Placeholder("?".to_string()))]
+            }
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    statements
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Quotes an identifier such as a stream or field name so it can be safely interpolated into SQL.
+/// Embedded double quotes are escaped by doubling them.
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
 fn is_aggregate_in_select(query: &Query) -> bool {
     if let SetExpr::Select(ref select) = *query.body {
         if select.distinct.is_some() {
@@ -220,6 +255,273 @@ fn is_multi_search_eligible_for_histogram(
     Ok(false)
 }
 
+/// A condition on a column in the WHERE clause of a query. See [`extract_predicates`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Predicate {
+    pub column: String,
+    pub op: PredicateOp,
+    /// True when the column is compared with literal values only, e.g. `host = 'a'` but not
+    /// `host = other_host` or `host IN (SELECT ...)`.
+    pub is_literal: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PredicateOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    In,
+    NotIn,
+    Between,
+    NotBetween,
+    Like,
+    NotLike,
+    ILike,
+    NotILike,
+    IsNull,
+    IsNotNull,
+    /// Full text search with `match_all` or `fuzzy_match_all`, recorded on the
+    /// [`INDEX_FIELD_NAME_FOR_ALL`] column.
+    MatchAll,
+    /// A function that takes the column as its first argument, such as `str_match` or
+    /// `re_match`, by its lowercase name.
+    Function(String),
+}
+
+/// Returns the predicates of the WHERE clauses of a query, including those of subqueries, in
+/// order of appearance, e.g. `host Eq` and `code Gt` for `WHERE host = 'a' OR code > 500`.
+///
+/// AND, OR, NOT and parentheses are looked through, so the predicates under a NOT keep their
+/// own operator. A comparison between two columns is recorded on the left one. Unparseable SQL
+/// has no predicates.
+pub fn extract_predicates(query: &str) -> Vec<Predicate> {
+    let ast = match Parser::parse_sql(&GenericDialect {}, query) {
+        Ok(ast) => ast,
+        Err(e) => {
+            log::warn!("extract_predicates: failed to parse sql: {query}, err: {e}");
+            return vec![];
+        }
+    };
+    predicates_of(&ast)
+}
+
+/// Returns the columns that the WHERE clauses of a query compare for equality with a literal,
+/// e.g. `host` and `status` for `WHERE host = 'a' AND status IN (200, 404)`. Each column is
+/// returned once, in order of appearance. See [`extract_predicates`].
+pub fn equality_filter_columns(query: &str) -> Vec<String> {
+    let mut columns: Vec<String> = vec![];
+    for predicate in extract_predicates(query) {
+        if matches!(predicate.op, PredicateOp::Eq | PredicateOp::In)
+            && predicate.is_literal
+            && !columns.contains(&predicate.column)
+        {
+            columns.push(predicate.column);
+        }
+    }
+    columns
+}
+
+fn predicates_of(ast: &[Statement]) -> Vec<Predicate> {
+    let mut visitor = PredicateVisitor::default();
+    for statement in ast.iter() {
+        let _ = statement.visit(&mut visitor);
+    }
+    visitor.predicates
+}
+
+#[derive(Default)]
+struct PredicateVisitor {
+    predicates: Vec<Predicate>,
+}
+
+impl PredicateVisitor {
+    fn visit_predicate(&mut self, expr: &Expr) {
+        match expr {
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::And | BinaryOperator::Or,
+                right,
+            } => {
+                self.visit_predicate(left);
+                self.visit_predicate(right);
+            }
+            Expr::BinaryOp { left, op, right } => {
+                let Some(op) = comparison_op(op) else {
+                    return;
+                };
+                if column_name(left).is_some() {
+                    self.add(left, op, is_literal(right));
+                } else if column_name(right).is_some() {
+                    self.add(right, op.flip(), is_literal(left));
+                }
+            }
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                expr,
+            }
+            | Expr::Nested(expr) => self.visit_predicate(expr),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let op = if *negated {
+                    PredicateOp::NotIn
+                } else {
+                    PredicateOp::In
+                };
+                self.add(expr, op, list.iter().all(is_literal));
+            }
+            Expr::InSubquery { expr, negated, .. } => {
+                let op = if *negated {
+                    PredicateOp::NotIn
+                } else {
+                    PredicateOp::In
+                };
+                self.add(expr, op, false);
+            }
+            Expr::Between {
+                expr,
+                negated,
+                low,
+                high,
+            } => {
+                let op = if *negated {
+                    PredicateOp::NotBetween
+                } else {
+                    PredicateOp::Between
+                };
+                self.add(expr, op, is_literal(low) && is_literal(high));
+            }
+            Expr::Like {
+                negated,
+                expr,
+                pattern,
+                ..
+            } => {
+                let op = if *negated {
+                    PredicateOp::NotLike
+                } else {
+                    PredicateOp::Like
+                };
+                self.add(expr, op, is_literal(pattern));
+            }
+            Expr::ILike {
+                negated,
+                expr,
+                pattern,
+                ..
+            } => {
+                let op = if *negated {
+                    PredicateOp::NotILike
+                } else {
+                    PredicateOp::ILike
+                };
+                self.add(expr, op, is_literal(pattern));
+            }
+            Expr::IsNull(expr) => self.add(expr, PredicateOp::IsNull, true),
+            Expr::IsNotNull(expr) => self.add(expr, PredicateOp::IsNotNull, true),
+            Expr::Function(func) => self.visit_function(func),
+            _ => {}
+        }
+    }
+
+    fn visit_function(&mut self, func: &Function) {
+        let name = func.name.to_string().to_lowercase();
+        let args = match &func.args {
+            FunctionArguments::List(list) => list
+                .args
+                .iter()
+                .filter_map(|arg| match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Some(expr),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            _ => vec![],
+        };
+        if name == "match_all" || name == "fuzzy_match_all" {
+            self.predicates.push(Predicate {
+                column: INDEX_FIELD_NAME_FOR_ALL.to_string(),
+                op: PredicateOp::MatchAll,
+                is_literal: args.iter().all(|arg| is_literal(arg)),
+            });
+        } else if let Some((column, rest)) = args.split_first() {
+            self.add(
+                column,
+                PredicateOp::Function(name),
+                rest.iter().all(|arg| is_literal(arg)),
+            );
+        }
+    }
+
+    fn add(&mut self, expr: &Expr, op: PredicateOp, is_literal: bool) {
+        if let Some(column) = column_name(expr) {
+            self.predicates.push(Predicate {
+                column: column.to_string(),
+                op,
+                is_literal,
+            });
+        }
+    }
+}
+
+impl Visitor for PredicateVisitor {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        if let SetExpr::Select(select) = query.body.as_ref()
+            && let Some(selection) = &select.selection
+        {
+            self.visit_predicate(selection);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl PredicateOp {
+    /// Returns the operator with its operands swapped, so `5 < code` becomes `code > 5`.
+    fn flip(self) -> Self {
+        match self {
+            PredicateOp::Lt => PredicateOp::Gt,
+            PredicateOp::LtEq => PredicateOp::GtEq,
+            PredicateOp::Gt => PredicateOp::Lt,
+            PredicateOp::GtEq => PredicateOp::LtEq,
+            op => op,
+        }
+    }
+}
+
+fn comparison_op(op: &BinaryOperator) -> Option<PredicateOp> {
+    match op {
+        BinaryOperator::Eq => Some(PredicateOp::Eq),
+        BinaryOperator::NotEq => Some(PredicateOp::NotEq),
+        BinaryOperator::Lt => Some(PredicateOp::Lt),
+        BinaryOperator::LtEq => Some(PredicateOp::LtEq),
+        BinaryOperator::Gt => Some(PredicateOp::Gt),
+        BinaryOperator::GtEq => Some(PredicateOp::GtEq),
+        _ => None,
+    }
+}
+
+fn column_name(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Identifier(ident) => Some(&ident.value),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|ident| ident.value.as_str()),
+        _ => None,
+    }
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value(_) => true,
+        Expr::UnaryOp { expr, .. } | Expr::Nested(expr) => is_literal(expr),
+        _ => false,
+    }
+}
+
 // Check if has group_by
 fn has_group_by(query: &Query) -> bool {
     if let SetExpr::Select(ref select) = *query.body {
@@ -1137,6 +1439,174 @@ mod tests {
         assert_eq!(is_union_all, true);
     }
 
+    #[test]
+    fn test_fingerprint_sql() {
+        assert_eq!(
+            fingerprint_sql("SELECT * FROM logs WHERE status=200"),
+            fingerprint_sql("select *  from logs\nwhere status = 500")
+        );
+        assert_eq!(
+            fingerprint_sql("SELECT * FROM logs WHERE status = 200"),
+            "SELECT * FROM logs WHERE status = ?"
+        );
+        assert_eq!(
+            fingerprint_sql("SELECT * FROM logs WHERE host = 'a' AND code IN (1, 2, 3)"),
+            fingerprint_sql("SELECT * FROM logs WHERE host = 'b' AND code IN (4)")
+        );
+        assert_ne!(
+            fingerprint_sql("SELECT * FROM logs WHERE status = 200"),
+            fingerprint_sql("SELECT * FROM logs WHERE code = 200")
+        );
+        // unparseable SQL falls back to the raw string
+        assert_eq!(fingerprint_sql("INVALID SQL"), "INVALID SQL");
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("status"), r#""status""#);
+        assert_eq!(quote_identifier("k8s.pod.name"), r#""k8s.pod.name""#);
+        assert_eq!(quote_identifier("user agent"), r#""user agent""#);
+        assert_eq!(quote_identifier(r#"a"b"#), r#""a""b""#);
+
+        // a quoted identifier is parsed back as a single column
+        let sql = format!(
+            "SELECT approx_distinct({}) AS d0 FROM {}",
+            quote_identifier(r#"a" FROM x; --"#),
+            quote_identifier("my stream")
+        );
+        let statements = Parser::parse_sql(&GenericDialect {}, &sql).unwrap();
+        assert_eq!(statements.len(), 1);
+    }
+
+    #[test]
+    fn test_equality_filter_columns() {
+        assert_eq!(
+            equality_filter_columns(
+                "SELECT * FROM logs WHERE host = 'a' AND (status IN (200, 404) OR 'x' = t.env)"
+            ),
+            vec!["host", "status", "env"]
+        );
+        // range filters, negated IN lists and column comparisons are not equality filters
+        assert!(
+            equality_filter_columns(
+                "SELECT * FROM logs WHERE code > 500 AND host NOT IN ('a') AND a = b"
+            )
+            .is_empty()
+        );
+        // columns are returned once, including those from subqueries
+        assert_eq!(
+            equality_filter_columns(
+                "SELECT * FROM (SELECT * FROM logs WHERE host = 'a') WHERE host = 'b'"
+            ),
+            vec!["host"]
+        );
+        // unparseable SQL has no equality filters
+        assert!(equality_filter_columns("INVALID SQL").is_empty());
+    }
+
+    fn predicate(column: &str, op: PredicateOp, is_literal: bool) -> Predicate {
+        Predicate {
+            column: column.to_string(),
+            op,
+            is_literal,
+        }
+    }
+
+    #[test]
+    fn test_extract_predicates_and_or() {
+        assert_eq!(
+            extract_predicates(
+                "SELECT * FROM logs WHERE host = 'a' AND (code >= 500 OR t.level != 'info')"
+            ),
+            vec![
+                predicate("host", PredicateOp::Eq, true),
+                predicate("code", PredicateOp::GtEq, true),
+                predicate("level", PredicateOp::NotEq, true),
+            ]
+        );
+        // literals on the left flip the comparison, columns compared with columns aren't literal
+        assert_eq!(
+            extract_predicates("SELECT * FROM logs WHERE 500 < code OR a = b"),
+            vec![
+                predicate("code", PredicateOp::Gt, true),
+                predicate("a", PredicateOp::Eq, false),
+            ]
+        );
+        assert!(extract_predicates("SELECT * FROM logs").is_empty());
+    }
+
+    #[test]
+    fn test_extract_predicates_in_between() {
+        assert_eq!(
+            extract_predicates(
+                "SELECT * FROM logs WHERE code IN (200, -1) AND host NOT IN ('a', other) \
+                 AND _timestamp BETWEEN 1 AND 2 AND size NOT BETWEEN 0 AND max_size"
+            ),
+            vec![
+                predicate("code", PredicateOp::In, true),
+                predicate("host", PredicateOp::NotIn, false),
+                predicate("_timestamp", PredicateOp::Between, true),
+                predicate("size", PredicateOp::NotBetween, false),
+            ]
+        );
+        // the subquery's own predicates are extracted too
+        assert_eq!(
+            extract_predicates(
+                "SELECT * FROM logs WHERE host IN (SELECT host FROM hosts WHERE env = 'prod')"
+            ),
+            vec![
+                predicate("host", PredicateOp::In, false),
+                predicate("env", PredicateOp::Eq, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_predicates_like_match_all() {
+        assert_eq!(
+            extract_predicates(
+                "SELECT * FROM logs WHERE match_all('error') AND msg LIKE '%timeout%' \
+                 AND msg NOT ILIKE 'debug%' AND str_match(k8s_pod, 'api') \
+                 AND fuzzy_match_all('eror', 1) AND trace_id IS NOT NULL AND span IS NULL"
+            ),
+            vec![
+                predicate(INDEX_FIELD_NAME_FOR_ALL, PredicateOp::MatchAll, true),
+                predicate("msg", PredicateOp::Like, true),
+                predicate("msg", PredicateOp::NotILike, true),
+                predicate(
+                    "k8s_pod",
+                    PredicateOp::Function("str_match".to_string()),
+                    true
+                ),
+                predicate(INDEX_FIELD_NAME_FOR_ALL, PredicateOp::MatchAll, true),
+                predicate("trace_id", PredicateOp::IsNotNull, true),
+                predicate("span", PredicateOp::IsNull, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_predicates_nested() {
+        assert_eq!(
+            extract_predicates(
+                "SELECT * FROM logs WHERE NOT (host = 'a' OR (code < 300 AND NOT msg LIKE 'x%'))"
+            ),
+            vec![
+                predicate("host", PredicateOp::Eq, true),
+                predicate("code", PredicateOp::Lt, true),
+                predicate("msg", PredicateOp::Like, true),
+            ]
+        );
+        // expressions over columns aren't columns
+        assert!(extract_predicates("SELECT * FROM logs WHERE lower(host) = 'a'").is_empty());
+    }
+
+    #[test]
+    fn test_extract_predicates_unparseable() {
+        assert!(extract_predicates("SELECT * FROM logs WHERE").is_empty());
+        assert!(extract_predicates("INVALID SQL").is_empty());
+    }
+
     #[test]
     fn test_is_explain_query() {
         // Test EXPLAIN query
@@ -19,7 +19,7 @@ use actix_web::{HttpRequest, HttpResponse, get, web};
 use chrono::{Duration, Utc};
 use config::{
     meta::{
-        search::{Query, Request as SearchRequest},
+        search::{Query, Request as SearchRequest, TrackTotalHits},
         self_reporting::usage::TRIGGERS_STREAM,
         stream::StreamType,
     },
@@ -392,8 +392,8 @@ pub async fn get_alert_history(
             start_time,
             end_time,
             from: 0,
-            size: 1,                // We only need the count, not the actual records
-            track_total_hits: true, // This triggers the COUNT(*) rewrite
+            size: 1, // We only need the count, not the actual records
+            track_total_hits: TrackTotalHits::Exact, // This triggers the COUNT(*) rewrite
             ..Default::default()
         },
         regions: vec![],
@@ -451,7 +451,7 @@ pub async fn get_alert_history(
             end_time,
             from: 0,
             size,
-            track_total_hits: false, // We already have the total, just get the data
+            track_total_hits: TrackTotalHits::Off, // We already have the total, just get the data
             ..Default::default()
         },
         regions: vec![],
@@ -41,6 +41,7 @@ pub mod patterns;
 pub mod pipeline;
 pub mod pipelines;
 pub mod promql;
+pub mod query_recommendations;
 pub mod ratelimit;
 #[cfg(feature = "enterprise")]
 pub mod re_pattern;
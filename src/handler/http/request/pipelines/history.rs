@@ -18,7 +18,7 @@ use chrono::{Duration, Utc};
 use config::{
     META_ORG_ID,
     meta::{
-        search::{Query, Request as SearchRequest},
+        search::{Query, Request as SearchRequest, TrackTotalHits},
         self_reporting::usage::TRIGGERS_STREAM,
         stream::StreamType,
     },
@@ -381,8 +381,8 @@ pub async fn get_pipeline_history(
             start_time,
             end_time,
             from: 0,
-            size: 1,                // We only need the count, not the actual records
-            track_total_hits: true, // This triggers the COUNT(*) rewrite
+            size: 1, // We only need the count, not the actual records
+            track_total_hits: TrackTotalHits::Exact, // This triggers the COUNT(*) rewrite
             ..Default::default()
         },
         regions: vec![],
@@ -441,7 +441,7 @@ pub async fn get_pipeline_history(
             end_time,
             from: 0,
             size,
-            track_total_hits: false, // We already have the total, just get the data
+            track_total_hits: TrackTotalHits::Off, // We already have the total, just get the data
             ..Default::default()
         },
         regions: vec![],
@@ -0,0 +1,701 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Query recommendation API endpoints (Enterprise feature)
+
+use actix_web::{HttpResponse, get, post, put, web};
+#[cfg(feature = "enterprise")]
+use infra::table::recommendations::{RecommendationStatus, StatusChange};
+use serde::Deserialize;
+
+#[cfg(feature = "enterprise")]
+use crate::common::meta::http::HttpResponse as MetaHttpResponse;
+
+#[derive(Debug, Deserialize)]
+pub struct RunQuery {
+    /// Overrides the analysed time window, in hours.
+    pub hours: Option<i64>,
+    /// Returns the recommendations without storing them.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Only analyses this stream.
+    pub stream: Option<String>,
+    /// The type of `stream`, logs by default.
+    #[serde(rename = "type")]
+    pub stream_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    /// Only lists recommendations with this status: open, accepted, dismissed, applied or
+    /// auto_resolved.
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// Format of the export: json, the default, or csv.
+    pub format: Option<String>,
+    /// Only exports recommendations with this status: open, accepted, dismissed, applied or
+    /// auto_resolved.
+    pub status: Option<String>,
+    /// Only exports recommendations of this type.
+    #[serde(rename = "type")]
+    pub recommendation_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageStatsQuery {
+    /// Time window to read the usage of, in hours. The analysed time window by default.
+    pub hours: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyRequest {
+    /// Ids of the accepted recommendations to apply.
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusUpdate {
+    /// The new status: open, accepted or dismissed.
+    pub status: String,
+}
+
+#[cfg(feature = "enterprise")]
+fn parse_status(status: &str) -> Option<RecommendationStatus> {
+    match status {
+        "open" | "accepted" | "dismissed" => status.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Only the stream types whose searches are analysed can be the stream of a run.
+#[cfg(feature = "enterprise")]
+fn parse_stream_type(stream_type: &str) -> Option<config::meta::stream::StreamType> {
+    match stream_type {
+        "logs" | "metrics" | "traces" => Some(config::meta::stream::StreamType::from(stream_type)),
+        _ => None,
+    }
+}
+
+/// Recommendations are only marked as applied by applying them, and as auto resolved by the
+/// recommendation runs, but can be listed by these statuses.
+#[cfg(feature = "enterprise")]
+fn parse_list_status(status: &str) -> Option<RecommendationStatus> {
+    match status {
+        "applied" => Some(RecommendationStatus::Applied),
+        "auto_resolved" => Some(RecommendationStatus::AutoResolved),
+        _ => parse_status(status),
+    }
+}
+
+/// Generate query recommendations for an organization immediately
+#[cfg(feature = "enterprise")]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "RunQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+        ("hours" = Option<i64>, Query, description = "Time window to analyse, in hours"),
+        ("dry_run" = Option<bool>, Query, description = "Return the recommendations without storing them"),
+        ("stream" = Option<String>, Query, description = "Only analyse this stream"),
+        ("type" = Option<String>, Query, description = "Stream type of `stream`: logs, the default, metrics or traces"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = serde_json::Value),
+        (status = 400, description = "Bad request"),
+        (status = 403, description = "Forbidden - Enterprise feature"),
+        (status = 404, description = "Query recommendations are disabled, or the stream doesn't exist"),
+        (status = 409, description = "The run was cancelled by a newer run"),
+        (status = 429, description = "A run of the organization was started too recently"),
+        (status = 500, description = "Internal server error"),
+    ),
+)]
+#[post("/{org_id}/query_recommendations/_run")]
+pub async fn run(
+    org_id: web::Path<String>,
+    query: web::Query<RunQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    use config::{get_config, meta::stream::StreamType, utils::time::now_micros};
+
+    use crate::service::alerts::scheduler::query_optimization_recommendation::{
+        OnDemandRunError, abort_manual_run, interval_micros, run_on_demand, try_start_manual_run,
+    };
+
+    let org_id = org_id.into_inner();
+    let duration = match query.hours {
+        Some(hours) if hours <= 0 => {
+            return Ok(MetaHttpResponse::bad_request(
+                "hours must be a positive number",
+            ));
+        }
+        Some(hours) => Some(interval_micros(hours.saturating_mul(60))),
+        None => None,
+    };
+
+    let cfg = get_config();
+    if !cfg.recommendations.enabled {
+        return Ok(MetaHttpResponse::not_found(
+            "Query recommendations are disabled",
+        ));
+    }
+
+    let stream_type = match query.stream_type.as_deref().map(parse_stream_type) {
+        Some(None) => {
+            return Ok(MetaHttpResponse::bad_request(
+                "type must be one of logs, metrics or traces",
+            ));
+        }
+        Some(Some(stream_type)) => stream_type,
+        None => StreamType::default(),
+    };
+    let stream_filter = match query.stream.as_deref() {
+        Some("") => {
+            return Ok(MetaHttpResponse::bad_request("stream must not be empty"));
+        }
+        Some(stream) => {
+            let schema = infra::schema::get(&org_id, stream, stream_type)
+                .await
+                .unwrap_or(arrow_schema::Schema::empty());
+            if schema.fields().is_empty() {
+                return Ok(MetaHttpResponse::not_found(format!(
+                    "Stream {stream_type}/{stream} not found"
+                )));
+            }
+            Some((stream_type, stream.to_string()))
+        }
+        None => None,
+    };
+
+    // dry runs don't store anything, so they don't count against the cooldown
+    let cooldown = cfg.recommendations.run_cooldown;
+    let started_at = now_micros();
+    if !query.dry_run
+        && let Err(retry_after) = try_start_manual_run(&org_id, started_at, cooldown)
+    {
+        return Ok(MetaHttpResponse::too_many_requests(
+            format!(
+                "Recommendations of org {org_id} were generated less than {cooldown} seconds ago, retry in {retry_after} seconds"
+            ),
+            retry_after,
+        ));
+    }
+
+    let result = run_on_demand(&org_id, duration, query.dry_run, stream_filter).await;
+    if result.is_err() && !query.dry_run {
+        abort_manual_run(&org_id, started_at);
+    }
+    match result {
+        Ok(report) => Ok(MetaHttpResponse::json(report)),
+        Err(e @ OnDemandRunError::Cancelled) => Ok(MetaHttpResponse::conflict(e)),
+        Err(e) => {
+            log::error!("Error generating query recommendations for org {org_id}: {e}");
+            Ok(MetaHttpResponse::internal_error(e))
+        }
+    }
+}
+
+/// Generate query recommendations for an organization immediately (OSS - Not Supported)
+#[cfg(not(feature = "enterprise"))]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "RunQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+    ),
+    responses(
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[post("/{org_id}/query_recommendations/_run")]
+pub async fn run(
+    _org_id: web::Path<String>,
+    _query: web::Query<RunQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Forbidden().json("Enterprise feature not available"))
+}
+
+/// List the stored query recommendations of an organization
+#[cfg(feature = "enterprise")]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ListQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+        ("status" = Option<String>, Query, description = "Filter by status: open, accepted, dismissed, applied or auto_resolved"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = serde_json::Value),
+        (status = 400, description = "Bad request"),
+        (status = 403, description = "Forbidden - Enterprise feature"),
+        (status = 500, description = "Internal server error"),
+    ),
+)]
+#[get("/{org_id}/query_recommendations")]
+pub async fn list(
+    org_id: web::Path<String>,
+    query: web::Query<ListQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let org_id = org_id.into_inner();
+    let status = match query.status.as_deref().map(parse_list_status) {
+        Some(None) => {
+            return Ok(MetaHttpResponse::bad_request(
+                "status must be one of open, accepted, dismissed, applied or auto_resolved",
+            ));
+        }
+        Some(status) => status,
+        None => None,
+    };
+
+    match infra::table::recommendations::list_recommendations(&org_id, status, None).await {
+        Ok(recommendations) => Ok(MetaHttpResponse::json(recommendations)),
+        Err(e) => {
+            log::error!("Error listing query recommendations for org {org_id}: {e}");
+            Ok(MetaHttpResponse::internal_error(e))
+        }
+    }
+}
+
+/// List the stored query recommendations of an organization (OSS - Not Supported)
+#[cfg(not(feature = "enterprise"))]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ListQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+    ),
+    responses(
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[get("/{org_id}/query_recommendations")]
+pub async fn list(
+    _org_id: web::Path<String>,
+    _query: web::Query<ListQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Forbidden().json("Enterprise feature not available"))
+}
+
+/// Export the stored query recommendations of an organization as JSON or CSV
+///
+/// The CSV export has a column for each field of the recommendations, followed by a
+/// `payload.<key>` column for each key of their payloads.
+#[cfg(feature = "enterprise")]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ExportQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+        ("format" = Option<String>, Query, description = "Export format: json (default) or csv"),
+        ("status" = Option<String>, Query, description = "Filter by status: open, accepted, dismissed, applied or auto_resolved"),
+        ("type" = Option<String>, Query, description = "Filter by recommendation type"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = String),
+        (status = 400, description = "Bad request"),
+        (status = 403, description = "Forbidden - Enterprise feature"),
+        (status = 500, description = "Internal server error"),
+    ),
+)]
+#[get("/{org_id}/query_recommendations/_export")]
+pub async fn export(
+    org_id: web::Path<String>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    use crate::service::alerts::scheduler::query_optimization_recommendation::recommendations_to_csv;
+
+    let org_id = org_id.into_inner();
+    let csv = match query.format.as_deref() {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(_) => return Ok(MetaHttpResponse::bad_request("format must be json or csv")),
+    };
+    let status = match query.status.as_deref().map(parse_list_status) {
+        Some(None) => {
+            return Ok(MetaHttpResponse::bad_request(
+                "status must be one of open, accepted, dismissed, applied or auto_resolved",
+            ));
+        }
+        Some(status) => status,
+        None => None,
+    };
+
+    let recommendations = match infra::table::recommendations::list_recommendations(
+        &org_id,
+        status,
+        query.recommendation_type.as_deref(),
+    )
+    .await
+    {
+        Ok(recommendations) => recommendations,
+        Err(e) => {
+            log::error!("Error exporting query recommendations for org {org_id}: {e}");
+            return Ok(MetaHttpResponse::internal_error(e));
+        }
+    };
+    if !csv {
+        return Ok(MetaHttpResponse::json(recommendations));
+    }
+    match recommendations_to_csv(&recommendations) {
+        Ok(body) => Ok(HttpResponse::Ok()
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{org_id}_query_recommendations.csv\""),
+            ))
+            .content_type("text/csv")
+            .body(body)),
+        Err(e) => {
+            log::error!("Error exporting query recommendations for org {org_id}: {e}");
+            Ok(MetaHttpResponse::internal_error(e))
+        }
+    }
+}
+
+/// Export the stored query recommendations of an organization (OSS - Not Supported)
+#[cfg(not(feature = "enterprise"))]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ExportQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+    ),
+    responses(
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[get("/{org_id}/query_recommendations/_export")]
+pub async fn export(
+    _org_id: web::Path<String>,
+    _query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Forbidden().json("Enterprise feature not available"))
+}
+
+/// Accept or dismiss a query recommendation
+///
+/// Dismissed recommendations are not generated again until the dismiss cooldown has passed.
+#[cfg(feature = "enterprise")]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "UpdateQueryRecommendationStatus",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+        ("id" = String, Path, description = "Recommendation ID"),
+    ),
+    request_body(content = Object, description = "New status: open, accepted or dismissed", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success"),
+        (status = 400, description = "Bad request"),
+        (status = 403, description = "Forbidden - Enterprise feature"),
+        (status = 404, description = "Not found"),
+        (status = 409, description = "Conflict - the recommendation was applied or auto resolved, or another one is open for the same field"),
+        (status = 500, description = "Internal server error"),
+    ),
+)]
+#[put("/{org_id}/query_recommendations/{id}/status")]
+pub async fn update_status(
+    path: web::Path<(String, String)>,
+    body: web::Json<StatusUpdate>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (org_id, id) = path.into_inner();
+    let Some(status) = parse_status(&body.status) else {
+        return Ok(MetaHttpResponse::bad_request(
+            "status must be one of open, accepted or dismissed",
+        ));
+    };
+
+    match infra::table::recommendations::update_status(&org_id, &id, status).await {
+        Ok(StatusChange::Updated) => Ok(MetaHttpResponse::ok("Recommendation status updated")),
+        Ok(StatusChange::NotFound) => Ok(MetaHttpResponse::not_found("Recommendation not found")),
+        Ok(StatusChange::Closed(current)) => Ok(MetaHttpResponse::conflict(format!(
+            "Recommendation is {current} and its status can no longer change"
+        ))),
+        Ok(StatusChange::Duplicate) => Ok(MetaHttpResponse::conflict(
+            "Another recommendation for the same field is open, accepted or applied",
+        )),
+        Err(e) => {
+            log::error!("Error updating query recommendation {id} for org {org_id}: {e}");
+            Ok(MetaHttpResponse::internal_error(e))
+        }
+    }
+}
+
+/// Accept or dismiss a query recommendation (OSS - Not Supported)
+#[cfg(not(feature = "enterprise"))]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "UpdateQueryRecommendationStatus",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+        ("id" = String, Path, description = "Recommendation ID"),
+    ),
+    responses(
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[put("/{org_id}/query_recommendations/{id}/status")]
+pub async fn update_status(
+    _path: web::Path<(String, String)>,
+    _body: web::Json<StatusUpdate>,
+) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Forbidden().json("Enterprise feature not available"))
+}
+
+/// Apply accepted query recommendations to the settings of their streams
+///
+/// Each recommendation is applied on its own, and the response holds the outcome of each one.
+#[cfg(feature = "enterprise")]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ApplyQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+    ),
+    request_body(content = Object, description = "Ids of the accepted recommendations to apply", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Success", body = serde_json::Value),
+        (status = 400, description = "Bad request"),
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[post("/{org_id}/query_recommendations/_apply")]
+pub async fn apply(
+    org_id: web::Path<String>,
+    body: web::Json<ApplyRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    use crate::service::alerts::scheduler::query_optimization_recommendation::apply_recommendations;
+
+    if body.ids.is_empty() {
+        return Ok(MetaHttpResponse::bad_request("ids must not be empty"));
+    }
+    let org_id = org_id.into_inner();
+    Ok(MetaHttpResponse::json(
+        apply_recommendations(&org_id, &body.ids).await,
+    ))
+}
+
+/// Apply accepted query recommendations to the settings of their streams (OSS - Not Supported)
+#[cfg(not(feature = "enterprise"))]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ApplyQueryRecommendations",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+    ),
+    responses(
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[post("/{org_id}/query_recommendations/_apply")]
+pub async fn apply(
+    _org_id: web::Path<String>,
+    _body: web::Json<ApplyRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Forbidden().json("Enterprise feature not available"))
+}
+
+/// Make the next scheduled recommendation run read the usage of its whole time window again
+///
+/// Scheduled runs only read the usage recorded since the previous run. The scheduled runs cover
+/// every organization, so their watermark can only be reset from the meta organization.
+#[cfg(feature = "enterprise")]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ResetQueryRecommendationsWatermark",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID, must be the meta organization"),
+    ),
+    responses(
+        (status = 200, description = "Success"),
+        (status = 403, description = "Forbidden - Enterprise feature, or not the meta organization"),
+        (status = 500, description = "Internal server error"),
+    ),
+)]
+#[post("/{org_id}/query_recommendations/_reset_watermark")]
+pub async fn reset_watermark(org_id: web::Path<String>) -> Result<HttpResponse, actix_web::Error> {
+    use crate::service::alerts::scheduler::query_optimization_recommendation::reset_usage_watermark;
+
+    let org_id = org_id.into_inner();
+    if org_id != config::META_ORG_ID {
+        return Ok(MetaHttpResponse::forbidden(
+            "The usage watermark can only be reset from the meta organization",
+        ));
+    }
+    match reset_usage_watermark().await {
+        Ok(()) => Ok(MetaHttpResponse::ok("Usage watermark reset")),
+        Err(e) => {
+            log::error!("Error resetting the usage watermark of query recommendations: {e}");
+            Ok(MetaHttpResponse::internal_error(e))
+        }
+    }
+}
+
+/// Make the next scheduled recommendation run read the usage of its whole time window again
+/// (OSS - Not Supported)
+#[cfg(not(feature = "enterprise"))]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ResetQueryRecommendationsWatermark",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+    ),
+    responses(
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[post("/{org_id}/query_recommendations/_reset_watermark")]
+pub async fn reset_watermark(_org_id: web::Path<String>) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Forbidden().json("Enterprise feature not available"))
+}
+
+/// List the latency stats of the queries searched in an organization
+///
+/// These are the stats the recommendation runs analyse the latency of: the number of queries of
+/// each query fingerprint, and their p95 and max response times. The meta organization gets the
+/// stats of every organization. At most `ZO_QUERY_RECOMMENDATION_TOP_K` fingerprints are
+/// returned.
+#[cfg(feature = "enterprise")]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ListQueryRecommendationsUsageStats",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+        ("hours" = Option<i64>, Query, description = "Time window to read the usage of, in hours"),
+    ),
+    responses(
+        (status = 200, description = "Success", body = serde_json::Value),
+        (status = 400, description = "Bad request"),
+        (status = 403, description = "Forbidden - Enterprise feature"),
+        (status = 500, description = "Internal server error"),
+    ),
+)]
+#[get("/{org_id}/query_recommendations/_usage_stats")]
+pub async fn usage_stats(
+    org_id: web::Path<String>,
+    query: web::Query<UsageStatsQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    use crate::service::alerts::scheduler::query_optimization_recommendation::{
+        interval_micros, top_usage_stats,
+    };
+
+    let org_id = org_id.into_inner();
+    let duration = match query.hours {
+        Some(hours) if hours <= 0 => {
+            return Ok(MetaHttpResponse::bad_request(
+                "hours must be a positive number",
+            ));
+        }
+        Some(hours) => interval_micros(hours.saturating_mul(60)),
+        None => config::get_config().recommendations.duration,
+    };
+
+    match top_usage_stats(&org_id, duration).await {
+        Ok(stats) => Ok(MetaHttpResponse::json(stats)),
+        Err(e) => {
+            log::error!("Error reading the query usage stats of org {org_id}: {e}");
+            Ok(MetaHttpResponse::internal_error(e))
+        }
+    }
+}
+
+/// List the latency stats of the queries searched in an organization (OSS - Not Supported)
+#[cfg(not(feature = "enterprise"))]
+#[utoipa::path(
+    context_path = "/api",
+    tag = "Recommendations",
+    operation_id = "ListQueryRecommendationsUsageStats",
+    security(("Authorization" = [])),
+    params(
+        ("org_id" = String, Path, description = "Organization ID"),
+    ),
+    responses(
+        (status = 403, description = "Forbidden - Enterprise feature"),
+    ),
+)]
+#[get("/{org_id}/query_recommendations/_usage_stats")]
+pub async fn usage_stats(
+    _org_id: web::Path<String>,
+    _query: web::Query<UsageStatsQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    Ok(HttpResponse::Forbidden().json("Enterprise feature not available"))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, http::StatusCode, test};
+
+    use super::*;
+
+    #[cfg(feature = "enterprise")]
+    #[test]
+    fn test_parse_stream_type() {
+        use config::meta::stream::StreamType;
+
+        assert_eq!(parse_stream_type("logs"), Some(StreamType::Logs));
+        assert_eq!(parse_stream_type("metrics"), Some(StreamType::Metrics));
+        assert_eq!(parse_stream_type("traces"), Some(StreamType::Traces));
+        // unknown types are rejected instead of falling back to logs
+        assert_eq!(parse_stream_type("log"), None);
+        assert_eq!(parse_stream_type("enrichment_tables"), None);
+        assert_eq!(parse_stream_type(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_usage_stats_rejects_invalid_window() {
+        let app = test::init_service(App::new().service(usage_stats)).await;
+        let req = test::TestRequest::get()
+            .uri("/default/query_recommendations/_usage_stats?hours=0")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        if cfg!(feature = "enterprise") {
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        } else {
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/default/query_recommendations/_usage_stats?hours=one")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
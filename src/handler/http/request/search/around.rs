@@ -18,7 +18,7 @@ use chrono::{Duration, Utc};
 use config::{
     DEFAULT_SEARCH_AROUND_FIELDS, TIMESTAMP_COL_NAME,
     meta::{
-        search::{SearchEventType, default_use_cache},
+        search::{SearchEventType, TrackTotalHits, default_use_cache},
         self_reporting::usage::{RequestStats, UsageType},
         stream::StreamType,
     },
@@ -154,7 +154,7 @@ pub(crate) async fn around(
             end_time: around_key,
             quick_mode: false,
             query_type: "".to_string(),
-            track_total_hits: false,
+            track_total_hits: TrackTotalHits::Off,
             uses_zo_fn: false,
             query_fn: query_fn.clone(),
             action_id: None,
@@ -174,6 +174,7 @@ pub(crate) async fn around(
         use_cache: default_use_cache(),
         clear_cache: false,
         local_mode: None,
+        search_priority: Default::default(),
     };
     let resp_forward = SearchService::search(trace_id, org_id, stream_type, user_id.clone(), &req)
         .instrument(http_span.clone())
@@ -193,7 +194,7 @@ pub(crate) async fn around(
             end_time: around_end_time,
             quick_mode: false,
             query_type: "".to_string(),
-            track_total_hits: false,
+            track_total_hits: TrackTotalHits::Off,
             uses_zo_fn: false,
             query_fn: query_fn.clone(),
             action_id: None,
@@ -213,6 +214,7 @@ pub(crate) async fn around(
         use_cache: default_use_cache(),
         clear_cache: false,
         local_mode: None,
+        search_priority: Default::default(),
     };
     let resp_backward = SearchService::search(trace_id, org_id, stream_type, user_id.clone(), &req)
         .instrument(http_span)
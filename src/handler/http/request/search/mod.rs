@@ -985,6 +985,7 @@ pub async fn build_search_request_per_field(
         use_cache: req.use_cache,
         clear_cache: req.clear_cache,
         local_mode: None,
+        search_priority: Default::default(),
     };
 
     let distinct_prefix = if can_use_distinct_stream {
@@ -1200,6 +1201,7 @@ async fn values_v1(
         use_cache: default_use_cache(),
         clear_cache: get_clear_cache_from_request(query),
         local_mode: None,
+        search_priority: Default::default(),
     };
 
     req.use_cache = get_use_cache_from_request(query);
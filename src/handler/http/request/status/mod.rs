@@ -767,7 +767,17 @@ pub async fn redirect(req: actix_web::HttpRequest) -> Result<HttpResponse, Error
             }
 
             // store session_id in cluster co-ordinator
-            let _ = crate::service::session::set_session(&session_id, &access_token).await;
+            let user_agent = req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok());
+            let _ = crate::service::session::set_session(
+                &session_id,
+                &access_token,
+                Some(&audit_message.user_email),
+                user_agent,
+            )
+            .await;
 
             let access_token = format!("session {session_id}");
 
@@ -870,7 +880,21 @@ async fn refresh_token_with_dex(req: actix_web::HttpRequest) -> HttpResponse {
             }
 
             // store session_id in cluster co-ordinator
-            let _ = crate::service::session::set_session(&session_id, &access_token).await;
+            let user_id = crate::handler::http::auth::validator::get_user_email_from_auth_str(
+                &format!("Bearer {access_token}"),
+            )
+            .await;
+            let user_agent = req
+                .headers()
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok());
+            let _ = crate::service::session::set_session(
+                &session_id,
+                &access_token,
+                user_id.as_deref(),
+                user_agent,
+            )
+            .await;
 
             let access_token = format!("session {session_id}");
 
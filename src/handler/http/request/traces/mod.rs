@@ -357,7 +357,7 @@ pub async fn get_latest_traces(
             end_time,
             quick_mode: false,
             query_type: "".to_string(),
-            track_total_hits: false,
+            track_total_hits: config::meta::search::TrackTotalHits::Off,
             uses_zo_fn: false,
             query_fn: None,
             action_id: None,
@@ -377,6 +377,7 @@ pub async fn get_latest_traces(
         use_cache: default_use_cache(),
         clear_cache: false,
         local_mode: None,
+        search_priority: Default::default(),
     };
 
     req.use_cache = get_use_cache_from_request(&query);
@@ -642,6 +642,21 @@ pub fn get_service_routes(svc: &mut web::ServiceConfig) {
         .service(alerts::deduplication::set_config)
         .service(alerts::deduplication::delete_config);
 
+    // the recommendations subsystem is opt-in, its endpoints don't exist unless it's enabled
+    #[cfg(feature = "enterprise")]
+    let service = if get_config().recommendations.enabled {
+        service
+            .service(query_recommendations::run)
+            .service(query_recommendations::list)
+            .service(query_recommendations::export)
+            .service(query_recommendations::update_status)
+            .service(query_recommendations::apply)
+            .service(query_recommendations::reset_watermark)
+            .service(query_recommendations::usage_stats)
+    } else {
+        service
+    };
+
     #[cfg(feature = "cloud")]
     let service = service
         .service(organization::org::get_org_invites)
@@ -15,7 +15,7 @@
 
 use config::{
     RwHashMap,
-    meta::stream::{FileMeta, StreamStats, StreamType},
+    meta::stream::{FileMeta, StreamSize, StreamStats, StreamType},
 };
 use once_cell::sync::Lazy;
 
@@ -36,6 +36,17 @@ pub fn get_stream_stats(org_id: &str, stream_name: &str, stream_type: StreamType
         .unwrap_or_default()
 }
 
+/// Returns the row count, size and time range of a stream from its cached stats, or all zeros
+/// if the stream has no data.
+#[inline]
+pub fn get_stream_size(org_id: &str, stream_name: &str, stream_type: StreamType) -> StreamSize {
+    let key = format!("{org_id}/{stream_type}/{stream_name}");
+    STATS
+        .get(&key)
+        .map(|v| StreamSize::from(v.value()))
+        .unwrap_or_default()
+}
+
 #[inline]
 pub fn remove_stream_stats(org_id: &str, stream_name: &str, stream_type: StreamType) {
     let key = format!("{org_id}/{stream_type}/{stream_name}");
@@ -128,4 +139,38 @@ mod tests {
         let stats = get_stream_stats("nexus", "default", StreamType::Logs);
         assert_eq!(stats.doc_num, 5000);
     }
+
+    #[test]
+    fn test_get_stream_size() {
+        let val = StreamStats {
+            created_at: 1667978841102,
+            doc_time_min: 1667978841102,
+            doc_time_max: 1667978845374,
+            doc_num: 5000,
+            file_num: 2,
+            storage_size: 2048.0,
+            compressed_size: 512.0,
+            index_size: 100.0,
+        };
+        set_stream_stats("size_org", "seeded", StreamType::Logs, val);
+        assert_eq!(
+            get_stream_size("size_org", "seeded", StreamType::Logs),
+            StreamSize {
+                doc_count: 5000,
+                storage_bytes: 2048,
+                min_ts: 1667978841102,
+                max_ts: 1667978845374,
+            }
+        );
+
+        // stats are kept per stream type
+        assert_eq!(
+            get_stream_size("size_org", "seeded", StreamType::Metrics),
+            StreamSize::default()
+        );
+        assert_eq!(
+            get_stream_size("size_org", "missing", StreamType::Logs),
+            StreamSize::default()
+        );
+    }
 }
@@ -127,6 +127,8 @@ pub enum DbError {
     TemplateError(#[from] TemplateError),
     #[error("PutAlert# {0}")]
     PutAlert(#[from] PutAlertError),
+    #[error("CreateSession# {0}")]
+    CreateSession(#[from] CreateSessionError),
 }
 
 #[derive(ThisError, Debug)]
@@ -163,6 +165,14 @@ pub enum PutAlertError {
     IntoTriggerThresholdOperator(config::meta::alerts::Operator),
 }
 
+#[derive(ThisError, Debug)]
+pub enum CreateSessionError {
+    #[error("session {0} already exists")]
+    SessionExists(String),
+    #[error("cannot create a session with an empty access token")]
+    EmptyAccessToken,
+}
+
 #[derive(ThisError, Debug)]
 pub enum DestinationError {
     #[error("alert destination template not found")]
@@ -101,6 +101,22 @@ impl Algorithm {
     }
 }
 
+/// Returns true if a valid master encryption key is configured.
+pub(crate) fn has_master_key() -> bool {
+    !matches!(*MASTER_KEY, Algorithm::None)
+}
+
+/// Encrypts data with the master encryption key. Returns the plaintext as-is if no master key is
+/// configured.
+pub(crate) fn encrypt_with_master_key(plaintext: &str) -> Result<String, errors::Error> {
+    MASTER_KEY.encrypt(plaintext)
+}
+
+/// Decrypts data encrypted with [`encrypt_with_master_key`].
+pub(crate) fn decrypt_with_master_key(encrypted: &str) -> Result<String, errors::Error> {
+    MASTER_KEY.decrypt(encrypted)
+}
+
 impl std::fmt::Display for EntryKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -9,8 +9,8 @@ pub use super::{
     org_users::Entity as OrgUsers, organizations::Entity as Organizations,
     pipeline_last_errors::Entity as PipelineLastErrors,
     re_pattern_stream_map::Entity as RePatternStreamMap, re_patterns::Entity as RePatterns,
-    report_dashboards::Entity as ReportDashboards, reports::Entity as Reports,
-    search_job_partitions::Entity as SearchJobPartitions,
+    recommendations::Entity as Recommendations, report_dashboards::Entity as ReportDashboards,
+    reports::Entity as Reports, search_job_partitions::Entity as SearchJobPartitions,
     search_job_results::Entity as SearchJobResults, search_jobs::Entity as SearchJobs,
     search_queue::Entity as SearchQueue, sessions::Entity as Sessions,
     templates::Entity as Templates, timed_annotation_panels::Entity as TimedAnnotationPanels,
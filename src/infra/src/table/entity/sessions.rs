@@ -26,6 +26,15 @@ pub struct Model {
     pub access_token: String,
     pub created_at: i64,
     pub updated_at: i64,
+    pub expires_at: i64,
+    pub last_used_at: i64,
+    pub user_id: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub user_agent: Option<String>,
+    pub token_hash: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub metadata: Option<String>,
+    pub source: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -17,12 +17,12 @@
 //! the meta table.
 
 use config::utils::json;
-use sea_orm::{
-    ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
-};
+use sea_orm::{EntityTrait, PaginatorTrait, Set, TransactionTrait};
 use sea_orm_migration::prelude::*;
 use serde::{self, Deserialize};
 
+use super::meta::v1 as meta;
+
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
@@ -35,10 +35,7 @@ impl MigrationTrait for Migration {
         // Migrate pages of 100 records at a time to avoid loading too many
         // records into memory.
         // txn.execute()
-        let mut meta_pages = meta::Entity::find()
-            .filter(meta::Column::Module.eq("folders"))
-            .order_by_asc(meta::Column::Id)
-            .paginate(&txn, 100);
+        let mut meta_pages = meta::find_by_module("folders").paginate(&txn, 100);
 
         while let Some(metas) = meta_pages.fetch_and_next().await? {
             let folders_rslt: Result<Vec<_>, DbErr> = metas
@@ -92,28 +89,6 @@ struct MetaFolder {
 // remain unchanged rather than ORM models in the `entity` module that will be
 // updated to reflect the latest changes to table schemas.
 
-/// Representation of the meta table at the time this migration executes.
-mod meta {
-    use sea_orm::entity::prelude::*;
-
-    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
-    #[sea_orm(table_name = "meta")]
-    pub struct Model {
-        #[sea_orm(primary_key)]
-        pub id: i64,
-        pub module: String,
-        pub key1: String,
-        pub key2: String,
-        pub start_dt: i64,
-        pub value: String,
-    }
-
-    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-    pub enum Relation {}
-
-    impl ActiveModelBehavior for ActiveModel {}
-}
-
 /// Representation of the folder table at the time this migration executes.
 mod folder {
     use sea_orm::entity::prelude::*;
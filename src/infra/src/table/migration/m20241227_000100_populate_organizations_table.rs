@@ -16,11 +16,11 @@
 #[cfg(feature = "cloud")]
 use config::utils::time::day_micros;
 use hashbrown::HashSet;
-use sea_orm::{
-    ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
-};
+use sea_orm::{EntityTrait, PaginatorTrait, Set, TransactionTrait};
 use sea_orm_migration::prelude::*;
 
+use super::meta::v1 as meta;
+
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
@@ -35,10 +35,7 @@ impl MigrationTrait for Migration {
         // Migrate pages of 100 records at a time to avoid loading too many
         // records into memory.
         // txn.execute()
-        let mut meta_pages = meta::Entity::find()
-            .filter(meta::Column::Module.eq("schema"))
-            .order_by_asc(meta::Column::Id)
-            .paginate(&txn, 100);
+        let mut meta_pages = meta::find_by_module("schema").paginate(&txn, 100);
 
         while let Some(metas) = meta_pages.fetch_and_next().await? {
             let mut orgs = vec![];
@@ -89,29 +86,6 @@ impl MigrationTrait for Migration {
     }
 }
 
-/// Representation of the meta table at the time this migration executes.
-mod meta {
-    use sea_orm::entity::prelude::*;
-
-    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
-    #[sea_orm(table_name = "meta")]
-    pub struct Model {
-        #[sea_orm(primary_key)]
-        pub id: i64,
-        pub module: String,
-        pub key1: String,
-        pub key2: String,
-        pub start_dt: i64,
-        #[sea_orm(column_type = "Text")]
-        pub value: String,
-    }
-
-    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-    pub enum Relation {}
-
-    impl ActiveModelBehavior for ActiveModel {}
-}
-
 /// Representation of the folder table at the time this migration executes.
 mod organizations {
     use sea_orm::entity::prelude::*;
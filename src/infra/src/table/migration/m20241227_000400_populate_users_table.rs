@@ -15,9 +15,7 @@
 
 use config::utils::json;
 use hashbrown::HashSet;
-use sea_orm::{
-    ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
-};
+use sea_orm::{EntityTrait, PaginatorTrait, Set, TransactionTrait};
 use sea_orm_migration::prelude::*;
 
 #[derive(DeriveMigrationName)]
@@ -31,10 +29,7 @@ impl MigrationTrait for Migration {
         // Migrate pages of 100 records at a time to avoid loading too many
         // records into memory.
         // txn.execute()
-        let mut meta_pages = meta::Entity::find()
-            .filter(meta::Column::Module.eq("user"))
-            .order_by_asc(meta::Column::Id)
-            .paginate(&txn, 100);
+        let mut meta_pages = meta::find_by_module("user").paginate(&txn, 100);
         let mut orgs_set = HashSet::new();
         let mut org_pages = organization::Entity::find().paginate(&txn, 100);
         while let Some(orgs) = org_pages.fetch_and_next().await? {
@@ -120,30 +115,14 @@ impl MigrationTrait for Migration {
     }
 }
 
-/// Representation of the meta table at the time this migration executes.
+/// Representation of the meta table at the time this migration executes, and of the user
+/// records it holds.
 mod meta {
     use std::{fmt, str::FromStr};
 
-    use sea_orm::entity::prelude::*;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
-    #[sea_orm(table_name = "meta")]
-    pub struct Model {
-        #[sea_orm(primary_key)]
-        pub id: i64,
-        pub module: String,
-        pub key1: String,
-        pub key2: String,
-        pub start_dt: i64,
-        #[sea_orm(column_type = "Text")]
-        pub value: String,
-    }
-
-    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-    pub enum Relation {}
-
-    impl ActiveModelBehavior for ActiveModel {}
+    pub use super::super::meta::v1::find_by_module;
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct DBUser {
@@ -18,6 +18,8 @@
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, TransactionTrait};
 use sea_orm_migration::prelude::*;
 
+use super::meta::v1 as meta;
+
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
@@ -38,31 +40,3 @@ impl MigrationTrait for Migration {
         Ok(())
     }
 }
-
-// The schemas of tables might change after subsequent migrations. Therefore
-// this migration only references ORM models in private submodules that should
-// remain unchanged rather than ORM models in the `entity` module that will be
-// updated to reflect the latest changes to table schemas.
-
-/// Representation of the meta table at the time this migration executes.
-mod meta {
-    use sea_orm::entity::prelude::*;
-
-    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
-    #[sea_orm(table_name = "meta")]
-    pub struct Model {
-        #[sea_orm(primary_key)]
-        pub id: i64,
-        pub module: String,
-        pub key1: String,
-        pub key2: String,
-        pub start_dt: i64,
-        #[sea_orm(column_type = "Text")]
-        pub value: String,
-    }
-
-    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-    pub enum Relation {}
-
-    impl ActiveModelBehavior for ActiveModel {}
-}
@@ -14,12 +14,12 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use config::utils::json;
-use sea_orm::{
-    ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
-};
+use sea_orm::{EntityTrait, PaginatorTrait, Set, TransactionTrait};
 use sea_orm_migration::prelude::*;
 use svix_ksuid::KsuidLike;
 
+use super::meta::v1 as meta;
+
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
@@ -30,10 +30,7 @@ impl MigrationTrait for Migration {
 
         // Migrate pages of 100 records at a time to avoid loading too many
         // records into memory.
-        let mut meta_pages = meta::Entity::find()
-            .filter(meta::Column::Module.eq("templates"))
-            .order_by_asc(meta::Column::Id)
-            .paginate(&txn, 100);
+        let mut meta_pages = meta::find_by_module("templates").paginate(&txn, 100);
 
         while let Some(metas) = meta_pages.fetch_and_next().await? {
             let new_temp_results: Result<Vec<_>, DbErr> = metas
@@ -135,29 +132,6 @@ mod meta_templates {
     }
 }
 
-/// Representation of the meta table at the time this migration executes.
-mod meta {
-    use sea_orm::entity::prelude::*;
-
-    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
-    #[sea_orm(table_name = "meta")]
-    pub struct Model {
-        #[sea_orm(primary_key)]
-        pub id: i64,
-        pub module: String,
-        pub key1: String,
-        pub key2: String,
-        pub start_dt: i64,
-        #[sea_orm(column_type = "Text")]
-        pub value: String,
-    }
-
-    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-    pub enum Relation {}
-
-    impl ActiveModelBehavior for ActiveModel {}
-}
-
 /// Representation of the templates table at the time this migration executes.
 mod template {
 
@@ -16,12 +16,12 @@
 use std::collections::HashMap;
 
 use config::utils::json;
-use sea_orm::{
-    ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
-};
+use sea_orm::{EntityTrait, PaginatorTrait, Set, TransactionTrait};
 use sea_orm_migration::prelude::*;
 use svix_ksuid::KsuidLike;
 
+use super::meta::v1 as meta;
+
 #[derive(DeriveMigrationName)]
 pub struct Migration;
 
@@ -40,10 +40,7 @@ impl MigrationTrait for Migration {
 
         // Migrate pages of 100 records at a time to avoid loading too many
         // records into memory.
-        let mut meta_pages = meta::Entity::find()
-            .filter(meta::Column::Module.eq("destinations"))
-            .order_by_asc(meta::Column::Id)
-            .paginate(&txn, 100);
+        let mut meta_pages = meta::find_by_module("destinations").paginate(&txn, 100);
 
         while let Some(metas) = meta_pages.fetch_and_next().await? {
             let new_temp_results: Result<Vec<_>, DbErr> = metas
@@ -191,29 +188,6 @@ mod meta_destinations {
     }
 }
 
-/// Representation of the meta table at the time this migration executes.
-mod meta {
-    use sea_orm::entity::prelude::*;
-
-    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
-    #[sea_orm(table_name = "meta")]
-    pub struct Model {
-        #[sea_orm(primary_key)]
-        pub id: i64,
-        pub module: String,
-        pub key1: String,
-        pub key2: String,
-        pub start_dt: i64,
-        #[sea_orm(column_type = "Text")]
-        pub value: String,
-    }
-
-    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-    pub enum Relation {}
-
-    impl ActiveModelBehavior for ActiveModel {}
-}
-
 /// Representation of the destinations table at the time this migration executes.
 mod destinations {
 
@@ -15,7 +15,7 @@
 
 use sea_orm_migration::prelude::*;
 
-use super::get_text_type;
+use super::{PlanMigration, get_text_type, statement_sql};
 
 #[derive(DeriveMigrationName)]
 pub struct Migration;
@@ -37,6 +37,16 @@ impl MigrationTrait for Migration {
     }
 }
 
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        vec![statement_sql(
+            db_backend,
+            &create_sessions_table_statement(),
+        )]
+    }
+}
+
 /// Statement to create the sessions table.
 fn create_sessions_table_statement() -> TableCreateStatement {
     let text_type = get_text_type();
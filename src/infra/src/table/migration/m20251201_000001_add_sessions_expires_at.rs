@@ -0,0 +1,199 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Adds the sessions's expires_at column and back-fills it for existing rows.
+
+use sea_orm::{DatabaseConnection, Statement};
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, count_rows, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db_backend = manager.get_database_backend();
+        manager
+            .alter_table(add_expires_at_column_statement(db_backend))
+            .await?;
+        set_expires_at_for_existing_rows(manager).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(drop_expires_at_column_statement())
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        let mut statements = vec![statement_sql(
+            db_backend,
+            &add_expires_at_column_statement(db_backend),
+        )];
+        let ttl = config::get_config().auth.session_ttl;
+        if ttl > 0 {
+            statements.push(statement_sql(
+                db_backend,
+                &set_expires_at_statement(ttl.saturating_mul(1_000_000)),
+            ));
+        }
+        statements
+    }
+
+    async fn estimated_rows(&self, db: &DatabaseConnection) -> Result<Option<u64>, DbErr> {
+        if config::get_config().auth.session_ttl <= 0 {
+            return Ok(Some(0));
+        }
+        count_rows(db, "sessions").await.map(Some)
+    }
+}
+
+/// Statement to add the expires_at column. A value of 0 means the session never expires.
+fn add_expires_at_column_statement(db_backend: sea_orm::DbBackend) -> TableAlterStatement {
+    let mut column = ColumnDef::new(Sessions::ExpiresAt);
+    column.big_integer().not_null().default(0);
+    if matches!(db_backend, sea_orm::DbBackend::MySql) {
+        // MySQL doesn't support IF NOT EXISTS in ALTER TABLE
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column(column)
+            .to_owned()
+    } else {
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column_if_not_exists(column)
+            .to_owned()
+    }
+}
+
+/// Statement to drop the expires_at column.
+fn drop_expires_at_column_statement() -> TableAlterStatement {
+    Table::alter()
+        .table(Sessions::Table)
+        .drop_column(Sessions::ExpiresAt)
+        .to_owned()
+}
+
+/// Statement to set expires_at = created_at + ttl for rows that have no expiry yet.
+fn set_expires_at_statement(ttl_micros: i64) -> UpdateStatement {
+    Query::update()
+        .table(Sessions::Table)
+        .value(
+            Sessions::ExpiresAt,
+            Expr::col(Sessions::CreatedAt).add(ttl_micros),
+        )
+        .and_where(Expr::col(Sessions::ExpiresAt).eq(0))
+        .to_owned()
+}
+
+/// Back-fills expires_at for existing rows using the configured session TTL.
+async fn set_expires_at_for_existing_rows(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let ttl = config::get_config().auth.session_ttl;
+    if ttl <= 0 {
+        log::debug!("[SESSIONS_MIGRATION] session ttl disabled, existing sessions never expire");
+        return Ok(());
+    }
+
+    let db = manager.get_connection();
+    let backend = db.get_database_backend();
+    let update_query = set_expires_at_statement(ttl.saturating_mul(1_000_000));
+    let (sql, values) = match backend {
+        sea_orm::DatabaseBackend::MySql => update_query.build(MysqlQueryBuilder),
+        sea_orm::DatabaseBackend::Postgres => update_query.build(PostgresQueryBuilder),
+        sea_orm::DatabaseBackend::Sqlite => update_query.build(SqliteQueryBuilder),
+    };
+    let statement = Statement::from_sql_and_values(backend, sql, values);
+    let ret = db.execute(statement).await?;
+    log::debug!(
+        "[SESSIONS_MIGRATION] set expires_at for {} rows",
+        ret.rows_affected()
+    );
+
+    Ok(())
+}
+
+/// Identifiers used in queries on the sessions table.
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    CreatedAt,
+    ExpiresAt,
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &add_expires_at_column_statement(sea_orm::DbBackend::Postgres)
+                .to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN IF NOT EXISTS "expires_at" bigint NOT NULL DEFAULT 0"#
+        );
+        collapsed_eq!(
+            &drop_expires_at_column_statement().to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "expires_at""#
+        );
+        collapsed_eq!(
+            &set_expires_at_statement(1_000_000).to_string(PostgresQueryBuilder),
+            r#"UPDATE "sessions" SET "expires_at" = "created_at" + 1000000 WHERE "expires_at" = 0"#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &add_expires_at_column_statement(sea_orm::DbBackend::MySql)
+                .to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` ADD COLUMN `expires_at` bigint NOT NULL DEFAULT 0"#
+        );
+        collapsed_eq!(
+            &drop_expires_at_column_statement().to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` DROP COLUMN `expires_at`"#
+        );
+        collapsed_eq!(
+            &set_expires_at_statement(1_000_000).to_string(MysqlQueryBuilder),
+            r#"UPDATE `sessions` SET `expires_at` = `created_at` + 1000000 WHERE `expires_at` = 0"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        // SQLite doesn't support IF NOT EXISTS in ALTER TABLE ADD COLUMN
+        collapsed_eq!(
+            &add_expires_at_column_statement(sea_orm::DbBackend::Sqlite)
+                .to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN "expires_at" bigint NOT NULL DEFAULT 0"#
+        );
+        collapsed_eq!(
+            &drop_expires_at_column_statement().to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "expires_at""#
+        );
+        collapsed_eq!(
+            &set_expires_at_statement(1_000_000).to_string(SqliteQueryBuilder),
+            r#"UPDATE "sessions" SET "expires_at" = "created_at" + 1000000 WHERE "expires_at" = 0"#
+        );
+    }
+}
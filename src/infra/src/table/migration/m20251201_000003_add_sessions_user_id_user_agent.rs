@@ -0,0 +1,193 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Adds the nullable user_id and user_agent columns to the sessions table, and an index on
+//! user_id to list the sessions of a user.
+//!
+//! Existing rows keep NULL values. Legacy sessions were already removed from the meta table by
+//! `m20251118_000003_delete_meta_sessions`, so there is no source to derive user_id from.
+
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, create_index, drop_index, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const SESSIONS_USER_ID_IDX: &str = "sessions_user_id_idx";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db_backend = manager.get_database_backend();
+        // SQLite doesn't support multiple alter options in a single statement
+        manager
+            .alter_table(add_column_statement(db_backend, user_id_column()))
+            .await?;
+        manager
+            .alter_table(add_column_statement(db_backend, user_agent_column()))
+            .await?;
+        manager
+            .create_index(create_sessions_user_id_idx_stmnt())
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(drop_sessions_user_id_idx_stmnt())
+            .await?;
+        manager
+            .alter_table(drop_column_statement(Sessions::UserAgent))
+            .await?;
+        manager
+            .alter_table(drop_column_statement(Sessions::UserId))
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        vec![
+            statement_sql(
+                db_backend,
+                &add_column_statement(db_backend, user_id_column()),
+            ),
+            statement_sql(
+                db_backend,
+                &add_column_statement(db_backend, user_agent_column()),
+            ),
+            statement_sql(db_backend, &create_sessions_user_id_idx_stmnt()),
+        ]
+    }
+}
+
+/// Definition of the user_id column.
+fn user_id_column() -> ColumnDef {
+    ColumnDef::new(Sessions::UserId)
+        .string_len(256)
+        .null()
+        .to_owned()
+}
+
+/// Definition of the user_agent column.
+fn user_agent_column() -> ColumnDef {
+    ColumnDef::new(Sessions::UserAgent).text().null().to_owned()
+}
+
+/// Statement to add a column to the sessions table.
+fn add_column_statement(db_backend: sea_orm::DbBackend, column: ColumnDef) -> TableAlterStatement {
+    if matches!(db_backend, sea_orm::DbBackend::MySql) {
+        // MySQL doesn't support IF NOT EXISTS in ALTER TABLE
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column(column)
+            .to_owned()
+    } else {
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column_if_not_exists(column)
+            .to_owned()
+    }
+}
+
+/// Statement to drop a column from the sessions table.
+fn drop_column_statement(column: Sessions) -> TableAlterStatement {
+    Table::alter()
+        .table(Sessions::Table)
+        .drop_column(column)
+        .to_owned()
+}
+
+/// Statement to create the index on user_id.
+fn create_sessions_user_id_idx_stmnt() -> IndexCreateStatement {
+    create_index(SESSIONS_USER_ID_IDX, Sessions::Table, [Sessions::UserId])
+}
+
+/// Statement to drop the index on user_id.
+fn drop_sessions_user_id_idx_stmnt() -> IndexDropStatement {
+    drop_index(SESSIONS_USER_ID_IDX, Sessions::Table)
+}
+
+/// Identifiers used in queries on the sessions table.
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    UserId,
+    UserAgent,
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &add_column_statement(
+                sea_orm::DbBackend::Postgres,
+                ColumnDef::new(Sessions::UserId)
+                    .string_len(256)
+                    .null()
+                    .to_owned()
+            )
+            .to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN IF NOT EXISTS "user_id" varchar(256) NULL"#
+        );
+        collapsed_eq!(
+            &create_sessions_user_id_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "sessions_user_id_idx" ON "sessions" ("user_id")"#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &add_column_statement(
+                sea_orm::DbBackend::MySql,
+                ColumnDef::new(Sessions::UserId)
+                    .string_len(256)
+                    .null()
+                    .to_owned()
+            )
+            .to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` ADD COLUMN `user_id` varchar(256) NULL"#
+        );
+        collapsed_eq!(
+            &create_sessions_user_id_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"CREATE INDEX `sessions_user_id_idx` ON `sessions` (`user_id`)"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        collapsed_eq!(
+            &add_column_statement(
+                sea_orm::DbBackend::Sqlite,
+                ColumnDef::new(Sessions::UserAgent).text().null().to_owned()
+            )
+            .to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN "user_agent" text NULL"#
+        );
+        collapsed_eq!(
+            &create_sessions_user_id_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "sessions_user_id_idx" ON "sessions" ("user_id")"#
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Change the column type of the sessions access_token column to longtext on mysql, so
+//! encrypted tokens are never truncated when ZO_TEXT_DATA_TYPE is set to a smaller type.
+
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, get_long_text_type, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        widen_access_token_column(manager).await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // Reversing this migration is not supported.
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        if !matches!(db_backend, sea_orm::DbBackend::MySql) {
+            return vec![];
+        }
+        vec![statement_sql(
+            db_backend,
+            &widen_access_token_column_statement(db_backend),
+        )]
+    }
+}
+
+// Widen the access_token column.
+async fn widen_access_token_column(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let db_backend = manager.get_database_backend();
+    if !matches!(db_backend, sea_orm::DbBackend::MySql) {
+        log::debug!("[Migration] Non-mysql db does not need this migration");
+        return Ok(());
+    }
+    manager
+        .alter_table(widen_access_token_column_statement(db_backend))
+        .await?;
+    Ok(())
+}
+
+fn widen_access_token_column_statement(db_backend: sea_orm::DbBackend) -> TableAlterStatement {
+    Table::alter()
+        .table(Sessions::Table)
+        .modify_column(
+            ColumnDef::new(Sessions::AccessToken)
+                .custom(Alias::new(get_long_text_type(db_backend)))
+                .not_null()
+                .to_owned(),
+        )
+        .to_owned()
+}
+
+/// Identifiers used in queries on the sessions table.
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    AccessToken,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mysql() {
+        let statement = widen_access_token_column_statement(sea_orm::DbBackend::MySql);
+        assert_eq!(
+            statement.to_string(MysqlQueryBuilder),
+            "ALTER TABLE `sessions` MODIFY COLUMN `access_token` longtext NOT NULL"
+        );
+    }
+}
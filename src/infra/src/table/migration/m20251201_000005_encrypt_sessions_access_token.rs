@@ -0,0 +1,183 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encrypts the access tokens of existing sessions with the master encryption key.
+//!
+//! Sessions are left untouched if no master encryption key is configured. Tokens that are already
+//! encrypted are skipped, so re-running the migration is always safe.
+
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, backfill_page_size, backfill_paged_in_transactions, count_rows};
+use crate::table::cipher;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !cipher::has_master_key() {
+            log::info!("[SESSIONS_MIGRATION] No master encryption key, skip encrypting sessions");
+            return Ok(());
+        }
+        let (updated, skipped) = rewrite_access_tokens(manager, |token| {
+            if is_token_encrypted(token) {
+                Ok(None)
+            } else {
+                encrypt_token(token).map(Some)
+            }
+        })
+        .await?;
+        log::info!(
+            "[SESSIONS_MIGRATION] encrypted {updated} session access tokens, skipped {skipped} already encrypted"
+        );
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        if !cipher::has_master_key() {
+            return Ok(());
+        }
+        let (updated, skipped) = rewrite_access_tokens(manager, |token| {
+            if is_token_encrypted(token) {
+                decrypt_token(token).map(Some)
+            } else {
+                Ok(None)
+            }
+        })
+        .await?;
+        log::info!(
+            "[SESSIONS_MIGRATION] decrypted {updated} session access tokens, skipped {skipped} in plaintext"
+        );
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, _db_backend: sea_orm::DbBackend) -> Vec<String> {
+        // the tokens are rewritten one row at a time
+        vec![]
+    }
+
+    async fn estimated_rows(&self, db: &DatabaseConnection) -> Result<Option<u64>, DbErr> {
+        if !cipher::has_master_key() {
+            return Ok(Some(0));
+        }
+        count_rows(db, "sessions").await.map(Some)
+    }
+}
+
+/// Rewrites the access token of every session for which `f` returns a new value, and returns the
+/// number of updated and skipped sessions.
+async fn rewrite_access_tokens<F>(manager: &SchemaManager<'_>, f: F) -> Result<(u64, u64), DbErr>
+where
+    F: Fn(&str) -> Result<Option<String>, DbErr> + Copy + Send + 'static,
+{
+    let db = manager.get_connection();
+    let query = sessions::Entity::find().order_by_asc(sessions::Column::SessionId);
+    let progress = backfill_paged_in_transactions(
+        "encrypt_sessions",
+        db,
+        query,
+        backfill_page_size(),
+        move |txn, sessions| {
+            Box::pin(async move {
+                let mut updated = 0;
+                for session in sessions {
+                    let Some(access_token) = f(&session.access_token)? else {
+                        continue;
+                    };
+                    sessions::Entity::update_many()
+                        .col_expr(sessions::Column::AccessToken, Expr::value(access_token))
+                        .filter(sessions::Column::SessionId.eq(session.session_id))
+                        .exec(txn)
+                        .await?;
+                    updated += 1;
+                }
+                Ok(updated)
+            })
+        },
+    )
+    .await?;
+
+    Ok((
+        progress.rows_changed,
+        progress.rows_read - progress.rows_changed,
+    ))
+}
+
+// The format of encrypted tokens might change after this migration. Therefore it
+// encrypts and decrypts them with its own copy of the helpers of the `sessions`
+// table module, as they were when the migration was written.
+
+/// Prefix marking an encrypted access token.
+const ENCRYPTED_TOKEN_PREFIX: &str = "enc:";
+
+/// Encrypts an access token with the master encryption key.
+fn encrypt_token(access_token: &str) -> Result<String, DbErr> {
+    let encrypted = cipher::encrypt_with_master_key(access_token)
+        .map_err(|e| DbErr::Migration(e.to_string()))?;
+    Ok(format!("{ENCRYPTED_TOKEN_PREFIX}{encrypted}"))
+}
+
+/// Decrypts an access token encrypted by [`encrypt_token`].
+fn decrypt_token(stored: &str) -> Result<String, DbErr> {
+    let encrypted = stored
+        .strip_prefix(ENCRYPTED_TOKEN_PREFIX)
+        .unwrap_or(stored);
+    cipher::decrypt_with_master_key(encrypted).map_err(|e| DbErr::Migration(e.to_string()))
+}
+
+/// Returns true if the stored access token is encrypted.
+fn is_token_encrypted(stored: &str) -> bool {
+    stored.starts_with(ENCRYPTED_TOKEN_PREFIX)
+}
+
+// The schemas of tables might change after subsequent migrations. Therefore
+// this migration only references ORM models in private submodules that should
+// remain unchanged rather than ORM models in the `entity` module that will be
+// updated to reflect the latest changes to table schemas.
+
+/// Representation of the sessions table columns used by this migration.
+mod sessions {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+    #[sea_orm(table_name = "sessions")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub session_id: String,
+        pub access_token: String,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_token_encrypted() {
+        assert!(!is_token_encrypted("token"));
+        assert!(is_token_encrypted("enc:token"));
+    }
+}
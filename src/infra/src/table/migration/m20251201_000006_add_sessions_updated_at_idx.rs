@@ -0,0 +1,117 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Adds an index on the sessions's updated_at column, so that the session cleanup job can find
+//! stale sessions without scanning the whole table.
+
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, create_index, drop_index, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const SESSIONS_UPDATED_AT_IDX: &str = "sessions_updated_at_idx";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_index(create_sessions_updated_at_idx_stmnt())
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(drop_sessions_updated_at_idx_stmnt())
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        vec![statement_sql(
+            db_backend,
+            &create_sessions_updated_at_idx_stmnt(),
+        )]
+    }
+}
+
+/// Statement to create the index on updated_at.
+fn create_sessions_updated_at_idx_stmnt() -> IndexCreateStatement {
+    create_index(
+        SESSIONS_UPDATED_AT_IDX,
+        Sessions::Table,
+        [Sessions::UpdatedAt],
+    )
+}
+
+/// Statement to drop the index on updated_at.
+fn drop_sessions_updated_at_idx_stmnt() -> IndexDropStatement {
+    drop_index(SESSIONS_UPDATED_AT_IDX, Sessions::Table)
+}
+
+/// Identifiers used in queries on the sessions table.
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    UpdatedAt,
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &create_sessions_updated_at_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "sessions_updated_at_idx" ON "sessions" ("updated_at")"#
+        );
+        collapsed_eq!(
+            &drop_sessions_updated_at_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"DROP INDEX "sessions_updated_at_idx""#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &create_sessions_updated_at_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"CREATE INDEX `sessions_updated_at_idx` ON `sessions` (`updated_at`)"#
+        );
+        collapsed_eq!(
+            &drop_sessions_updated_at_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"DROP INDEX `sessions_updated_at_idx` ON `sessions`"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        collapsed_eq!(
+            &create_sessions_updated_at_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "sessions_updated_at_idx" ON "sessions" ("updated_at")"#
+        );
+        collapsed_eq!(
+            &drop_sessions_updated_at_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"DROP INDEX "sessions_updated_at_idx""#
+        );
+    }
+}
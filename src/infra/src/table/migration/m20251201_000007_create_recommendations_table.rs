@@ -0,0 +1,252 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, create_index, create_unique_index, drop_index, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const RECOMMENDATIONS_ORG_STATUS_IDX: &str = "recommendations_org_status_idx";
+const RECOMMENDATIONS_DEDUPE_KEY_IDX: &str = "recommendations_dedupe_key_idx";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(create_recommendations_table_statement())
+            .await?;
+        manager
+            .create_index(create_recommendations_org_status_idx_stmnt())
+            .await?;
+        manager
+            .create_index(create_recommendations_dedupe_key_idx_stmnt())
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(drop_index(
+                RECOMMENDATIONS_DEDUPE_KEY_IDX,
+                Recommendations::Table,
+            ))
+            .await?;
+        manager
+            .drop_index(drop_index(
+                RECOMMENDATIONS_ORG_STATUS_IDX,
+                Recommendations::Table,
+            ))
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Recommendations::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        vec![
+            statement_sql(db_backend, &create_recommendations_table_statement()),
+            statement_sql(db_backend, &create_recommendations_org_status_idx_stmnt()),
+            statement_sql(db_backend, &create_recommendations_dedupe_key_idx_stmnt()),
+        ]
+    }
+}
+
+/// Statement to create the recommendations table.
+fn create_recommendations_table_statement() -> TableCreateStatement {
+    Table::create()
+        .table(Recommendations::Table)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(Recommendations::Id)
+                .string_len(27)
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(Recommendations::OrgId)
+                .string_len(256)
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Recommendations::Stream)
+                .string_len(256)
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Recommendations::RecommendationType)
+                .string_len(64)
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Recommendations::TargetField)
+                .string_len(256)
+                .not_null(),
+        )
+        .col(ColumnDef::new(Recommendations::Payload).json().not_null())
+        .col(
+            ColumnDef::new(Recommendations::Status)
+                .string_len(32)
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Recommendations::CreatedAt)
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Recommendations::UpdatedAt)
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Recommendations::DedupeKey)
+                .string_len(64)
+                .null(),
+        )
+        .to_owned()
+}
+
+/// Statement to create the index used to list the recommendations of an org by status.
+fn create_recommendations_org_status_idx_stmnt() -> IndexCreateStatement {
+    create_index(
+        RECOMMENDATIONS_ORG_STATUS_IDX,
+        Recommendations::Table,
+        [Recommendations::OrgId, Recommendations::Status],
+    )
+}
+
+/// Statement to create the unique index that keeps a single open, accepted or applied
+/// recommendation per org, stream, type and target field. Closed recommendations have no dedupe
+/// key, and NULLs never collide.
+fn create_recommendations_dedupe_key_idx_stmnt() -> IndexCreateStatement {
+    create_unique_index(
+        RECOMMENDATIONS_DEDUPE_KEY_IDX,
+        Recommendations::Table,
+        [Recommendations::DedupeKey],
+    )
+}
+
+/// Identifiers used in queries on the recommendations table.
+#[derive(DeriveIden)]
+enum Recommendations {
+    Table,
+    Id,
+    OrgId,
+    Stream,
+    RecommendationType,
+    TargetField,
+    Payload,
+    Status,
+    CreatedAt,
+    UpdatedAt,
+    DedupeKey,
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &create_recommendations_table_statement().to_string(PostgresQueryBuilder),
+            r#"
+                CREATE TABLE IF NOT EXISTS "recommendations" (
+                "id" varchar(27) NOT NULL PRIMARY KEY,
+                "org_id" varchar(256) NOT NULL,
+                "stream" varchar(256) NOT NULL,
+                "recommendation_type" varchar(64) NOT NULL,
+                "target_field" varchar(256) NOT NULL,
+                "payload" json NOT NULL,
+                "status" varchar(32) NOT NULL,
+                "created_at" bigint NOT NULL,
+                "updated_at" bigint NOT NULL,
+                "dedupe_key" varchar(64) NULL
+            )"#
+        );
+        collapsed_eq!(
+            &create_recommendations_org_status_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "recommendations_org_status_idx" ON "recommendations" ("org_id", "status")"#
+        );
+        collapsed_eq!(
+            &create_recommendations_dedupe_key_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "recommendations_dedupe_key_idx" ON "recommendations" ("dedupe_key")"#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &create_recommendations_table_statement().to_string(MysqlQueryBuilder),
+            r#"
+                CREATE TABLE IF NOT EXISTS `recommendations` (
+                `id` varchar(27) NOT NULL PRIMARY KEY,
+                `org_id` varchar(256) NOT NULL,
+                `stream` varchar(256) NOT NULL,
+                `recommendation_type` varchar(64) NOT NULL,
+                `target_field` varchar(256) NOT NULL,
+                `payload` json NOT NULL,
+                `status` varchar(32) NOT NULL,
+                `created_at` bigint NOT NULL,
+                `updated_at` bigint NOT NULL,
+                `dedupe_key` varchar(64) NULL
+            )"#
+        );
+        collapsed_eq!(
+            &create_recommendations_org_status_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"CREATE INDEX `recommendations_org_status_idx` ON `recommendations` (`org_id`, `status`)"#
+        );
+        collapsed_eq!(
+            &create_recommendations_dedupe_key_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"CREATE UNIQUE INDEX `recommendations_dedupe_key_idx` ON `recommendations` (`dedupe_key`)"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        collapsed_eq!(
+            &create_recommendations_table_statement().to_string(SqliteQueryBuilder),
+            r#"
+                CREATE TABLE IF NOT EXISTS "recommendations" (
+                "id" varchar(27) NOT NULL PRIMARY KEY,
+                "org_id" varchar(256) NOT NULL,
+                "stream" varchar(256) NOT NULL,
+                "recommendation_type" varchar(64) NOT NULL,
+                "target_field" varchar(256) NOT NULL,
+                "payload" json_text NOT NULL,
+                "status" varchar(32) NOT NULL,
+                "created_at" bigint NOT NULL,
+                "updated_at" bigint NOT NULL,
+                "dedupe_key" varchar(64) NULL
+            )"#
+        );
+        collapsed_eq!(
+            &create_recommendations_org_status_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "recommendations_org_status_idx" ON "recommendations" ("org_id", "status")"#
+        );
+        collapsed_eq!(
+            &create_recommendations_dedupe_key_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "recommendations_dedupe_key_idx" ON "recommendations" ("dedupe_key")"#
+        );
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Adds the sessions's token_hash column, indexed so that sessions can be found by their access
+//! token without a full scan, and back-fills it for existing rows.
+//!
+//! The index is on a fixed-size hash of the token rather than on the token itself, which keeps
+//! the index small however long the tokens are.
+
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+use sea_orm_migration::prelude::*;
+
+use super::{
+    PlanMigration, backfill_page_size, backfill_paged_in_transactions, count_rows, create_index,
+    drop_index, statement_sql,
+};
+use crate::table::cipher;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const SESSIONS_TOKEN_HASH_IDX: &str = "sessions_token_hash_idx";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db_backend = manager.get_database_backend();
+        // the column is already there when a run interrupted during the back-fill is resumed
+        if !manager.has_column("sessions", "token_hash").await? {
+            manager
+                .alter_table(add_token_hash_column_statement(db_backend))
+                .await?;
+        }
+        // MySQL doesn't support IF NOT EXISTS in CREATE INDEX, and its DDL is not rolled back
+        // with a failed back-fill, so the index can also be there already
+        if !manager
+            .has_index("sessions", SESSIONS_TOKEN_HASH_IDX)
+            .await?
+        {
+            manager
+                .create_index(create_sessions_token_hash_idx_stmnt())
+                .await?;
+        }
+        set_token_hash_for_existing_rows(manager).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(drop_sessions_token_hash_idx_stmnt())
+            .await?;
+        manager
+            .alter_table(drop_token_hash_column_statement())
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        // the hashes are back-filled one row at a time
+        vec![
+            statement_sql(db_backend, &add_token_hash_column_statement(db_backend)),
+            statement_sql(db_backend, &create_sessions_token_hash_idx_stmnt()),
+        ]
+    }
+
+    async fn estimated_rows(&self, db: &DatabaseConnection) -> Result<Option<u64>, DbErr> {
+        count_rows(db, "sessions").await.map(Some)
+    }
+}
+
+/// Statement to add the token_hash column, which holds the hex encoded SHA-256 of the plaintext
+/// access token.
+fn add_token_hash_column_statement(db_backend: sea_orm::DbBackend) -> TableAlterStatement {
+    let mut column = ColumnDef::new(Sessions::TokenHash);
+    column.string_len(64).null();
+    if matches!(db_backend, sea_orm::DbBackend::MySql) {
+        // MySQL doesn't support IF NOT EXISTS in ALTER TABLE
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column(column)
+            .to_owned()
+    } else {
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column_if_not_exists(column)
+            .to_owned()
+    }
+}
+
+/// Statement to drop the token_hash column.
+fn drop_token_hash_column_statement() -> TableAlterStatement {
+    Table::alter()
+        .table(Sessions::Table)
+        .drop_column(Sessions::TokenHash)
+        .to_owned()
+}
+
+/// Statement to create the index on token_hash.
+fn create_sessions_token_hash_idx_stmnt() -> IndexCreateStatement {
+    create_index(
+        SESSIONS_TOKEN_HASH_IDX,
+        Sessions::Table,
+        [Sessions::TokenHash],
+    )
+}
+
+/// Statement to drop the index on token_hash.
+fn drop_sessions_token_hash_idx_stmnt() -> IndexDropStatement {
+    drop_index(SESSIONS_TOKEN_HASH_IDX, Sessions::Table)
+}
+
+/// Back-fills token_hash for existing rows from their, possibly encrypted, access token.
+async fn set_token_hash_for_existing_rows(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+    let query = sessions::Entity::find().order_by_asc(sessions::Column::SessionId);
+    let progress = backfill_paged_in_transactions(
+        "sessions_token_hash",
+        db,
+        query,
+        backfill_page_size(),
+        |txn, sessions| {
+            Box::pin(async move {
+                let mut updated = 0;
+                for session in sessions {
+                    let access_token = decrypt_token(&session.access_token)?;
+                    sessions::Entity::update_many()
+                        .col_expr(
+                            sessions::Column::TokenHash,
+                            Expr::value(token_hash(&access_token)),
+                        )
+                        .filter(sessions::Column::SessionId.eq(session.session_id))
+                        .exec(txn)
+                        .await?;
+                    updated += 1;
+                }
+                Ok(updated)
+            })
+        },
+    )
+    .await?;
+
+    log::debug!(
+        "[SESSIONS_MIGRATION] set token_hash for {} rows",
+        progress.rows_changed
+    );
+    Ok(())
+}
+
+// The format of encrypted tokens and of their hash might change after this migration.
+// Therefore it decrypts and hashes them with its own copy of the helpers of the
+// `sessions` table module, as they were when the migration was written.
+
+/// Prefix marking an encrypted access token.
+const ENCRYPTED_TOKEN_PREFIX: &str = "enc:";
+
+/// Decrypts a stored access token. Tokens without the encryption prefix are plaintext and
+/// returned as-is.
+fn decrypt_token(stored: &str) -> Result<String, DbErr> {
+    match stored.strip_prefix(ENCRYPTED_TOKEN_PREFIX) {
+        Some(_) if !cipher::has_master_key() => Err(DbErr::Migration(
+            "session access token is encrypted but no master encryption key is configured".into(),
+        )),
+        Some(encrypted) => {
+            cipher::decrypt_with_master_key(encrypted).map_err(|e| DbErr::Migration(e.to_string()))
+        }
+        None => Ok(stored.to_string()),
+    }
+}
+
+/// Returns the hex encoded SHA-256 of a plaintext access token.
+fn token_hash(access_token: &str) -> String {
+    sha256::digest(access_token)
+}
+
+/// Identifiers used in queries on the sessions table.
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    TokenHash,
+}
+
+// The schemas of tables might change after subsequent migrations. Therefore
+// this migration only references ORM models in private submodules that should
+// remain unchanged rather than ORM models in the `entity` module that will be
+// updated to reflect the latest changes to table schemas.
+
+/// Representation of the sessions table columns used by this migration.
+mod sessions {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+    #[sea_orm(table_name = "sessions")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub session_id: String,
+        pub access_token: String,
+        pub token_hash: Option<String>,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn test_token_hash_of_plaintext_token() {
+        assert_eq!(decrypt_token("token").unwrap(), "token");
+        assert_eq!(
+            token_hash("token"),
+            "3c469e9d6c5875d37a43f353d4f88e61fcf812c66eee3457465a40b0da4153e0"
+        );
+    }
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &add_token_hash_column_statement(sea_orm::DbBackend::Postgres)
+                .to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN IF NOT EXISTS "token_hash" varchar(64) NULL"#
+        );
+        collapsed_eq!(
+            &create_sessions_token_hash_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "sessions_token_hash_idx" ON "sessions" ("token_hash")"#
+        );
+        collapsed_eq!(
+            &drop_sessions_token_hash_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"DROP INDEX "sessions_token_hash_idx""#
+        );
+        collapsed_eq!(
+            &drop_token_hash_column_statement().to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "token_hash""#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &add_token_hash_column_statement(sea_orm::DbBackend::MySql)
+                .to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` ADD COLUMN `token_hash` varchar(64) NULL"#
+        );
+        collapsed_eq!(
+            &create_sessions_token_hash_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"CREATE INDEX `sessions_token_hash_idx` ON `sessions` (`token_hash`)"#
+        );
+        collapsed_eq!(
+            &drop_sessions_token_hash_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"DROP INDEX `sessions_token_hash_idx` ON `sessions`"#
+        );
+        collapsed_eq!(
+            &drop_token_hash_column_statement().to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` DROP COLUMN `token_hash`"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        // SQLite doesn't support IF NOT EXISTS in ALTER TABLE ADD COLUMN
+        collapsed_eq!(
+            &add_token_hash_column_statement(sea_orm::DbBackend::Sqlite)
+                .to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN "token_hash" varchar(64) NULL"#
+        );
+        collapsed_eq!(
+            &create_sessions_token_hash_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "sessions_token_hash_idx" ON "sessions" ("token_hash")"#
+        );
+        collapsed_eq!(
+            &drop_sessions_token_hash_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"DROP INDEX "sessions_token_hash_idx""#
+        );
+        collapsed_eq!(
+            &drop_token_hash_column_statement().to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "token_hash""#
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Adds the nullable metadata column to the sessions table, which holds session attributes such
+//! as the client ip or the login method as JSON.
+//!
+//! Existing rows keep NULL values, there is no source to derive their attributes from.
+
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, get_text_type, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db_backend = manager.get_database_backend();
+        manager
+            .alter_table(add_metadata_column_statement(db_backend))
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(drop_metadata_column_statement())
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        vec![statement_sql(
+            db_backend,
+            &add_metadata_column_statement(db_backend),
+        )]
+    }
+}
+
+/// Statement to add the metadata column.
+fn add_metadata_column_statement(db_backend: sea_orm::DbBackend) -> TableAlterStatement {
+    let mut column = ColumnDef::new(Sessions::Metadata);
+    column.custom(Alias::new(get_text_type())).null();
+    if matches!(db_backend, sea_orm::DbBackend::MySql) {
+        // MySQL doesn't support IF NOT EXISTS in ALTER TABLE
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column(column)
+            .to_owned()
+    } else {
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column_if_not_exists(column)
+            .to_owned()
+    }
+}
+
+/// Statement to drop the metadata column.
+fn drop_metadata_column_statement() -> TableAlterStatement {
+    Table::alter()
+        .table(Sessions::Table)
+        .drop_column(Sessions::Metadata)
+        .to_owned()
+}
+
+/// Identifiers used in queries on the sessions table.
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    Metadata,
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &add_metadata_column_statement(sea_orm::DbBackend::Postgres)
+                .to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN IF NOT EXISTS "metadata" text NULL"#
+        );
+        collapsed_eq!(
+            &drop_metadata_column_statement().to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "metadata""#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &add_metadata_column_statement(sea_orm::DbBackend::MySql).to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` ADD COLUMN `metadata` text NULL"#
+        );
+        collapsed_eq!(
+            &drop_metadata_column_statement().to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` DROP COLUMN `metadata`"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        // SQLite doesn't support IF NOT EXISTS in ALTER TABLE ADD COLUMN
+        collapsed_eq!(
+            &add_metadata_column_statement(sea_orm::DbBackend::Sqlite)
+                .to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN "metadata" text NULL"#
+        );
+        collapsed_eq!(
+            &drop_metadata_column_statement().to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "metadata""#
+        );
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Adds the nullable source column to the sessions table, which records how a session was
+//! created, and sets it to `legacy` for existing rows, whose origin is unknown.
+
+use sea_orm::{DatabaseConnection, Statement};
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, count_rows, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Source of the sessions that existed before the source column. Must match
+/// `SessionSource::Legacy`.
+const LEGACY_SOURCE: &str = "legacy";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db_backend = manager.get_database_backend();
+        manager
+            .alter_table(add_source_column_statement(db_backend))
+            .await?;
+        set_source_for_existing_rows(manager).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.alter_table(drop_source_column_statement()).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        vec![
+            statement_sql(db_backend, &add_source_column_statement(db_backend)),
+            statement_sql(db_backend, &set_legacy_source_statement()),
+        ]
+    }
+
+    async fn estimated_rows(&self, db: &DatabaseConnection) -> Result<Option<u64>, DbErr> {
+        count_rows(db, "sessions").await.map(Some)
+    }
+}
+
+/// Statement to add the source column.
+fn add_source_column_statement(db_backend: sea_orm::DbBackend) -> TableAlterStatement {
+    let mut column = ColumnDef::new(Sessions::Source);
+    column.string_len(32).null();
+    if matches!(db_backend, sea_orm::DbBackend::MySql) {
+        // MySQL doesn't support IF NOT EXISTS in ALTER TABLE
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column(column)
+            .to_owned()
+    } else {
+        Table::alter()
+            .table(Sessions::Table)
+            .add_column_if_not_exists(column)
+            .to_owned()
+    }
+}
+
+/// Statement to drop the source column.
+fn drop_source_column_statement() -> TableAlterStatement {
+    Table::alter()
+        .table(Sessions::Table)
+        .drop_column(Sessions::Source)
+        .to_owned()
+}
+
+/// Statement to set the source of rows that have none yet.
+fn set_legacy_source_statement() -> UpdateStatement {
+    Query::update()
+        .table(Sessions::Table)
+        .value(Sessions::Source, LEGACY_SOURCE)
+        .and_where(Expr::col(Sessions::Source).is_null())
+        .to_owned()
+}
+
+/// Marks the existing rows as legacy sessions.
+async fn set_source_for_existing_rows(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+    let backend = db.get_database_backend();
+    let update_query = set_legacy_source_statement();
+    let (sql, values) = match backend {
+        sea_orm::DatabaseBackend::MySql => update_query.build(MysqlQueryBuilder),
+        sea_orm::DatabaseBackend::Postgres => update_query.build(PostgresQueryBuilder),
+        sea_orm::DatabaseBackend::Sqlite => update_query.build(SqliteQueryBuilder),
+    };
+    let statement = Statement::from_sql_and_values(backend, sql, values);
+    let ret = db.execute(statement).await?;
+    log::debug!(
+        "[SESSIONS_MIGRATION] set source for {} rows",
+        ret.rows_affected()
+    );
+
+    Ok(())
+}
+
+/// Identifiers used in queries on the sessions table.
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    Source,
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &add_source_column_statement(sea_orm::DbBackend::Postgres)
+                .to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN IF NOT EXISTS "source" varchar(32) NULL"#
+        );
+        collapsed_eq!(
+            &set_legacy_source_statement().to_string(PostgresQueryBuilder),
+            r#"UPDATE "sessions" SET "source" = 'legacy' WHERE "source" IS NULL"#
+        );
+        collapsed_eq!(
+            &drop_source_column_statement().to_string(PostgresQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "source""#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &add_source_column_statement(sea_orm::DbBackend::MySql).to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` ADD COLUMN `source` varchar(32) NULL"#
+        );
+        collapsed_eq!(
+            &set_legacy_source_statement().to_string(MysqlQueryBuilder),
+            r#"UPDATE `sessions` SET `source` = 'legacy' WHERE `source` IS NULL"#
+        );
+        collapsed_eq!(
+            &drop_source_column_statement().to_string(MysqlQueryBuilder),
+            r#"ALTER TABLE `sessions` DROP COLUMN `source`"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        // SQLite doesn't support IF NOT EXISTS in ALTER TABLE ADD COLUMN
+        collapsed_eq!(
+            &add_source_column_statement(sea_orm::DbBackend::Sqlite).to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" ADD COLUMN "source" varchar(32) NULL"#
+        );
+        collapsed_eq!(
+            &set_legacy_source_statement().to_string(SqliteQueryBuilder),
+            r#"UPDATE "sessions" SET "source" = 'legacy' WHERE "source" IS NULL"#
+        );
+        collapsed_eq!(
+            &drop_source_column_statement().to_string(SqliteQueryBuilder),
+            r#"ALTER TABLE "sessions" DROP COLUMN "source""#
+        );
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Creates the query_latency_stats table, which holds the p95 latency of each query fingerprint
+//! as measured by each recommendation run, so that runs can detect latency regressions.
+
+use sea_orm_migration::prelude::*;
+
+use super::{PlanMigration, create_index, drop_index, get_text_type, statement_sql};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const QUERY_LATENCY_STATS_ORG_FINGERPRINT_IDX: &str = "query_latency_stats_org_fingerprint_idx";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(create_query_latency_stats_table_statement())
+            .await?;
+        manager
+            .create_index(create_query_latency_stats_org_fingerprint_idx_stmnt())
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(drop_index(
+                QUERY_LATENCY_STATS_ORG_FINGERPRINT_IDX,
+                QueryLatencyStats::Table,
+            ))
+            .await?;
+        manager
+            .drop_table(Table::drop().table(QueryLatencyStats::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl PlanMigration for Migration {
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String> {
+        vec![
+            statement_sql(db_backend, &create_query_latency_stats_table_statement()),
+            statement_sql(
+                db_backend,
+                &create_query_latency_stats_org_fingerprint_idx_stmnt(),
+            ),
+        ]
+    }
+}
+
+fn create_query_latency_stats_table_statement() -> TableCreateStatement {
+    Table::create()
+        .table(QueryLatencyStats::Table)
+        .if_not_exists()
+        .col(
+            ColumnDef::new(QueryLatencyStats::Id)
+                .string_len(27)
+                .not_null()
+                .primary_key(),
+        )
+        .col(
+            ColumnDef::new(QueryLatencyStats::OrgId)
+                .string_len(256)
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(QueryLatencyStats::Fingerprint)
+                .string_len(64)
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(QueryLatencyStats::Sql)
+                .custom(Alias::new(get_text_type()))
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(QueryLatencyStats::P95Micros)
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(QueryLatencyStats::Queries)
+                .big_integer()
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(QueryLatencyStats::CreatedAt)
+                .big_integer()
+                .not_null(),
+        )
+        .to_owned()
+}
+
+fn create_query_latency_stats_org_fingerprint_idx_stmnt() -> IndexCreateStatement {
+    create_index(
+        QUERY_LATENCY_STATS_ORG_FINGERPRINT_IDX,
+        QueryLatencyStats::Table,
+        [
+            QueryLatencyStats::OrgId,
+            QueryLatencyStats::Fingerprint,
+            QueryLatencyStats::CreatedAt,
+        ],
+    )
+}
+
+#[derive(DeriveIden)]
+enum QueryLatencyStats {
+    Table,
+    Id,
+    OrgId,
+    Fingerprint,
+    Sql,
+    P95Micros,
+    Queries,
+    CreatedAt,
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+
+    use super::*;
+
+    #[test]
+    fn postgres() {
+        collapsed_eq!(
+            &create_query_latency_stats_table_statement().to_string(PostgresQueryBuilder),
+            r#"
+                CREATE TABLE IF NOT EXISTS "query_latency_stats" (
+                "id" varchar(27) NOT NULL PRIMARY KEY,
+                "org_id" varchar(256) NOT NULL,
+                "fingerprint" varchar(64) NOT NULL,
+                "sql" text NOT NULL,
+                "p95_micros" bigint NOT NULL,
+                "queries" bigint NOT NULL,
+                "created_at" bigint NOT NULL
+            )"#
+        );
+        collapsed_eq!(
+            &create_query_latency_stats_org_fingerprint_idx_stmnt().to_string(PostgresQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "query_latency_stats_org_fingerprint_idx" ON "query_latency_stats" ("org_id", "fingerprint", "created_at")"#
+        );
+    }
+
+    #[test]
+    fn mysql() {
+        collapsed_eq!(
+            &create_query_latency_stats_table_statement().to_string(MysqlQueryBuilder),
+            r#"
+                CREATE TABLE IF NOT EXISTS `query_latency_stats` (
+                `id` varchar(27) NOT NULL PRIMARY KEY,
+                `org_id` varchar(256) NOT NULL,
+                `fingerprint` varchar(64) NOT NULL,
+                `sql` text NOT NULL,
+                `p95_micros` bigint NOT NULL,
+                `queries` bigint NOT NULL,
+                `created_at` bigint NOT NULL
+            )"#
+        );
+        collapsed_eq!(
+            &create_query_latency_stats_org_fingerprint_idx_stmnt().to_string(MysqlQueryBuilder),
+            r#"CREATE INDEX `query_latency_stats_org_fingerprint_idx` ON `query_latency_stats` (`org_id`, `fingerprint`, `created_at`)"#
+        );
+    }
+
+    #[test]
+    fn sqlite() {
+        collapsed_eq!(
+            &create_query_latency_stats_table_statement().to_string(SqliteQueryBuilder),
+            r#"
+                CREATE TABLE IF NOT EXISTS "query_latency_stats" (
+                "id" varchar(27) NOT NULL PRIMARY KEY,
+                "org_id" varchar(256) NOT NULL,
+                "fingerprint" varchar(64) NOT NULL,
+                "sql" text NOT NULL,
+                "p95_micros" bigint NOT NULL,
+                "queries" bigint NOT NULL,
+                "created_at" bigint NOT NULL
+            )"#
+        );
+        collapsed_eq!(
+            &create_query_latency_stats_org_fingerprint_idx_stmnt().to_string(SqliteQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "query_latency_stats_org_fingerprint_idx" ON "query_latency_stats" ("org_id", "fingerprint", "created_at")"#
+        );
+    }
+}
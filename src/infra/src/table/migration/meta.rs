@@ -0,0 +1,100 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Snapshots of the meta table, shared by the data migrations that read records from it or
+//! delete them.
+//!
+//! Migrations must keep working against the schema they were written for, so each snapshot is
+//! frozen once a migration uses it. If the meta table ever changes, add a new versioned snapshot
+//! next to the existing ones instead of editing them.
+//!
+//! The snapshots are only visible to the migrations, runtime code reads the meta table through
+//! the `db` module.
+
+/// Representation of the meta table since it was first migrated to dedicated tables.
+pub mod v1 {
+    use sea_orm::{QueryOrder, Select, entity::prelude::*};
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+    #[sea_orm(table_name = "meta")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub id: i64,
+        pub module: String,
+        pub key1: String,
+        pub key2: String,
+        pub start_dt: i64,
+        #[sea_orm(column_type = "Text")]
+        pub value: String,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+
+    /// Selects the records of a module, in the order they were inserted, so that migrations
+    /// paging through them see every record once.
+    pub fn find_by_module(module: &str) -> Select<Entity> {
+        Entity::find()
+            .filter(Column::Module.eq(module))
+            .order_by_asc(Column::Id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase, PaginatorTrait, QueryTrait};
+
+    use super::v1;
+
+    fn record(id: i64, module: &str) -> v1::Model {
+        v1::Model {
+            id,
+            module: module.to_string(),
+            key1: "default".to_string(),
+            key2: format!("key-{id}"),
+            start_dt: 0,
+            value: "{}".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_by_module_sql() {
+        assert_eq!(
+            v1::find_by_module("folders")
+                .build(DatabaseBackend::Postgres)
+                .to_string(),
+            r#"SELECT "meta"."id", "meta"."module", "meta"."key1", "meta"."key2", "meta"."start_dt", "meta"."value" FROM "meta" WHERE "meta"."module" = 'folders' ORDER BY "meta"."id" ASC"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_by_module_reads_seeded_rows() {
+        let db = MockDatabase::new(DatabaseBackend::Sqlite)
+            .append_query_results([vec![record(1, "folders"), record(3, "folders")]])
+            .into_connection();
+        let mut pages = v1::find_by_module("folders").paginate(&db, 100);
+        let records = pages.fetch_and_next().await.unwrap().unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| record.key2.as_str())
+                .collect::<Vec<_>>(),
+            vec!["key-1", "key-3"]
+        );
+        assert!(records.iter().all(|record| record.module == "folders"));
+    }
+}
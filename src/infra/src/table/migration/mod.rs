@@ -16,7 +16,16 @@
 // Allow enterprise feature cfg check (defined at workspace level)
 #![allow(unexpected_cfgs)]
 
+use std::{
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
 use config::meta::meta_store::MetaStore;
+use sea_orm::{
+    ActiveModelTrait, ConnectionTrait, DatabaseConnection, DatabaseTransaction, EntityTrait,
+    Iterable, PaginatorTrait, Select, StatementBuilder, TransactionTrait,
+};
 pub use sea_orm_migration::prelude::*;
 
 mod m20241114_000001_create_folders_table;
@@ -74,6 +83,18 @@ mod m20251105_000001_update_enrichment_table_created_at_mysql;
 mod m20251118_000001_add_alert_row_template_type;
 mod m20251118_000002_create_sessions_table;
 mod m20251118_000003_delete_meta_sessions;
+mod m20251201_000001_add_sessions_expires_at;
+mod m20251201_000002_add_sessions_last_used_at;
+mod m20251201_000003_add_sessions_user_id_user_agent;
+mod m20251201_000004_widen_sessions_access_token_mysql;
+mod m20251201_000005_encrypt_sessions_access_token;
+mod m20251201_000006_add_sessions_updated_at_idx;
+mod m20251201_000007_create_recommendations_table;
+mod m20251201_000008_add_sessions_token_hash;
+mod m20251201_000009_add_sessions_metadata;
+mod m20251201_000010_add_sessions_source;
+mod m20251201_000011_create_query_latency_stats_table;
+mod meta;
 
 pub struct Migrator;
 
@@ -137,10 +158,135 @@ impl MigratorTrait for Migrator {
             Box::new(m20251118_000001_add_alert_row_template_type::Migration),
             Box::new(m20251118_000002_create_sessions_table::Migration),
             Box::new(m20251118_000003_delete_meta_sessions::Migration),
+            Box::new(m20251201_000001_add_sessions_expires_at::Migration),
+            Box::new(m20251201_000002_add_sessions_last_used_at::Migration),
+            Box::new(m20251201_000003_add_sessions_user_id_user_agent::Migration),
+            Box::new(m20251201_000004_widen_sessions_access_token_mysql::Migration),
+            Box::new(m20251201_000005_encrypt_sessions_access_token::Migration),
+            Box::new(m20251201_000006_add_sessions_updated_at_idx::Migration),
+            Box::new(m20251201_000007_create_recommendations_table::Migration),
+            Box::new(m20251201_000008_add_sessions_token_hash::Migration),
+            Box::new(m20251201_000009_add_sessions_metadata::Migration),
+            Box::new(m20251201_000010_add_sessions_source::Migration),
+            Box::new(m20251201_000011_create_query_latency_stats_table::Migration),
         ]
     }
 }
 
+/// What a pending migration would do, worked out without running it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationPlan {
+    pub name: String,
+    /// The SQL the migration would run, or None if the migration can't describe it.
+    pub statements: Option<Vec<String>>,
+    /// The number of rows a data migration would process, None for schema migrations.
+    pub estimated_rows: Option<u64>,
+}
+
+/// Implemented by migrations that can describe what they would do without running.
+#[async_trait::async_trait]
+trait PlanMigration: Send + Sync {
+    /// The statements the migration runs on `db_backend`, as SQL. Statements a data migration
+    /// builds from the rows it reads are left out, [`PlanMigration::estimated_rows`] covers them.
+    fn statements(&self, db_backend: sea_orm::DbBackend) -> Vec<String>;
+
+    /// The number of rows the migration would process, for data migrations.
+    async fn estimated_rows(&self, _db: &DatabaseConnection) -> Result<Option<u64>, DbErr> {
+        Ok(None)
+    }
+}
+
+/// Returns the plan of a migration by its name, None if the migration has none.
+fn migration_plan(name: &str) -> Option<Box<dyn PlanMigration>> {
+    let plan: Box<dyn PlanMigration> = match name {
+        "m20251118_000002_create_sessions_table" => {
+            Box::new(m20251118_000002_create_sessions_table::Migration)
+        }
+        "m20251201_000001_add_sessions_expires_at" => {
+            Box::new(m20251201_000001_add_sessions_expires_at::Migration)
+        }
+        "m20251201_000002_add_sessions_last_used_at" => {
+            Box::new(m20251201_000002_add_sessions_last_used_at::Migration)
+        }
+        "m20251201_000003_add_sessions_user_id_user_agent" => {
+            Box::new(m20251201_000003_add_sessions_user_id_user_agent::Migration)
+        }
+        "m20251201_000004_widen_sessions_access_token_mysql" => {
+            Box::new(m20251201_000004_widen_sessions_access_token_mysql::Migration)
+        }
+        "m20251201_000005_encrypt_sessions_access_token" => {
+            Box::new(m20251201_000005_encrypt_sessions_access_token::Migration)
+        }
+        "m20251201_000006_add_sessions_updated_at_idx" => {
+            Box::new(m20251201_000006_add_sessions_updated_at_idx::Migration)
+        }
+        "m20251201_000007_create_recommendations_table" => {
+            Box::new(m20251201_000007_create_recommendations_table::Migration)
+        }
+        "m20251201_000008_add_sessions_token_hash" => {
+            Box::new(m20251201_000008_add_sessions_token_hash::Migration)
+        }
+        "m20251201_000009_add_sessions_metadata" => {
+            Box::new(m20251201_000009_add_sessions_metadata::Migration)
+        }
+        "m20251201_000010_add_sessions_source" => {
+            Box::new(m20251201_000010_add_sessions_source::Migration)
+        }
+        "m20251201_000011_create_query_latency_stats_table" => {
+            Box::new(m20251201_000011_create_query_latency_stats_table::Migration)
+        }
+        _ => return None,
+    };
+    Some(plan)
+}
+
+/// Returns the plans of the migrations that have not been applied yet, in the order they would
+/// run. Nothing is migrated, but the table that tracks applied migrations is created if missing.
+pub async fn plan_pending_migrations(db: &DatabaseConnection) -> Result<Vec<MigrationPlan>, DbErr> {
+    let db_backend = db.get_database_backend();
+    let mut plans = vec![];
+    for migration in Migrator::get_pending_migrations(db).await? {
+        let name = migration.name().to_string();
+        let plan = match migration_plan(&name) {
+            Some(plan) => MigrationPlan {
+                statements: Some(plan.statements(db_backend)),
+                estimated_rows: plan.estimated_rows(db).await?,
+                name,
+            },
+            None => MigrationPlan {
+                name,
+                ..Default::default()
+            },
+        };
+        plans.push(plan);
+    }
+    Ok(plans)
+}
+
+/// Returns `statement` as SQL for `db_backend`, with its values inlined.
+fn statement_sql<S: StatementBuilder>(db_backend: sea_orm::DbBackend, statement: &S) -> String {
+    db_backend.build(statement).to_string()
+}
+
+/// Returns the number of rows of `table`, 0 if the table doesn't exist yet.
+async fn count_rows(db: &DatabaseConnection, table: &str) -> Result<u64, DbErr> {
+    if !SchemaManager::new(db).has_table(table).await? {
+        return Ok(0);
+    }
+    let query = Query::select()
+        .expr_as(Expr::col(Asterisk).count(), Alias::new("count"))
+        .from(Alias::new(table))
+        .to_owned();
+    let count = match db
+        .query_one(db.get_database_backend().build(&query))
+        .await?
+    {
+        Some(row) => row.try_get::<i64>("", "count")?,
+        None => 0,
+    };
+    Ok(count as u64)
+}
+
 pub fn get_text_type() -> String {
     let db_type = config::get_config().common.meta_store.as_str().into();
     match db_type {
@@ -148,3 +294,558 @@ pub fn get_text_type() -> String {
         _ => "text".to_string(),
     }
 }
+
+/// Default number of rows per page for data migrations.
+pub const BACKFILL_PAGE_SIZE: u64 = 1000;
+
+/// Returns the number of rows per page for data migrations, `ZO_META_MIGRATION_PAGE_SIZE`, or
+/// [`BACKFILL_PAGE_SIZE`] when it is 0.
+pub fn backfill_page_size() -> u64 {
+    page_size_or_default(config::get_config().limit.meta_migration_page_size)
+}
+
+fn page_size_or_default(page_size: u64) -> u64 {
+    if page_size == 0 {
+        BACKFILL_PAGE_SIZE
+    } else {
+        page_size
+    }
+}
+
+/// Number of pages between two progress logs of [`backfill_paged`].
+const BACKFILL_LOG_INTERVAL_PAGES: u64 = 10;
+
+/// Number of pages [`backfill_paged_in_transactions`] writes in one transaction on SQLite.
+const SQLITE_BACKFILL_COMMIT_INTERVAL_PAGES: u64 = 10;
+
+/// Upper bound on the rows of one insert statement, which keeps statements well below MySQL's
+/// max_allowed_packet.
+const MAX_INSERT_ROWS: usize = 1000;
+
+/// Progress of a [`backfill_paged`] run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BackfillProgress {
+    pub pages: u64,
+    pub rows_read: u64,
+    pub rows_changed: u64,
+    pub elapsed: Duration,
+}
+
+/// Calls `f` with the rows selected by `query`, `page_size` rows at a time, so data migrations
+/// never load a whole table into memory. `f` returns the number of rows of the page it changed.
+///
+/// Rows are read through `db`, which should be the transaction the migration writes in, so that
+/// a failing page rolls back all the pages before it. Progress is logged under `name` every
+/// [`BACKFILL_LOG_INTERVAL_PAGES`] pages and once more when done.
+pub async fn backfill_paged<'db, E, C, F, Fut>(
+    name: &str,
+    db: &'db C,
+    query: Select<E>,
+    page_size: u64,
+    mut f: F,
+) -> Result<BackfillProgress, DbErr>
+where
+    E: EntityTrait,
+    E::Model: Sync + 'db,
+    C: ConnectionTrait,
+    F: FnMut(Vec<E::Model>) -> Fut,
+    Fut: Future<Output = Result<u64, DbErr>>,
+{
+    let start = Instant::now();
+    let mut progress = BackfillProgress::default();
+    let mut pages = query.paginate(db, page_size);
+    while let Some(models) = pages.fetch_and_next().await? {
+        progress.rows_read += models.len() as u64;
+        progress.rows_changed += f(models).await?;
+        progress.pages += 1;
+        progress.elapsed = start.elapsed();
+        if progress.pages % BACKFILL_LOG_INTERVAL_PAGES == 0 {
+            log::info!(
+                "[Migration] {name}: {} pages, {} rows read, {} rows changed in {}s",
+                progress.pages,
+                progress.rows_read,
+                progress.rows_changed,
+                progress.elapsed.as_secs()
+            );
+        }
+    }
+    progress.elapsed = start.elapsed();
+    log::info!(
+        "[Migration] {name} done: {} rows read, {} rows changed in {}ms",
+        progress.rows_read,
+        progress.rows_changed,
+        progress.elapsed.as_millis()
+    );
+    Ok(progress)
+}
+
+/// Like [`backfill_paged`], but opens the transactions `f` writes in itself.
+///
+/// Postgres and MySQL write every page in a single transaction. SQLite commits every
+/// [`SQLITE_BACKFILL_COMMIT_INTERVAL_PAGES`] pages instead, because one large transaction grows
+/// its WAL without bound and locks out readers until the migration is done. A run interrupted on
+/// SQLite therefore keeps the pages it already committed, and the migration runs again on the
+/// next start: `f` must skip rows an earlier run already changed.
+pub async fn backfill_paged_in_transactions<E, C, F>(
+    name: &str,
+    db: &C,
+    query: Select<E>,
+    page_size: u64,
+    mut f: F,
+) -> Result<BackfillProgress, DbErr>
+where
+    E: EntityTrait,
+    E::Model: Sync,
+    C: ConnectionTrait + TransactionTrait,
+    F: for<'t> FnMut(
+        &'t DatabaseTransaction,
+        Vec<E::Model>,
+    ) -> Pin<Box<dyn Future<Output = Result<u64, DbErr>> + Send + 't>>,
+{
+    let commit_interval = backfill_commit_interval_pages(db.get_database_backend());
+    let start = Instant::now();
+    let mut progress = BackfillProgress::default();
+    let mut txn = db.begin().await?;
+    loop {
+        let models = query
+            .clone()
+            .paginate(&txn, page_size)
+            .fetch_page(progress.pages)
+            .await?;
+        if models.is_empty() {
+            break;
+        }
+        progress.rows_read += models.len() as u64;
+        progress.rows_changed += f(&txn, models).await?;
+        progress.pages += 1;
+        progress.elapsed = start.elapsed();
+        if progress.pages % BACKFILL_LOG_INTERVAL_PAGES == 0 {
+            log::info!(
+                "[Migration] {name}: {} pages, {} rows read, {} rows changed in {}s",
+                progress.pages,
+                progress.rows_read,
+                progress.rows_changed,
+                progress.elapsed.as_secs()
+            );
+        }
+        if commit_interval.is_some_and(|interval| progress.pages % interval == 0) {
+            txn.commit().await?;
+            txn = db.begin().await?;
+        }
+    }
+    txn.commit().await?;
+    progress.elapsed = start.elapsed();
+    log::info!(
+        "[Migration] {name} done: {} rows read, {} rows changed in {}ms",
+        progress.rows_read,
+        progress.rows_changed,
+        progress.elapsed.as_millis()
+    );
+    Ok(progress)
+}
+
+/// Returns after how many pages [`backfill_paged_in_transactions`] commits, or None if it writes
+/// every page in one transaction.
+fn backfill_commit_interval_pages(db_backend: sea_orm::DbBackend) -> Option<u64> {
+    match db_backend {
+        sea_orm::DbBackend::Sqlite => Some(SQLITE_BACKFILL_COMMIT_INTERVAL_PAGES),
+        sea_orm::DbBackend::MySql | sea_orm::DbBackend::Postgres => None,
+    }
+}
+
+/// Returns the number of rows with `columns` columns that fit in one insert statement without
+/// exceeding the bind parameter limit of the database.
+pub fn max_insert_rows(db_backend: sea_orm::DbBackend, columns: usize) -> usize {
+    let max_params = match db_backend {
+        sea_orm::DbBackend::MySql | sea_orm::DbBackend::Postgres => 65_535,
+        // SQLITE_MAX_VARIABLE_NUMBER since SQLite 3.32
+        sea_orm::DbBackend::Sqlite => 32_766,
+    };
+    (max_params / columns.max(1)).clamp(1, MAX_INSERT_ROWS)
+}
+
+/// Inserts `models` with as few statements as possible, splitting them so that no statement
+/// exceeds [`max_insert_rows`].
+pub async fn insert_many_chunked<A, C>(db: &C, models: Vec<A>) -> Result<(), DbErr>
+where
+    A: ActiveModelTrait,
+    C: ConnectionTrait,
+{
+    let columns = <A::Entity as EntityTrait>::Column::iter().count();
+    let max_rows = max_insert_rows(db.get_database_backend(), columns);
+    let mut models = models.into_iter().peekable();
+    while models.peek().is_some() {
+        let chunk = models.by_ref().take(max_rows).collect::<Vec<_>>();
+        <A::Entity as EntityTrait>::insert_many(chunk)
+            .exec(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Statement to create the index `name` on `cols` of `table`, if it does not exist yet. Several
+/// columns make a composite index, in the given order.
+pub fn create_index<T, C, I>(name: &str, table: T, cols: I) -> IndexCreateStatement
+where
+    T: IntoTableRef,
+    C: IntoIndexColumn,
+    I: IntoIterator<Item = C>,
+{
+    let mut index = sea_query::Index::create();
+    index.if_not_exists().name(name).table(table);
+    for col in cols {
+        index.col(col);
+    }
+    index
+}
+
+/// Statement to create the unique index `name` on `cols` of `table`, if it does not exist yet.
+/// Several columns make the combination of their values unique.
+pub fn create_unique_index<T, C, I>(name: &str, table: T, cols: I) -> IndexCreateStatement
+where
+    T: IntoTableRef,
+    C: IntoIndexColumn,
+    I: IntoIterator<Item = C>,
+{
+    create_index(name, table, cols).unique().to_owned()
+}
+
+/// Statement to drop the index `name` of `table`. MySQL requires the table, other databases
+/// ignore it.
+pub fn drop_index<T: IntoTableRef>(name: &str, table: T) -> IndexDropStatement {
+    Index::drop().name(name).table(table).to_owned()
+}
+
+/// Returns the column type for values that may not fit in 64KB, such as encrypted tokens.
+///
+/// Unlike [`get_text_type`] this does not follow `ZO_TEXT_DATA_TYPE`: MySQL always gets
+/// `longtext`, since its `text` type would truncate large values.
+pub fn get_long_text_type(db_backend: sea_orm::DbBackend) -> &'static str {
+    match db_backend {
+        sea_orm::DbBackend::MySql => "longtext",
+        _ => "text",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use collapse::*;
+    use sea_orm::{
+        ActiveValue::Set, ColumnTrait, Database, QueryFilter, QueryOrder, Schema, TransactionTrait,
+    };
+
+    use super::*;
+
+    /// Table the backfill test runs against.
+    mod rows {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+        #[sea_orm(table_name = "rows")]
+        pub struct Model {
+            #[sea_orm(primary_key, auto_increment = false)]
+            pub id: i64,
+            pub value: i64,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    #[test]
+    fn test_page_size_or_default() {
+        assert_eq!(page_size_or_default(0), BACKFILL_PAGE_SIZE);
+        assert_eq!(page_size_or_default(250), 250);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_paged() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let backend = db.get_database_backend();
+        let create = Schema::new(backend).create_table_from_entity(rows::Entity);
+        db.execute(backend.build(&create)).await.unwrap();
+        let models = (0..350)
+            .map(|id| rows::ActiveModel {
+                id: Set(id),
+                value: Set(0),
+            })
+            .collect::<Vec<_>>();
+        insert_many_chunked(&db, models).await.unwrap();
+
+        let txn = db.begin().await.unwrap();
+        let query = rows::Entity::find().order_by_asc(rows::Column::Id);
+        let progress = backfill_paged("test", &txn, query, 100, |page| {
+            let txn = &txn;
+            async move {
+                let mut changed = 0;
+                for row in page.into_iter().filter(|row| row.id % 2 == 0) {
+                    rows::Entity::update_many()
+                        .col_expr(rows::Column::Value, Expr::value(1))
+                        .filter(rows::Column::Id.eq(row.id))
+                        .exec(txn)
+                        .await?;
+                    changed += 1;
+                }
+                Ok(changed)
+            }
+        })
+        .await
+        .unwrap();
+        txn.commit().await.unwrap();
+
+        assert_eq!(progress.pages, 4);
+        assert_eq!(progress.rows_read, 350);
+        assert_eq!(progress.rows_changed, 175);
+        let updated = rows::Entity::find()
+            .filter(rows::Column::Value.eq(1))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(updated, 175);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_paged_in_transactions_resumes_after_interruption() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let backend = db.get_database_backend();
+        let create = Schema::new(backend).create_table_from_entity(rows::Entity);
+        db.execute(backend.build(&create)).await.unwrap();
+        let models = (0..250)
+            .map(|id| rows::ActiveModel {
+                id: Set(id),
+                value: Set(0),
+            })
+            .collect::<Vec<_>>();
+        insert_many_chunked(&db, models).await.unwrap();
+
+        // sets value to 1 for rows that don't have it yet, and fails on the row `fail_at`
+        let backfill = |fail_at: Option<i64>| {
+            let query = rows::Entity::find().order_by_asc(rows::Column::Id);
+            backfill_paged_in_transactions("test", &db, query, 10, move |txn, page| {
+                Box::pin(async move {
+                    let mut changed = 0;
+                    for row in page.into_iter().filter(|row| row.value == 0) {
+                        if Some(row.id) == fail_at {
+                            return Err(DbErr::Custom("interrupted".to_string()));
+                        }
+                        rows::Entity::update_many()
+                            .col_expr(rows::Column::Value, Expr::value(1))
+                            .filter(rows::Column::Id.eq(row.id))
+                            .exec(txn)
+                            .await?;
+                        changed += 1;
+                    }
+                    Ok(changed)
+                })
+            })
+        };
+        let updated = || {
+            rows::Entity::find()
+                .filter(rows::Column::Value.eq(1))
+                .count(&db)
+        };
+
+        // the 10 pages committed before the failure are kept, the rest is rolled back
+        assert!(backfill(Some(145)).await.is_err());
+        assert_eq!(updated().await.unwrap(), 100);
+
+        // running again finishes the rows left over
+        let progress = backfill(None).await.unwrap();
+        assert_eq!(progress.pages, 25);
+        assert_eq!(progress.rows_read, 250);
+        assert_eq!(progress.rows_changed, 150);
+        assert_eq!(updated().await.unwrap(), 250);
+    }
+
+    #[test]
+    fn test_backfill_commit_interval_pages() {
+        assert_eq!(
+            backfill_commit_interval_pages(sea_orm::DbBackend::Sqlite),
+            Some(SQLITE_BACKFILL_COMMIT_INTERVAL_PAGES)
+        );
+        assert_eq!(
+            backfill_commit_interval_pages(sea_orm::DbBackend::Postgres),
+            None
+        );
+        assert_eq!(
+            backfill_commit_interval_pages(sea_orm::DbBackend::MySql),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plan_pending_migrations() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let plans = plan_pending_migrations(&db).await.unwrap();
+        assert_eq!(plans.len(), Migrator::migrations().len());
+        // planning applies nothing
+        assert_eq!(
+            Migrator::get_pending_migrations(&db).await.unwrap().len(),
+            plans.len()
+        );
+
+        let plan = plans
+            .iter()
+            .find(|plan| plan.name == "m20251201_000009_add_sessions_metadata")
+            .unwrap();
+        assert_eq!(
+            plan.statements,
+            Some(vec![
+                r#"ALTER TABLE "sessions" ADD COLUMN "metadata" text NULL"#.to_string()
+            ])
+        );
+        assert_eq!(plan.estimated_rows, None);
+
+        // the sessions table doesn't exist yet, so there is nothing to back-fill
+        let plan = plans
+            .iter()
+            .find(|plan| plan.name == "m20251201_000010_add_sessions_source")
+            .unwrap();
+        assert_eq!(
+            plan.statements.as_ref().unwrap()[1],
+            r#"UPDATE "sessions" SET "source" = 'legacy' WHERE "source" IS NULL"#
+        );
+        assert_eq!(plan.estimated_rows, Some(0));
+
+        let plan = plans
+            .iter()
+            .find(|plan| plan.name == "m20241114_000001_create_folders_table")
+            .unwrap();
+        assert_eq!(plan.statements, None);
+    }
+
+    #[tokio::test]
+    async fn test_count_rows() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        assert_eq!(count_rows(&db, "rows").await.unwrap(), 0);
+
+        let backend = db.get_database_backend();
+        let create = Schema::new(backend).create_table_from_entity(rows::Entity);
+        db.execute(backend.build(&create)).await.unwrap();
+        let models = (0..42)
+            .map(|id| rows::ActiveModel {
+                id: Set(id),
+                value: Set(id),
+            })
+            .collect::<Vec<_>>();
+        insert_many_chunked(&db, models).await.unwrap();
+        assert_eq!(count_rows(&db, "rows").await.unwrap(), 42);
+    }
+
+    #[test]
+    fn test_index_statements_postgres() {
+        collapsed_eq!(
+            &create_index("rows_value_idx", rows::Entity, [rows::Column::Value])
+                .to_string(PostgresQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "rows_value_idx" ON "rows" ("value")"#
+        );
+        collapsed_eq!(
+            &create_unique_index("rows_value_idx", rows::Entity, [rows::Column::Value])
+                .to_string(PostgresQueryBuilder),
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "rows_value_idx" ON "rows" ("value")"#
+        );
+        collapsed_eq!(
+            &drop_index("rows_value_idx", rows::Entity).to_string(PostgresQueryBuilder),
+            r#"DROP INDEX "rows_value_idx""#
+        );
+    }
+
+    #[test]
+    fn test_index_statements_mysql() {
+        collapsed_eq!(
+            &create_index("rows_value_idx", rows::Entity, [rows::Column::Value])
+                .to_string(MysqlQueryBuilder),
+            r#"CREATE INDEX `rows_value_idx` ON `rows` (`value`)"#
+        );
+        collapsed_eq!(
+            &create_unique_index("rows_value_idx", rows::Entity, [rows::Column::Value])
+                .to_string(MysqlQueryBuilder),
+            r#"CREATE UNIQUE INDEX `rows_value_idx` ON `rows` (`value`)"#
+        );
+        collapsed_eq!(
+            &drop_index("rows_value_idx", rows::Entity).to_string(MysqlQueryBuilder),
+            r#"DROP INDEX `rows_value_idx` ON `rows`"#
+        );
+    }
+
+    #[test]
+    fn test_index_statements_sqlite() {
+        collapsed_eq!(
+            &create_index("rows_value_idx", rows::Entity, [rows::Column::Value])
+                .to_string(SqliteQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "rows_value_idx" ON "rows" ("value")"#
+        );
+        collapsed_eq!(
+            &create_unique_index("rows_value_idx", rows::Entity, [rows::Column::Value])
+                .to_string(SqliteQueryBuilder),
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "rows_value_idx" ON "rows" ("value")"#
+        );
+        collapsed_eq!(
+            &drop_index("rows_value_idx", rows::Entity).to_string(SqliteQueryBuilder),
+            r#"DROP INDEX "rows_value_idx""#
+        );
+    }
+
+    #[test]
+    fn test_composite_index_statements() {
+        let cols = [rows::Column::Id, rows::Column::Value];
+        collapsed_eq!(
+            &create_unique_index("rows_id_value_idx", rows::Entity, cols)
+                .to_string(PostgresQueryBuilder),
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "rows_id_value_idx" ON "rows" ("id", "value")"#
+        );
+        collapsed_eq!(
+            &create_unique_index("rows_id_value_idx", rows::Entity, cols)
+                .to_string(MysqlQueryBuilder),
+            r#"CREATE UNIQUE INDEX `rows_id_value_idx` ON `rows` (`id`, `value`)"#
+        );
+        collapsed_eq!(
+            &create_unique_index("rows_id_value_idx", rows::Entity, cols)
+                .to_string(SqliteQueryBuilder),
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "rows_id_value_idx" ON "rows" ("id", "value")"#
+        );
+        collapsed_eq!(
+            &create_index("rows_id_value_idx", rows::Entity, cols).to_string(PostgresQueryBuilder),
+            r#"CREATE INDEX IF NOT EXISTS "rows_id_value_idx" ON "rows" ("id", "value")"#
+        );
+    }
+
+    #[test]
+    fn test_max_insert_rows() {
+        assert_eq!(max_insert_rows(sea_orm::DbBackend::Sqlite, 10), 1000);
+        assert_eq!(max_insert_rows(sea_orm::DbBackend::Sqlite, 100), 327);
+        assert_eq!(max_insert_rows(sea_orm::DbBackend::MySql, 100), 655);
+        assert_eq!(max_insert_rows(sea_orm::DbBackend::Postgres, 0), 1000);
+        assert_eq!(max_insert_rows(sea_orm::DbBackend::Sqlite, 40_000), 1);
+    }
+
+    #[tokio::test]
+    async fn test_insert_many_chunked() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let backend = db.get_database_backend();
+        let create = Schema::new(backend).create_table_from_entity(rows::Entity);
+        db.execute(backend.build(&create)).await.unwrap();
+
+        let models = (0..2500)
+            .map(|id| rows::ActiveModel {
+                id: Set(id),
+                value: Set(id),
+            })
+            .collect::<Vec<_>>();
+        insert_many_chunked(&db, models).await.unwrap();
+        assert_eq!(rows::Entity::find().count(&db).await.unwrap(), 2500);
+
+        insert_many_chunked::<rows::ActiveModel, _>(&db, vec![])
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_long_text_type() {
+        assert_eq!(get_long_text_type(sea_orm::DbBackend::MySql), "longtext");
+        assert_eq!(get_long_text_type(sea_orm::DbBackend::Postgres), "text");
+        assert_eq!(get_long_text_type(sea_orm::DbBackend::Sqlite), "text");
+    }
+}
@@ -14,6 +14,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use config::get_config;
+pub use migration::MigrationPlan;
 use migration::Migrator;
 use sea_orm_migration::MigratorTrait;
 
@@ -35,9 +36,11 @@ pub mod folders;
 mod migration;
 pub mod org_users;
 pub mod organizations;
+pub mod query_latency_stats;
 pub mod ratelimit;
 pub mod re_pattern;
 pub mod re_pattern_stream_map;
+pub mod recommendations;
 pub mod reports;
 pub mod search_job;
 pub mod search_queue;
@@ -52,6 +55,7 @@ pub mod users;
 pub async fn init() -> Result<(), anyhow::Error> {
     distinct_values::init().await?;
     short_urls::init().await?;
+    sessions::init().await?;
     Ok(())
 }
 
@@ -93,6 +97,13 @@ pub async fn down(steps: Option<u32>) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Returns what the pending migrations would do, without applying them.
+pub async fn plan() -> Result<Vec<MigrationPlan>, anyhow::Error> {
+    let client = ORM_CLIENT_DDL.get_or_init(connect_to_orm_ddl).await;
+    let plans = migration::plan_pending_migrations(client).await?;
+    Ok(plans)
+}
+
 pub async fn create_user_tables() -> Result<(), anyhow::Error> {
     organizations::create_table().await?;
     users::create_table().await?;
@@ -0,0 +1,248 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::time::now_micros;
+use hashbrown::HashMap;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use svix_ksuid::KsuidLike;
+
+use super::{
+    entity::query_latency_stats::{ActiveModel, Column, Entity},
+    get_lock,
+};
+use crate::{
+    db::{ORM_CLIENT, connect_to_orm},
+    errors,
+};
+
+/// The p95 latency of the queries sharing a fingerprint, as measured by one recommendation run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyStat {
+    pub fingerprint: String,
+    /// One of the queries of the fingerprint.
+    pub sql: String,
+    pub p95_micros: i64,
+    /// Number of queries of the fingerprint the p95 was computed from.
+    pub queries: i64,
+}
+
+/// Stores the latency stats measured by a recommendation run of an org.
+pub async fn add_run(org_id: &str, stats: &[LatencyStat]) -> Result<(), errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    add_run_models(client, org_id, stats, now_micros()).await
+}
+
+async fn add_run_models<C: ConnectionTrait>(
+    db: &C,
+    org_id: &str,
+    stats: &[LatencyStat],
+    now: i64,
+) -> Result<(), errors::Error> {
+    if stats.is_empty() {
+        return Ok(());
+    }
+    let records = stats.iter().map(|stat| ActiveModel {
+        id: Set(svix_ksuid::Ksuid::new(None, None).to_string()),
+        org_id: Set(org_id.to_string()),
+        fingerprint: Set(stat.fingerprint.clone()),
+        sql: Set(stat.sql.clone()),
+        p95_micros: Set(stat.p95_micros),
+        queries: Set(stat.queries),
+        created_at: Set(now),
+    });
+    Entity::insert_many(records).exec(db).await?;
+    Ok(())
+}
+
+/// Returns the most recently measured p95 latency of each of `fingerprints` of an org, in
+/// microseconds. Fingerprints that were never measured are left out.
+pub async fn latest_p95(
+    org_id: &str,
+    fingerprints: &[String],
+) -> Result<HashMap<String, i64>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    latest_p95_models(client, org_id, fingerprints).await
+}
+
+async fn latest_p95_models<C: ConnectionTrait>(
+    db: &C,
+    org_id: &str,
+    fingerprints: &[String],
+) -> Result<HashMap<String, i64>, errors::Error> {
+    if fingerprints.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let records = Entity::find()
+        .filter(Column::OrgId.eq(org_id))
+        .filter(Column::Fingerprint.is_in(fingerprints.iter().map(String::as_str)))
+        .order_by_desc(Column::CreatedAt)
+        .all(db)
+        .await?;
+    let mut latest = HashMap::new();
+    for record in records {
+        latest
+            .entry(record.fingerprint)
+            .or_insert(record.p95_micros);
+    }
+    Ok(latest)
+}
+
+/// Deletes the stats measured before `cutoff` and returns the number of rows removed.
+/// Returns the latest stats of each fingerprint measured since `since`, by org, of every org
+/// when `org_id` is None. Runs that only read the usage recorded since the previous run merge
+/// their stats into these.
+pub async fn latest_since(
+    org_id: Option<&str>,
+    since: i64,
+) -> Result<HashMap<String, Vec<LatencyStat>>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    latest_since_models(client, org_id, since).await
+}
+
+async fn latest_since_models<C: ConnectionTrait>(
+    db: &C,
+    org_id: Option<&str>,
+    since: i64,
+) -> Result<HashMap<String, Vec<LatencyStat>>, errors::Error> {
+    let mut query = Entity::find().filter(Column::CreatedAt.gte(since));
+    if let Some(org_id) = org_id {
+        query = query.filter(Column::OrgId.eq(org_id));
+    }
+    let records = query.order_by_desc(Column::CreatedAt).all(db).await?;
+    let mut latest: HashMap<String, HashMap<String, LatencyStat>> = HashMap::new();
+    for record in records {
+        latest
+            .entry(record.org_id)
+            .or_default()
+            .entry(record.fingerprint.clone())
+            .or_insert(LatencyStat {
+                fingerprint: record.fingerprint,
+                sql: record.sql,
+                p95_micros: record.p95_micros,
+                queries: record.queries,
+            });
+    }
+    Ok(latest
+        .into_iter()
+        .map(|(org_id, stats)| (org_id, stats.into_values().collect()))
+        .collect())
+}
+
+pub async fn delete_older_than(cutoff: i64) -> Result<u64, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let result = Entity::delete_many()
+        .filter(Column::CreatedAt.lt(cutoff))
+        .exec(client)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+
+    use super::*;
+    use crate::table::entity::query_latency_stats::Model;
+
+    fn record(fingerprint: &str, p95_micros: i64, created_at: i64) -> Model {
+        Model {
+            id: format!("{fingerprint}-{created_at}"),
+            org_id: "default".to_string(),
+            fingerprint: fingerprint.to_string(),
+            sql: "SELECT * FROM logs".to_string(),
+            p95_micros,
+            queries: 1,
+            created_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_latest_p95_keeps_the_newest_run() {
+        // rows come newest first
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![
+                record("a", 300, 20),
+                record("b", 50, 20),
+                record("a", 100, 10),
+            ]])
+            .into_connection();
+        let latest = latest_p95_models(&db, "default", &["a".to_string(), "b".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(latest.get("a"), Some(&300));
+        assert_eq!(latest.get("b"), Some(&50));
+
+        // nothing to look up, no query
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        assert!(
+            latest_p95_models(&db, "default", &[])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_latest_since_keeps_the_newest_run_by_org() {
+        let mut other_org = record("a", 70, 15);
+        other_org.org_id = "other".to_string();
+        // rows come newest first
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![
+                record("a", 300, 20),
+                other_org,
+                record("b", 50, 12),
+                record("a", 100, 10),
+            ]])
+            .into_connection();
+        let latest = latest_since_models(&db, None, 10).await.unwrap();
+        let mut default = latest["default"]
+            .iter()
+            .map(|stat| (stat.fingerprint.as_str(), stat.p95_micros))
+            .collect::<Vec<_>>();
+        default.sort();
+        assert_eq!(default, vec![("a", 300), ("b", 50)]);
+        assert_eq!(latest["other"].len(), 1);
+        assert_eq!(latest["other"][0].p95_micros, 70);
+    }
+
+    #[tokio::test]
+    async fn test_add_run_inserts_one_row_per_fingerprint() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult {
+                last_insert_id: 0,
+                rows_affected: 2,
+            }])
+            .into_connection();
+        let stats = ["a", "b"].map(|fingerprint| LatencyStat {
+            fingerprint: fingerprint.to_string(),
+            sql: "SELECT * FROM logs".to_string(),
+            p95_micros: 100,
+            queries: 3,
+        });
+        add_run_models(&db, "default", &stats, 10).await.unwrap();
+        let log = db.into_transaction_log();
+        assert_eq!(log.len(), 1);
+        assert!(format!("{log:?}").contains("INSERT INTO"));
+
+        // an empty run stores nothing
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        add_run_models(&db, "default", &[], 10).await.unwrap();
+        assert!(db.into_transaction_log().is_empty());
+    }
+}
@@ -0,0 +1,562 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use config::utils::time::now_micros;
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, Set, SqlErr,
+    sea_query::Expr,
+};
+use serde::{Deserialize, Serialize};
+use svix_ksuid::KsuidLike;
+
+use super::{
+    entity::recommendations::{ActiveModel, Column, Entity, Model},
+    get_lock,
+};
+use crate::{
+    db::{ORM_CLIENT, connect_to_orm},
+    errors,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RecommendationStatus {
+    Open,
+    Accepted,
+    Dismissed,
+    /// Accepted, and the stream settings it recommends have been applied.
+    Applied,
+    /// Closed by a recommendation run because it no longer applies, see
+    /// [`AUTO_RESOLVED_REASON_KEY`].
+    AutoResolved,
+}
+
+impl RecommendationStatus {
+    /// Returns true for the statuses that keep new recommendations for the same org, stream,
+    /// type and target field from being stored, see [`add`].
+    fn holds_dedupe_key(self) -> bool {
+        matches!(self, Self::Open | Self::Accepted | Self::Applied)
+    }
+
+    /// Returns true for the statuses a recommendation can still move on from with
+    /// [`update_status`]. Applied and auto resolved recommendations are closed for good.
+    pub fn can_change(self) -> bool {
+        CHANGEABLE_STATUSES.contains(&self)
+    }
+}
+
+/// Statuses that [`update_status`] moves a recommendation from.
+const CHANGEABLE_STATUSES: [RecommendationStatus; 3] = [
+    RecommendationStatus::Open,
+    RecommendationStatus::Accepted,
+    RecommendationStatus::Dismissed,
+];
+
+/// Outcome of [`update_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusChange {
+    Updated,
+    NotFound,
+    /// The recommendation has this status, which it can't move on from, see
+    /// [`RecommendationStatus::can_change`].
+    Closed(RecommendationStatus),
+    /// Another open, accepted or applied recommendation exists for the same org, stream, type
+    /// and target field, so this one can't be reopened or accepted.
+    Duplicate,
+}
+
+/// Payload key holding why a recommendation was auto resolved.
+pub const AUTO_RESOLVED_REASON_KEY: &str = "auto_resolved_reason";
+
+impl std::str::FromStr for RecommendationStatus {
+    type Err = errors::FromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Self::Open),
+            "accepted" => Ok(Self::Accepted),
+            "dismissed" => Ok(Self::Dismissed),
+            "applied" => Ok(Self::Applied),
+            "auto_resolved" => Ok(Self::AutoResolved),
+            _ => Err(errors::FromStrError {
+                value: s.to_owned(),
+                ty: "RecommendationStatus".to_owned(),
+            }),
+        }
+    }
+}
+
+/// Returns the status of a stored recommendation. The column is only written from
+/// [`RecommendationStatus`], so an unknown value means the row was changed by hand: it is logged
+/// and read as open, which lets the recommendation be reviewed again.
+fn stored_status(id: &str, status: &str) -> RecommendationStatus {
+    status.parse().unwrap_or_else(|e| {
+        log::warn!(
+            "[RECOMMENDATIONS] recommendation {id} has an invalid status, reading it as open: {e}"
+        );
+        RecommendationStatus::Open
+    })
+}
+
+impl std::fmt::Display for RecommendationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open => write!(f, "open"),
+            Self::Accepted => write!(f, "accepted"),
+            Self::Dismissed => write!(f, "dismissed"),
+            Self::Applied => write!(f, "applied"),
+            Self::AutoResolved => write!(f, "auto_resolved"),
+        }
+    }
+}
+
+/// A recommendation generated by the query recommendation engine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recommendation {
+    pub id: String,
+    pub org_id: String,
+    pub stream: String,
+    pub recommendation_type: String,
+    pub target_field: String,
+    pub payload: serde_json::Value,
+    pub status: RecommendationStatus,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Recommendation {
+    /// Returns how long the recommendation has existed at `now`, in microseconds.
+    pub fn age(&self, now: i64) -> i64 {
+        now - self.created_at
+    }
+}
+
+impl From<Model> for Recommendation {
+    fn from(value: Model) -> Self {
+        Self {
+            id: value.id,
+            org_id: value.org_id,
+            stream: value.stream,
+            recommendation_type: value.recommendation_type,
+            target_field: value.target_field,
+            payload: value.payload,
+            status: stored_status(&value.id, &value.status),
+            created_at: value.created_at,
+            updated_at: value.updated_at,
+        }
+    }
+}
+
+/// Stores a new open recommendation, unless the same org, stream, type and target field already
+/// has an open, accepted or applied recommendation, or one that was dismissed less than
+/// `dismiss_cooldown` microseconds ago.
+///
+/// Returns the id of the stored recommendation, or None if it was suppressed.
+pub async fn add(
+    org_id: &str,
+    stream: &str,
+    recommendation_type: &str,
+    target_field: &str,
+    payload: serde_json::Value,
+    dismiss_cooldown: i64,
+) -> Result<Option<String>, errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    add_model(
+        client,
+        org_id,
+        stream,
+        recommendation_type,
+        target_field,
+        payload,
+        dismiss_cooldown,
+        now_micros(),
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn add_model<C: ConnectionTrait>(
+    db: &C,
+    org_id: &str,
+    stream: &str,
+    recommendation_type: &str,
+    target_field: &str,
+    payload: serde_json::Value,
+    dismiss_cooldown: i64,
+    now: i64,
+) -> Result<Option<String>, errors::Error> {
+    let existing = Entity::find()
+        .filter(Column::OrgId.eq(org_id))
+        .filter(Column::Stream.eq(stream))
+        .filter(Column::RecommendationType.eq(recommendation_type))
+        .filter(Column::TargetField.eq(target_field))
+        .filter(
+            Condition::any()
+                .add(Column::Status.is_in([
+                    RecommendationStatus::Open.to_string(),
+                    RecommendationStatus::Accepted.to_string(),
+                    RecommendationStatus::Applied.to_string(),
+                ]))
+                .add(
+                    Condition::all()
+                        .add(Column::Status.eq(RecommendationStatus::Dismissed.to_string()))
+                        .add(Column::UpdatedAt.gt(now - dismiss_cooldown)),
+                ),
+        )
+        .one(db)
+        .await?;
+    if existing.is_some() {
+        return Ok(None);
+    }
+
+    let id = svix_ksuid::Ksuid::new(None, None).to_string();
+    let record = ActiveModel {
+        id: Set(id.clone()),
+        org_id: Set(org_id.to_string()),
+        stream: Set(stream.to_string()),
+        recommendation_type: Set(recommendation_type.to_string()),
+        target_field: Set(target_field.to_string()),
+        payload: Set(payload),
+        status: Set(RecommendationStatus::Open.to_string()),
+        created_at: Set(now),
+        updated_at: Set(now),
+        dedupe_key: Set(Some(dedupe_key(
+            org_id,
+            stream,
+            recommendation_type,
+            target_field,
+        ))),
+    };
+    match Entity::insert(record).exec(db).await {
+        Ok(_) => Ok(Some(id)),
+        // another node stored the same recommendation between the lookup and the insert
+        Err(e) => match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => Ok(None),
+            _ => Err(e.into()),
+        },
+    }
+}
+
+/// Returns the value of the unique dedupe key column of a recommendation that is open, accepted
+/// or applied. The key is a hash, as the columns it covers are too wide to index together on
+/// MySQL.
+fn dedupe_key(org_id: &str, stream: &str, recommendation_type: &str, target_field: &str) -> String {
+    sha256::digest(format!(
+        "{org_id}\0{stream}\0{recommendation_type}\0{target_field}"
+    ))
+}
+
+/// Lists the recommendations of an org, newest first, optionally filtered by status and type.
+pub async fn list_recommendations(
+    org_id: &str,
+    status: Option<RecommendationStatus>,
+    recommendation_type: Option<&str>,
+) -> Result<Vec<Recommendation>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let mut query = Entity::find().filter(Column::OrgId.eq(org_id));
+    if let Some(status) = status {
+        query = query.filter(Column::Status.eq(status.to_string()));
+    }
+    if let Some(recommendation_type) = recommendation_type {
+        query = query.filter(Column::RecommendationType.eq(recommendation_type));
+    }
+    let records = query
+        .order_by_desc(Column::CreatedAt)
+        .all(client)
+        .await?
+        .into_iter()
+        .map(Recommendation::from)
+        .collect();
+    Ok(records)
+}
+
+/// Gets a recommendation of an org by id.
+pub async fn get(org_id: &str, id: &str) -> Result<Option<Recommendation>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let record = Entity::find()
+        .filter(Column::OrgId.eq(org_id))
+        .filter(Column::Id.eq(id))
+        .one(client)
+        .await?;
+    Ok(record.map(Recommendation::from))
+}
+
+/// Sets the status of a recommendation that is open, accepted or dismissed.
+pub async fn update_status(
+    org_id: &str,
+    id: &str,
+    status: RecommendationStatus,
+) -> Result<StatusChange, errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    update_status_model(client, org_id, id, status, now_micros()).await
+}
+
+async fn update_status_model<C: ConnectionTrait>(
+    db: &C,
+    org_id: &str,
+    id: &str,
+    status: RecommendationStatus,
+    now: i64,
+) -> Result<StatusChange, errors::Error> {
+    let find = || {
+        Entity::find()
+            .filter(Column::OrgId.eq(org_id))
+            .filter(Column::Id.eq(id))
+    };
+    let Some(record) = find().one(db).await? else {
+        return Ok(StatusChange::NotFound);
+    };
+    let current = stored_status(&record.id, &record.status);
+    if !current.can_change() {
+        return Ok(StatusChange::Closed(current));
+    }
+    let dedupe_key = status.holds_dedupe_key().then(|| {
+        dedupe_key(
+            &record.org_id,
+            &record.stream,
+            &record.recommendation_type,
+            &record.target_field,
+        )
+    });
+    let result = Entity::update_many()
+        .col_expr(Column::Status, Expr::value(status.to_string()))
+        .col_expr(Column::DedupeKey, Expr::value(dedupe_key))
+        .col_expr(Column::UpdatedAt, Expr::value(now))
+        .filter(Column::OrgId.eq(org_id))
+        .filter(Column::Id.eq(id))
+        .filter(Column::Status.is_in(CHANGEABLE_STATUSES.map(|status| status.to_string())))
+        .exec(db)
+        .await;
+    match result {
+        Ok(result) if result.rows_affected > 0 => Ok(StatusChange::Updated),
+        // it was applied or auto resolved, or deleted, since it was read
+        Ok(_) => Ok(find()
+            .one(db)
+            .await?
+            .map_or(StatusChange::NotFound, |record| {
+                StatusChange::Closed(stored_status(&record.id, &record.status))
+            })),
+        Err(e) => match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => Ok(StatusChange::Duplicate),
+            _ => Err(e.into()),
+        },
+    }
+}
+
+/// Marks an open recommendation as auto resolved, recording `reason` in its payload. Returns false
+/// if the recommendation does not exist or is no longer open, e.g. because it was accepted in the
+/// meantime.
+pub async fn auto_resolve(org_id: &str, id: &str, reason: &str) -> Result<bool, errors::Error> {
+    // make sure only one client is writing to the database(only for sqlite)
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let Some(record) = Entity::find()
+        .filter(Column::OrgId.eq(org_id))
+        .filter(Column::Id.eq(id))
+        .filter(Column::Status.eq(RecommendationStatus::Open.to_string()))
+        .one(client)
+        .await?
+    else {
+        return Ok(false);
+    };
+    let result = Entity::update_many()
+        .col_expr(
+            Column::Status,
+            Expr::value(RecommendationStatus::AutoResolved.to_string()),
+        )
+        .col_expr(
+            Column::Payload,
+            Expr::value(with_auto_resolved_reason(record.payload, reason)),
+        )
+        .col_expr(Column::DedupeKey, Expr::value(Option::<String>::None))
+        .col_expr(Column::UpdatedAt, Expr::value(now_micros()))
+        .filter(Column::OrgId.eq(org_id))
+        .filter(Column::Id.eq(id))
+        .filter(Column::Status.eq(RecommendationStatus::Open.to_string()))
+        .exec(client)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+fn with_auto_resolved_reason(mut payload: serde_json::Value, reason: &str) -> serde_json::Value {
+    if !payload.is_object() {
+        payload = serde_json::json!({});
+    }
+    payload[AUTO_RESOLVED_REASON_KEY] = serde_json::Value::String(reason.to_string());
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{Database, DatabaseConnection, Schema};
+
+    use super::*;
+
+    async fn test_db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let backend = db.get_database_backend();
+        let create = Schema::new(backend).create_table_from_entity(Entity);
+        db.execute(backend.build(&create)).await.unwrap();
+        db
+    }
+
+    async fn add_at(db: &DatabaseConnection, target_field: &str, now: i64) -> Option<String> {
+        add_model(
+            db,
+            "default",
+            "logs",
+            "index",
+            target_field,
+            serde_json::json!({}),
+            100,
+            now,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        for status in [
+            RecommendationStatus::Open,
+            RecommendationStatus::Accepted,
+            RecommendationStatus::Dismissed,
+            RecommendationStatus::Applied,
+            RecommendationStatus::AutoResolved,
+        ] {
+            assert_eq!(
+                status.to_string().parse::<RecommendationStatus>().unwrap(),
+                status
+            );
+        }
+        // unknown statuses are an error, and stored ones are read as open
+        assert!("closed".parse::<RecommendationStatus>().is_err());
+        assert_eq!(stored_status("id", "closed"), RecommendationStatus::Open);
+        assert_eq!(
+            stored_status("id", "applied"),
+            RecommendationStatus::Applied
+        );
+    }
+
+    #[test]
+    fn test_with_auto_resolved_reason() {
+        let payload = with_auto_resolved_reason(serde_json::json!({"sql": "SELECT 1"}), "gone");
+        assert_eq!(
+            payload,
+            serde_json::json!({"sql": "SELECT 1", "auto_resolved_reason": "gone"})
+        );
+        let payload = with_auto_resolved_reason(serde_json::Value::Null, "gone");
+        assert_eq!(payload, serde_json::json!({"auto_resolved_reason": "gone"}));
+    }
+
+    #[tokio::test]
+    async fn test_add_dedupes() {
+        let db = test_db().await;
+        let id = add_at(&db, "host", 1_000).await.unwrap();
+        assert!(add_at(&db, "host", 1_000).await.is_none());
+        assert!(add_at(&db, "pod", 1_000).await.is_some());
+
+        // dismissed recommendations are suppressed for the cooldown only
+        update_status_model(&db, "default", &id, RecommendationStatus::Dismissed, 2_000)
+            .await
+            .unwrap();
+        assert!(add_at(&db, "host", 2_050).await.is_none());
+        assert!(add_at(&db, "host", 2_200).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_update_status_transitions() {
+        let db = test_db().await;
+        let id = add_at(&db, "host", 1_000).await.unwrap();
+        let update = |status| update_status_model(&db, "default", &id, status, 2_000);
+        assert_eq!(
+            update(RecommendationStatus::Dismissed).await.unwrap(),
+            StatusChange::Updated
+        );
+        assert_eq!(
+            update(RecommendationStatus::Accepted).await.unwrap(),
+            StatusChange::Updated
+        );
+        assert_eq!(
+            update(RecommendationStatus::Applied).await.unwrap(),
+            StatusChange::Updated
+        );
+        // applied recommendations are closed for good
+        assert_eq!(
+            update(RecommendationStatus::Open).await.unwrap(),
+            StatusChange::Closed(RecommendationStatus::Applied)
+        );
+        assert_eq!(
+            update_status_model(&db, "default", "missing", RecommendationStatus::Open, 2_000)
+                .await
+                .unwrap(),
+            StatusChange::NotFound
+        );
+
+        // a dismissed recommendation can't be reopened over a newer one for the same field
+        let dismissed = add_at(&db, "pod", 1_000).await.unwrap();
+        update_status_model(
+            &db,
+            "default",
+            &dismissed,
+            RecommendationStatus::Dismissed,
+            2_000,
+        )
+        .await
+        .unwrap();
+        add_at(&db, "pod", 3_000).await.unwrap();
+        assert_eq!(
+            update_status_model(
+                &db,
+                "default",
+                &dismissed,
+                RecommendationStatus::Open,
+                3_000
+            )
+            .await
+            .unwrap(),
+            StatusChange::Duplicate
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_rejects_concurrent_duplicate() {
+        let db = test_db().await;
+        add_at(&db, "host", 1_000).await.unwrap();
+        // a second node that already did its lookup inserts the same open recommendation
+        let duplicate = ActiveModel {
+            id: Set("duplicate".to_string()),
+            org_id: Set("default".to_string()),
+            stream: Set("logs".to_string()),
+            recommendation_type: Set("index".to_string()),
+            target_field: Set("host".to_string()),
+            payload: Set(serde_json::json!({})),
+            status: Set(RecommendationStatus::Open.to_string()),
+            created_at: Set(1_000),
+            updated_at: Set(1_000),
+            dedupe_key: Set(Some(dedupe_key("default", "logs", "index", "host"))),
+        };
+        let err = Entity::insert(duplicate).exec(&db).await.unwrap_err();
+        assert!(matches!(
+            err.sql_err(),
+            Some(SqlErr::UniqueConstraintViolation(_))
+        ));
+        assert_eq!(Entity::find().all(&db).await.unwrap().len(), 1);
+    }
+}
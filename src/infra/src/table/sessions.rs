@@ -13,41 +13,231 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set, sea_query::OnConflict};
+use config::utils::{json, time::now_micros};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait, FromQueryResult,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set, SqlErr, TransactionTrait,
+    sea_query::{Expr, OnConflict},
+};
+use serde::{Deserialize, Serialize};
 
-use super::entity::sessions::{ActiveModel, Column, Entity, Model};
+use super::{
+    cipher,
+    entity::sessions::{ActiveModel, Column, Entity, Model},
+    get_lock,
+};
 use crate::{
     db::{ORM_CLIENT, connect_to_orm},
-    errors,
+    errors::{self, CreateSessionError, DbError},
 };
 
-/// Gets a session by session_id
+// Prefix of access tokens that are stored encrypted with the master encryption key
+const ENCRYPTED_TOKEN_PREFIX: &str = "enc:";
+
+pub async fn init() -> Result<(), errors::Error> {
+    if !cipher::has_master_key() {
+        log::warn!(
+            "[SESSION] No master encryption key configured, session access tokens are stored in plaintext"
+        );
+    }
+    Ok(())
+}
+
+/// Gets a session by session_id.
+///
+/// Expired sessions are treated as not found and are deleted lazily.
 pub async fn get(session_id: &str) -> Result<Option<Model>, errors::Error> {
     let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let Some(session) = Entity::find()
+        .filter(Column::SessionId.eq(session_id))
+        .one(client)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    if is_expired(&session, now_micros()) {
+        log::debug!("[SESSION] session expired, deleting: {session_id}");
+        if let Err(e) = delete(session_id).await {
+            log::warn!("[SESSION] Failed to delete expired session {session_id}: {e}");
+        }
+        return Ok(None);
+    }
+
+    Ok(Some(decrypt_model(session)?))
+}
+
+/// Gets a session by its access token, using the index on the token hash.
+///
+/// Expired sessions are treated as not found. When several sessions hold the same token, the
+/// most recently created one is returned.
+pub async fn get_session_by_token(access_token: &str) -> Result<Option<Model>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    get_session_by_token_model(client, access_token, now_micros()).await
+}
+
+async fn get_session_by_token_model<C: ConnectionTrait>(
+    db: &C,
+    access_token: &str,
+    now: i64,
+) -> Result<Option<Model>, errors::Error> {
     let session = Entity::find()
+        .filter(Column::TokenHash.eq(token_hash(access_token)))
+        .filter(not_expired(now))
+        .order_by_desc(Column::CreatedAt)
+        .one(db)
+        .await?;
+    session.map(decrypt_model).transpose()
+}
+
+/// Stores a session, encrypting the access token at rest.
+pub async fn store_session(session_id: &str, access_token: &str) -> Result<(), errors::Error> {
+    set(session_id, access_token, None, None).await
+}
+
+/// Gets the decrypted access token of a session.
+pub async fn get_session_token(session_id: &str) -> Result<Option<String>, errors::Error> {
+    Ok(get(session_id).await?.map(|session| session.access_token))
+}
+
+/// Session details that are safe to show to the session owner. Never includes the access token.
+#[derive(FromQueryResult, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub created_at: i64,
+    pub last_used_at: i64,
+    pub user_agent: Option<String>,
+}
+
+/// Attributes of a session, stored as JSON in the metadata column so new attributes don't need a
+/// schema change. Every attribute is optional, and unknown ones are ignored when reading.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login_method: Option<String>,
+}
+
+/// Gets the metadata of a session. Returns None if the session does not exist or has no
+/// metadata.
+pub async fn get_session_metadata(
+    session_id: &str,
+) -> Result<Option<SessionMetadata>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let metadata: Option<Option<String>> = Entity::find()
+        .select_only()
+        .column(Column::Metadata)
         .filter(Column::SessionId.eq(session_id))
+        .into_tuple()
         .one(client)
         .await?;
-    Ok(session)
+    Ok(metadata
+        .flatten()
+        .map(|metadata| json::from_str(&metadata))
+        .transpose()?)
 }
 
-/// Creates or updates a session atomically using upsert
-pub async fn set(session_id: &str, access_token: &str) -> Result<(), errors::Error> {
+/// Replaces the metadata of a session. Only the metadata column is written.
+///
+/// Returns true if the session exists.
+pub async fn set_session_metadata(
+    session_id: &str,
+    metadata: &SessionMetadata,
+) -> Result<bool, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let result = Entity::update_many()
+        .col_expr(Column::Metadata, Expr::value(json::to_string(metadata)?))
+        .filter(Column::SessionId.eq(session_id))
+        .exec(client)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// How a session was created.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSource {
+    /// Created before the source of sessions was recorded.
+    Legacy,
+    /// Login with a user name and password in the UI.
+    Login,
+    /// Exchange of an API token for a session.
+    ApiToken,
+    /// Login through an external identity provider, e.g. OIDC or SAML.
+    Sso,
+}
+
+impl std::str::FromStr for SessionSource {
+    type Err = errors::FromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(Self::Legacy),
+            "login" => Ok(Self::Login),
+            "api_token" => Ok(Self::ApiToken),
+            "sso" => Ok(Self::Sso),
+            _ => Err(errors::FromStrError {
+                value: s.to_owned(),
+                ty: "SessionSource".to_owned(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for SessionSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Legacy => write!(f, "legacy"),
+            Self::Login => write!(f, "login"),
+            Self::ApiToken => write!(f, "api_token"),
+            Self::Sso => write!(f, "sso"),
+        }
+    }
+}
+
+/// Creates or updates a session atomically using upsert. Updating a session replaces its token,
+/// user and user agent, and marks it as just used.
+pub async fn set(
+    session_id: &str,
+    access_token: &str,
+    user_id: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<(), errors::Error> {
     let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
     let now = chrono::Utc::now().timestamp_micros();
 
     // Use atomic upsert to avoid race conditions
     let active_model = ActiveModel {
         session_id: Set(session_id.to_string()),
-        access_token: Set(access_token.to_string()),
+        access_token: Set(encrypt_token(access_token)?),
         created_at: Set(now),
         updated_at: Set(now),
+        expires_at: Set(expires_at(now)),
+        last_used_at: Set(now),
+        user_id: Set(user_id.map(|v| v.to_string())),
+        user_agent: Set(user_agent.map(|v| v.to_string())),
+        token_hash: Set(Some(token_hash(access_token))),
+        metadata: Set(None),
+        source: Set(None),
     };
 
     Entity::insert(active_model)
         .on_conflict(
             OnConflict::column(Column::SessionId)
-                .update_columns([Column::AccessToken, Column::UpdatedAt])
+                .update_columns([
+                    Column::AccessToken,
+                    Column::TokenHash,
+                    Column::UpdatedAt,
+                    Column::ExpiresAt,
+                    Column::LastUsedAt,
+                    Column::UserId,
+                    Column::UserAgent,
+                ])
                 .to_owned(),
         )
         .exec(client)
@@ -56,6 +246,70 @@ pub async fn set(session_id: &str, access_token: &str) -> Result<(), errors::Err
     Ok(())
 }
 
+/// Creates a session, encrypting the access token at rest. Unlike [`set`], an existing session
+/// is never overwritten: creating a session_id that is already taken fails with
+/// [`CreateSessionError::SessionExists`].
+///
+/// Returns the stored session, with the access token decrypted.
+pub async fn create_session(
+    session_id: &str,
+    access_token: &str,
+    user_id: Option<&str>,
+    source: SessionSource,
+) -> Result<Model, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    create_session_model(
+        client,
+        session_id,
+        access_token,
+        user_id,
+        source,
+        now_micros(),
+    )
+    .await
+}
+
+async fn create_session_model<C: ConnectionTrait>(
+    db: &C,
+    session_id: &str,
+    access_token: &str,
+    user_id: Option<&str>,
+    source: SessionSource,
+    now: i64,
+) -> Result<Model, errors::Error> {
+    if access_token.is_empty() {
+        return Err(DbError::CreateSession(CreateSessionError::EmptyAccessToken).into());
+    }
+    let session_exists =
+        || DbError::CreateSession(CreateSessionError::SessionExists(session_id.to_string()));
+    if Entity::find_by_id(session_id).one(db).await?.is_some() {
+        return Err(session_exists().into());
+    }
+
+    let active_model = ActiveModel {
+        session_id: Set(session_id.to_string()),
+        access_token: Set(encrypt_token(access_token)?),
+        created_at: Set(now),
+        updated_at: Set(now),
+        expires_at: Set(expires_at(now)),
+        last_used_at: Set(now),
+        user_id: Set(user_id.map(|v| v.to_string())),
+        user_agent: Set(None),
+        token_hash: Set(Some(token_hash(access_token))),
+        metadata: Set(None),
+        source: Set(Some(source.to_string())),
+    };
+    match active_model.insert(db).await {
+        Ok(session) => decrypt_model(session),
+        // another node created the same session between the lookup and the insert
+        Err(e) => match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => Err(session_exists().into()),
+            _ => Err(e.into()),
+        },
+    }
+}
+
 /// Deletes a session by session_id
 pub async fn delete(session_id: &str) -> Result<(), errors::Error> {
     let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
@@ -66,9 +320,531 @@ pub async fn delete(session_id: &str) -> Result<(), errors::Error> {
     Ok(())
 }
 
-/// Lists all sessions
+/// Sets last_used_at of a session to `now`, unless it was already updated within the last
+/// `min_interval` microseconds. Only the last_used_at column is written.
+///
+/// Returns true if the session was updated.
+pub async fn touch(session_id: &str, now: i64, min_interval: i64) -> Result<bool, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let result = Entity::update_many()
+        .col_expr(Column::LastUsedAt, Expr::value(now))
+        .filter(Column::SessionId.eq(session_id))
+        .filter(Column::LastUsedAt.lt(now - min_interval))
+        .exec(client)
+        .await?;
+    Ok(result.rows_affected > 0)
+}
+
+/// Revokes a session by session_id in a transaction.
+///
+/// Returns true if a session was actually removed.
+pub async fn revoke(session_id: &str) -> Result<bool, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let txn = client.begin().await?;
+    match Entity::delete_many()
+        .filter(Column::SessionId.eq(session_id))
+        .exec(&txn)
+        .await
+    {
+        Ok(result) => {
+            txn.commit().await?;
+            Ok(result.rows_affected > 0)
+        }
+        Err(e) => {
+            txn.rollback().await?;
+            Err(e.into())
+        }
+    }
+}
+
+/// Revokes all sessions holding the given access token in a transaction, using the index on the
+/// token hash.
+///
+/// Returns the session_ids that were removed.
+pub async fn revoke_by_access_token(access_token: &str) -> Result<Vec<String>, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let txn = client.begin().await?;
+    let session_ids: Vec<String> = match Entity::find()
+        .select_only()
+        .column(Column::SessionId)
+        .filter(Column::TokenHash.eq(token_hash(access_token)))
+        .into_tuple()
+        .all(&txn)
+        .await
+    {
+        Ok(ids) => ids,
+        Err(e) => {
+            txn.rollback().await?;
+            return Err(e.into());
+        }
+    };
+    if session_ids.is_empty() {
+        txn.commit().await?;
+        return Ok(session_ids);
+    }
+
+    match Entity::delete_many()
+        .filter(Column::SessionId.is_in(session_ids.clone()))
+        .exec(&txn)
+        .await
+    {
+        Ok(_) => {
+            txn.commit().await?;
+            Ok(session_ids)
+        }
+        Err(e) => {
+            txn.rollback().await?;
+            Err(e.into())
+        }
+    }
+}
+
+/// Revokes the oldest sessions of a user, by created_at, so that at most `max_sessions` remain.
+/// Counting and deleting happen in one transaction.
+///
+/// Returns the session_ids that were removed.
+pub async fn revoke_overflow_for_user(
+    user_id: &str,
+    max_sessions: usize,
+) -> Result<Vec<String>, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let txn = client.begin().await?;
+    // newest sessions first, everything past `max_sessions` is overflow
+    let session_ids: Vec<String> = match Entity::find()
+        .select_only()
+        .column(Column::SessionId)
+        .filter(Column::UserId.eq(user_id))
+        .order_by_desc(Column::CreatedAt)
+        .offset(max_sessions as u64)
+        .into_tuple()
+        .all(&txn)
+        .await
+    {
+        Ok(ids) => ids,
+        Err(e) => {
+            txn.rollback().await?;
+            return Err(e.into());
+        }
+    };
+    if session_ids.is_empty() {
+        txn.commit().await?;
+        return Ok(session_ids);
+    }
+
+    match Entity::delete_many()
+        .filter(Column::SessionId.is_in(session_ids.clone()))
+        .exec(&txn)
+        .await
+    {
+        Ok(_) => {
+            txn.commit().await?;
+            Ok(session_ids)
+        }
+        Err(e) => {
+            txn.rollback().await?;
+            Err(e.into())
+        }
+    }
+}
+
+/// Deletes up to `limit` sessions that expired before `now`, or that were last used before
+/// `idle_cutoff`, and returns their session_ids. Callers purge in pages to avoid holding long
+/// locks on large tables.
+pub async fn delete_stale(
+    now: i64,
+    idle_cutoff: Option<i64>,
+    limit: u64,
+) -> Result<Vec<String>, errors::Error> {
+    let _lock = get_lock().await;
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let session_ids: Vec<String> = Entity::find()
+        .select_only()
+        .column(Column::SessionId)
+        .filter(stale(now, idle_cutoff))
+        .limit(limit)
+        .into_tuple()
+        .all(client)
+        .await?;
+    if session_ids.is_empty() {
+        return Ok(session_ids);
+    }
+
+    Entity::delete_many()
+        .filter(Column::SessionId.is_in(session_ids.clone()))
+        .exec(client)
+        .await?;
+    Ok(session_ids)
+}
+
+/// Condition matching the sessions that expired before `now`, or that were last used before
+/// `idle_cutoff`. Sessions are touched on use, so last_used_at is what tells idle ones apart.
+fn stale(now: i64, idle_cutoff: Option<i64>) -> Condition {
+    let expired = Condition::all()
+        .add(Column::ExpiresAt.gt(0))
+        .add(Column::ExpiresAt.lt(now));
+    let condition = Condition::any().add(expired);
+    match idle_cutoff {
+        Some(idle_cutoff) => condition.add(Column::LastUsedAt.lt(idle_cutoff)),
+        None => condition,
+    }
+}
+
+/// Lists all sessions that have not expired
 pub async fn list() -> Result<Vec<Model>, errors::Error> {
     let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
-    let sessions = Entity::find().all(client).await?;
+    let sessions = Entity::find()
+        .filter(not_expired(now_micros()))
+        .all(client)
+        .await?;
+    sessions.into_iter().map(decrypt_model).collect()
+}
+
+/// Counts the sessions that have not expired.
+pub async fn count_active_sessions() -> Result<u64, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let count = Entity::find()
+        .filter(not_expired(now_micros()))
+        .count(client)
+        .await?;
+    Ok(count)
+}
+
+/// Counts the sessions that have not expired per user. Sessions without a user are left out.
+pub async fn count_active_sessions_by_user() -> Result<Vec<(String, i64)>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let counts = Entity::find()
+        .select_only()
+        .column(Column::UserId)
+        .column_as(Column::SessionId.count(), "count")
+        .filter(Column::UserId.is_not_null())
+        .filter(not_expired(now_micros()))
+        .group_by(Column::UserId)
+        .into_tuple()
+        .all(client)
+        .await?;
+    Ok(counts)
+}
+
+/// Condition matching the sessions that have not expired at `now`.
+fn not_expired(now: i64) -> Condition {
+    Condition::any()
+        .add(Column::ExpiresAt.eq(0))
+        .add(Column::ExpiresAt.gte(now))
+}
+
+/// Lists the sessions of a user that have not expired, most recently used first
+pub async fn list_for_user(user_id: &str) -> Result<Vec<SessionInfo>, errors::Error> {
+    let client = ORM_CLIENT.get_or_init(connect_to_orm).await;
+    let sessions = Entity::find()
+        .select_only()
+        .column(Column::SessionId)
+        .column(Column::CreatedAt)
+        .column(Column::LastUsedAt)
+        .column(Column::UserAgent)
+        .filter(Column::UserId.eq(user_id))
+        .filter(not_expired(now_micros()))
+        .order_by_desc(Column::LastUsedAt)
+        .into_model::<SessionInfo>()
+        .all(client)
+        .await?;
     Ok(sessions)
 }
+
+/// Encrypts an access token with the master encryption key. The token is stored as-is if no
+/// master key is configured.
+pub(crate) fn encrypt_token(access_token: &str) -> Result<String, errors::Error> {
+    if !cipher::has_master_key() {
+        return Ok(access_token.to_string());
+    }
+    let encrypted = cipher::encrypt_with_master_key(access_token)?;
+    Ok(format!("{ENCRYPTED_TOKEN_PREFIX}{encrypted}"))
+}
+
+/// Decrypts an access token stored by [`encrypt_token`]. Tokens without the encryption prefix
+/// are plaintext and returned as-is.
+pub(crate) fn decrypt_token(stored: &str) -> Result<String, errors::Error> {
+    match stored.strip_prefix(ENCRYPTED_TOKEN_PREFIX) {
+        Some(_) if !cipher::has_master_key() => Err(errors::Error::Message(
+            "session access token is encrypted but no master encryption key is configured".into(),
+        )),
+        Some(encrypted) => cipher::decrypt_with_master_key(encrypted),
+        None => Ok(stored.to_string()),
+    }
+}
+
+/// Returns the hex encoded SHA-256 of a plaintext access token, which is what the token_hash
+/// column holds.
+pub(crate) fn token_hash(access_token: &str) -> String {
+    sha256::digest(access_token)
+}
+
+fn decrypt_model(mut session: Model) -> Result<Model, errors::Error> {
+    session.access_token = decrypt_token(&session.access_token)?;
+    Ok(session)
+}
+
+/// Returns the expiry timestamp for a session created at `now`, based on the configured session
+/// ttl. Returns 0 (never expires) when the ttl is disabled.
+pub fn expires_at(now: i64) -> i64 {
+    let ttl = config::get_config().auth.session_ttl;
+    if ttl <= 0 {
+        0
+    } else {
+        now.saturating_add(ttl.saturating_mul(1_000_000))
+    }
+}
+
+/// Returns true if the session has an expiry and it is earlier than `now`.
+pub fn is_expired(session: &Model, now: i64) -> bool {
+    expired_at(session.expires_at, now)
+}
+
+fn expired_at(expires_at: i64, now: i64) -> bool {
+    expires_at > 0 && expires_at < now
+}
+
+/// The access token of a session as cached in memory, along with when the session expires so
+/// the cache doesn't outlive it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedSession {
+    pub access_token: String,
+    /// In microseconds, 0 if the session never expires.
+    pub expires_at: i64,
+}
+
+impl CachedSession {
+    pub fn is_expired(&self, now: i64) -> bool {
+        expired_at(self.expires_at, now)
+    }
+}
+
+impl From<Model> for CachedSession {
+    fn from(session: Model) -> Self {
+        Self {
+            access_token: session.access_token,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{DatabaseBackend, MockDatabase, QueryTrait};
+
+    use super::*;
+
+    fn session(expires_at: i64) -> Model {
+        Model {
+            session_id: "session".to_string(),
+            access_token: "token".to_string(),
+            created_at: 0,
+            updated_at: 0,
+            expires_at,
+            last_used_at: 0,
+            user_id: None,
+            user_agent: None,
+            token_hash: Some(token_hash("token")),
+            metadata: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_is_expired() {
+        assert!(!is_expired(&session(0), 100));
+        assert!(!is_expired(&session(200), 100));
+        assert!(!is_expired(&session(100), 100));
+        assert!(is_expired(&session(50), 100));
+    }
+
+    #[test]
+    fn test_stale() {
+        let sql = |idle_cutoff| {
+            Entity::find()
+                .select_only()
+                .column(Column::SessionId)
+                .filter(stale(100, idle_cutoff))
+                .build(DatabaseBackend::Postgres)
+                .to_string()
+        };
+        let expired = r#""sessions"."expires_at" > 0 AND "sessions"."expires_at" < 100"#;
+        let idle = r#"OR "sessions"."last_used_at" < 50"#;
+        let with_idle = sql(Some(50));
+        assert!(with_idle.contains(expired), "{with_idle}");
+        assert!(with_idle.contains(idle), "{with_idle}");
+        assert!(!with_idle.contains("updated_at"), "{with_idle}");
+        let without_idle = sql(None);
+        assert!(without_idle.contains(expired), "{without_idle}");
+        assert!(!without_idle.contains("last_used_at"), "{without_idle}");
+    }
+
+    #[test]
+    fn test_cached_session_is_expired() {
+        let cached = CachedSession::from(session(50));
+        assert_eq!(cached.access_token, "token");
+        assert!(cached.is_expired(100));
+        assert!(!cached.is_expired(50));
+        assert!(!CachedSession::from(session(0)).is_expired(100));
+    }
+
+    #[tokio::test]
+    async fn test_create_session() {
+        let now = 1_700_000_000_000_000;
+        let stored = Model {
+            created_at: now,
+            updated_at: now,
+            expires_at: expires_at(now),
+            last_used_at: now,
+            user_id: Some("user@example.com".to_string()),
+            source: Some("sso".to_string()),
+            ..session(expires_at(now))
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Model>::new()])
+            .append_query_results([vec![stored.clone()]])
+            .into_connection();
+        let created = create_session_model(
+            &db,
+            "session",
+            "token",
+            Some("user@example.com"),
+            SessionSource::Sso,
+            now,
+        )
+        .await
+        .unwrap();
+        assert_eq!(created, stored);
+        assert_eq!(
+            created
+                .source
+                .as_deref()
+                .map(str::parse::<SessionSource>)
+                .transpose()
+                .unwrap(),
+            Some(SessionSource::Sso)
+        );
+        // one lookup and one insert, which stores the source
+        let log = db.into_transaction_log();
+        assert_eq!(log.len(), 2);
+        assert!(format!("{:?}", log[1]).contains("\"sso\""));
+    }
+
+    #[tokio::test]
+    async fn test_create_duplicate_session() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![session(0)]])
+            .into_connection();
+        let err = create_session_model(&db, "session", "token", None, SessionSource::Login, 100)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            errors::Error::DbError(DbError::CreateSession(CreateSessionError::SessionExists(id)))
+                if id == "session"
+        ));
+        // the existing session is not overwritten
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_session_with_empty_token() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let err = create_session_model(&db, "session", "", None, SessionSource::Login, 100)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            errors::Error::DbError(DbError::CreateSession(CreateSessionError::EmptyAccessToken))
+        ));
+        assert!(db.into_transaction_log().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_session_by_token() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![session(0)]])
+            .append_query_results([Vec::<Model>::new()])
+            .into_connection();
+        let found = get_session_by_token_model(&db, "token", 100).await.unwrap();
+        assert_eq!(found, Some(session(0)));
+        let missing = get_session_by_token_model(&db, "other", 100).await.unwrap();
+        assert_eq!(missing, None);
+
+        // looked up by hash, the plaintext token is never sent to the database
+        let log = format!("{:?}", db.into_transaction_log());
+        assert!(log.contains(&token_hash("token")));
+        assert!(log.contains(&token_hash("other")));
+        assert!(!log.contains("\"other\""));
+    }
+
+    #[test]
+    fn test_token_hash() {
+        let hash = token_hash("token");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, token_hash("token"));
+        assert_ne!(hash, token_hash("other"));
+    }
+
+    #[test]
+    fn test_session_metadata_round_trip() {
+        let metadata = SessionMetadata {
+            ip: Some("10.0.0.1".to_string()),
+            device: Some("Firefox on Linux".to_string()),
+            login_method: Some("sso".to_string()),
+        };
+        let stored = json::to_string(&metadata).unwrap();
+        assert_eq!(
+            json::from_str::<SessionMetadata>(&stored).unwrap(),
+            metadata
+        );
+
+        // unset attributes are left out
+        let metadata = SessionMetadata {
+            ip: Some("10.0.0.1".to_string()),
+            ..Default::default()
+        };
+        let stored = json::to_string(&metadata).unwrap();
+        assert_eq!(stored, r#"{"ip":"10.0.0.1"}"#);
+        assert_eq!(
+            json::from_str::<SessionMetadata>(&stored).unwrap(),
+            metadata
+        );
+    }
+
+    #[test]
+    fn test_session_metadata_compatibility() {
+        // metadata written by newer versions may have attributes this one does not know
+        let metadata: SessionMetadata =
+            json::from_str(r#"{"login_method":"password","country":"NZ"}"#).unwrap();
+        assert_eq!(metadata.login_method.as_deref(), Some("password"));
+        assert_eq!(metadata.ip, None);
+        assert_eq!(
+            json::from_str::<SessionMetadata>("{}").unwrap(),
+            SessionMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_session_source() {
+        for source in [
+            SessionSource::Legacy,
+            SessionSource::Login,
+            SessionSource::ApiToken,
+            SessionSource::Sso,
+        ] {
+            assert_eq!(source.to_string().parse::<SessionSource>().unwrap(), source);
+        }
+        // unknown sources are an error instead of passing for legacy sessions
+        assert!("unknown".parse::<SessionSource>().is_err());
+        assert!("".parse::<SessionSource>().is_err());
+    }
+
+    #[test]
+    fn test_plaintext_token() {
+        assert_eq!(decrypt_token("token").unwrap(), "token");
+    }
+}
@@ -19,6 +19,7 @@ use config::{
     get_config,
     meta::{cluster::Role, stream::StreamType},
     metrics::{self},
+    spawn_pausable_job,
     utils::async_file::scan_files,
 };
 use hashbrown::HashMap;
@@ -58,6 +59,63 @@ pub async fn run() -> Result<(), anyhow::Error> {
     }
 }
 
+/// Periodically refreshes the active session metrics. They count the sessions in the database,
+/// so they are refreshed less often than the other metrics, and only on compactor nodes.
+pub fn run_session_metrics() {
+    if !config::cluster::LOCAL_NODE.is_compactor() {
+        return;
+    }
+
+    spawn_pausable_job!(
+        "session_metrics",
+        get_config().auth.session_metrics_interval,
+        {
+            if let Err(e) = update_session_metrics().await {
+                log::error!("Error update session metrics: {e}");
+            }
+        }
+    );
+}
+
+async fn update_session_metrics() -> Result<(), anyhow::Error> {
+    let total = infra::table::sessions::count_active_sessions().await?;
+    metrics::META_NUM_ACTIVE_SESSIONS_TOTAL
+        .with_label_values::<&str>(&[])
+        .set(total as i64);
+
+    let user_sessions: HashMap<String, i64> =
+        infra::table::sessions::count_active_sessions_by_user()
+            .await?
+            .into_iter()
+            .collect();
+    let org_users = ORG_USERS
+        .iter()
+        .map(|user| (user.org_id.clone(), user.email.clone()))
+        .collect::<Vec<_>>();
+    metrics::META_NUM_ACTIVE_SESSIONS.reset();
+    for (org_id, count) in sessions_per_org(&user_sessions, &org_users) {
+        metrics::META_NUM_ACTIVE_SESSIONS
+            .with_label_values(&[org_id.as_str()])
+            .set(count);
+    }
+    Ok(())
+}
+
+/// Sums the sessions of the users of each org. A session counts for every org its user belongs
+/// to, so the per-org counts can add up to more than the total.
+fn sessions_per_org(
+    user_sessions: &HashMap<String, i64>,
+    org_users: &[(String, String)],
+) -> HashMap<String, i64> {
+    let mut counts = HashMap::new();
+    for (org_id, email) in org_users {
+        if let Some(count) = user_sessions.get(email) {
+            *counts.entry(org_id.clone()).or_insert(0) += count;
+        }
+    }
+    counts
+}
+
 async fn load_query_cache_limit_bytes() -> Result<(), anyhow::Error> {
     let cfg = get_config();
     metrics::QUERY_MEMORY_CACHE_LIMIT_BYTES
@@ -281,3 +339,28 @@ async fn update_parquet_metadata_cache_metrics() -> Result<(), anyhow::Error> {
         .set(mem_size as i64);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_per_org() {
+        let user_sessions = HashMap::from([
+            ("alice@example.com".to_string(), 2),
+            ("bob@example.com".to_string(), 1),
+        ]);
+        let org_users = [
+            ("default", "alice@example.com"),
+            ("default", "bob@example.com"),
+            ("other", "alice@example.com"),
+            ("other", "carol@example.com"),
+            ("empty", "carol@example.com"),
+        ]
+        .map(|(org_id, email)| (org_id.to_string(), email.to_string()));
+        let counts = sessions_per_org(&user_sessions, &org_users);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["default"], 3);
+        assert_eq!(counts["other"], 2);
+    }
+}
@@ -50,6 +50,7 @@ mod promql;
 mod promql_self_consume;
 #[cfg(feature = "enterprise")]
 mod service_graph;
+mod session_cleanup;
 mod stats;
 
 pub use file_downloader::{download_from_node, queue_download};
@@ -310,6 +311,7 @@ pub async fn init() -> Result<(), anyhow::Error> {
     #[cfg(feature = "enterprise")]
     tokio::task::spawn(service_graph::run());
     tokio::task::spawn(metrics::run());
+    metrics::run_session_metrics();
     let _ = promql::run();
     tokio::task::spawn(alert_manager::run());
     #[cfg(feature = "enterprise")]
@@ -318,6 +320,7 @@ pub async fn init() -> Result<(), anyhow::Error> {
     #[cfg(feature = "enterprise")]
     tokio::task::spawn(pipeline::run());
     pipeline_error_cleanup::run();
+    session_cleanup::run();
 
     if LOCAL_NODE.is_compactor() {
         tokio::task::spawn(file_list_dump::run());
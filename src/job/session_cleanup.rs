@@ -0,0 +1,103 @@
+// Copyright 2025 OpenObserve Inc.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time::{Duration, Instant};
+
+use config::{cluster::LOCAL_NODE, get_config, spawn_pausable_job, utils::time::now_micros};
+
+use crate::{common::infra::cluster, service::db::session};
+
+/// Number of sessions deleted per page.
+const PAGE_SIZE: u64 = 1000;
+
+/// A page slower than this is considered a sign of DB load, and the job waits
+/// as long as the page took before deleting the next one.
+const SLOW_PAGE_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Runs the periodic session cleanup job.
+///
+/// This job removes sessions that have expired, or that have not been used within
+/// the configured retention period, so the sessions table does not grow unbounded.
+///
+/// Only runs on compactor nodes, and only the compactor with the smallest UUID
+/// performs the cleanup.
+pub fn run() {
+    if !LOCAL_NODE.is_compactor() {
+        log::debug!("[SESSION_CLEANUP] Not running on compactor node, skipping");
+        return;
+    }
+
+    spawn_pausable_job!(
+        "session_cleanup",
+        get_config().auth.session_cleanup_interval,
+        {
+            if !is_leader().await {
+                log::debug!("[SESSION_CLEANUP] Not leader, skipping cleanup");
+                continue;
+            }
+
+            let now = now_micros();
+            // with retention disabled only the expired sessions are removed
+            let retention_days = get_config().auth.session_retention_days;
+            let idle_cutoff = (retention_days > 0)
+                .then(|| now.saturating_sub(retention_days.saturating_mul(24 * 3600 * 1_000_000)));
+            match purge_stale_sessions(now, idle_cutoff).await {
+                Ok(deleted) => {
+                    log::info!("[SESSION_CLEANUP] Deleted {deleted} expired or idle session(s)");
+                }
+                Err(e) => {
+                    log::error!("[SESSION_CLEANUP] Failed to cleanup sessions: {e}");
+                }
+            }
+        }
+    );
+}
+
+/// Deletes the sessions that expired before `now` or were last used before
+/// `idle_cutoff` page by page, backing off between pages when the database is
+/// slow to respond. The deleted sessions are evicted from the caches of the cluster.
+async fn purge_stale_sessions(now: i64, idle_cutoff: Option<i64>) -> Result<u64, anyhow::Error> {
+    let mut total = 0;
+    loop {
+        let start = Instant::now();
+        let deleted = session::delete_stale(now, idle_cutoff, PAGE_SIZE)
+            .await?
+            .len() as u64;
+        total += deleted;
+        if deleted < PAGE_SIZE {
+            return Ok(total);
+        }
+
+        let took = start.elapsed();
+        if took > SLOW_PAGE_THRESHOLD {
+            log::warn!(
+                "[SESSION_CLEANUP] Deleting {deleted} sessions took {}ms, backing off",
+                took.as_millis()
+            );
+            tokio::time::sleep(took).await;
+        }
+    }
+}
+
+async fn is_leader() -> bool {
+    match cluster::get_cached_online_compactor_nodes().await {
+        Some(mut nodes) if !nodes.is_empty() => {
+            nodes.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+            nodes[0].uuid == LOCAL_NODE.uuid
+        }
+        // If we can't get cached nodes, assume single node and run cleanup
+        _ => true,
+    }
+}
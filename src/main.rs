@@ -434,46 +434,64 @@ async fn main() -> Result<(), anyhow::Error> {
     }
 
     // Check for query recommendations trigger and create one
-    match db::scheduler::list(Some(QueryRecommendations)).await {
-        Ok(list) if list.len() == 1 => {}
-        _ => {
-            let _ = db::scheduler::delete(
-                META_ORG_ID,
-                TriggerModule::QueryRecommendations,
-                "QueryRecommendations",
-            )
-            .await
-            .inspect_err(|e| {
-                log::error!("Error while purging recommendations triggers. e={:?}", e);
-            });
-            let now = SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_micros() as i64;
-            // Get the next minute on the clock (e.g., if now is 10:37:25am, next_minute is
-            // 10:38:00am)
-            let minute_micros = 60 * 1_000_000; // 60 seconds in microseconds
-            let next_minute = (now / minute_micros + 1) * minute_micros;
-            let trigger = Trigger {
-                org: META_ORG_ID.to_string(),
-                module: TriggerModule::QueryRecommendations,
-                module_key: "QueryRecommendations".to_string(),
-                next_run_at: next_minute,
-                status: TriggerStatus::Waiting,
-                start_time: Some(next_minute),
-                end_time: None,
-                retries: 3,
-                ..Default::default()
-            };
-            let _ = db::scheduler::push(trigger).await.inspect_err(|e| {
-                log::error!(
-                    "Failed to setup the initial trigger for recommendations. e={:?}",
-                    e
-                )
-            });
-            log::info!("[QUERY_RECOMMENDATIONS] Setup the initial trigger.");
+    log::info!(
+        "[QUERY_RECOMMENDATIONS] Query recommendations are {}",
+        if cfg.recommendations.enabled {
+            "enabled"
+        } else {
+            "disabled"
         }
-    };
+    );
+    if cfg.recommendations.enabled {
+        match db::scheduler::list(Some(QueryRecommendations)).await {
+            Ok(list) if list.len() == 1 => {}
+            _ => {
+                let _ = db::scheduler::delete(
+                    META_ORG_ID,
+                    TriggerModule::QueryRecommendations,
+                    "QueryRecommendations",
+                )
+                .await
+                .inspect_err(|e| {
+                    log::error!("Error while purging recommendations triggers. e={:?}", e);
+                });
+                let now = SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .expect("Time went backwards")
+                    .as_micros() as i64;
+                // Get the next minute on the clock (e.g., if now is 10:37:25am, next_minute is
+                // 10:38:00am)
+                let minute_micros = 60 * 1_000_000; // 60 seconds in microseconds
+                let next_minute = (now / minute_micros + 1) * minute_micros;
+                let trigger = Trigger {
+                    org: META_ORG_ID.to_string(),
+                    module: TriggerModule::QueryRecommendations,
+                    module_key: "QueryRecommendations".to_string(),
+                    next_run_at: next_minute,
+                    status: TriggerStatus::Waiting,
+                    start_time: Some(next_minute),
+                    end_time: None,
+                    retries: 3,
+                    ..Default::default()
+                };
+                let _ = db::scheduler::push(trigger).await.inspect_err(|e| {
+                    log::error!(
+                        "Failed to setup the initial trigger for recommendations. e={:?}",
+                        e
+                    )
+                });
+                log::info!("[QUERY_RECOMMENDATIONS] Setup the initial trigger.");
+            }
+        };
+    } else {
+        // a trigger left from when recommendations were enabled must not run them
+        let _ = db::scheduler::delete(
+            META_ORG_ID,
+            TriggerModule::QueryRecommendations,
+            "QueryRecommendations",
+        )
+        .await;
+    }
 
     // init http server
     if !cfg.common.tracing_enabled && cfg.common.tracing_search_enabled {
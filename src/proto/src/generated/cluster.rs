@@ -1065,6 +1065,9 @@ pub struct SearchQuery {
     /// Backend converts this to SamplingConfig for internal node communication
     #[prost(double, optional, tag = "18")]
     pub sampling_ratio: ::core::option::Option<f64>,
+    /// Maximum number of hits counted when track_total_hits is set, 0 counts them all
+    #[prost(int64, tag = "19")]
+    pub track_total_hits_limit: i64,
 }
 /// Sampling configuration for query performance optimization
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -1101,6 +1104,8 @@ pub struct SearchEventContext {
     pub dashboard_folder_name: ::core::option::Option<::prost::alloc::string::String>,
     #[prost(string, optional, tag = "8")]
     pub alert_name: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "9")]
+    pub system_source: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Clone, PartialEq, Eq, Hash, ::prost::Message)]
 pub struct QueryStatus {
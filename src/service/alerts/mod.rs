@@ -25,7 +25,7 @@ use config::{
             TriggerCondition, TriggerEvalResults,
         },
         cluster::RoleGroup,
-        search::{SearchEventContext, SearchEventType, SqlQuery},
+        search::{SearchEventContext, SearchEventType, SqlQuery, TrackTotalHits},
         sql::resolve_stream_names,
         stream::StreamType,
     },
@@ -391,7 +391,7 @@ impl QueryConditionExt for QueryCondition {
                     end_time,
                     quick_mode: false,
                     query_type: "".to_string(),
-                    track_total_hits: false,
+                    track_total_hits: TrackTotalHits::Off,
                     action_id: None,
                     uses_zo_fn: false,
                     query_fn: if self.vrl_function.is_some() {
@@ -423,6 +423,7 @@ impl QueryConditionExt for QueryCondition {
                 use_cache: false,
                 clear_cache: false,
                 local_mode: None,
+                search_priority: Default::default(),
             };
             log::debug!(
                 "evaluate_scheduled trace_id: {trace_id}, begin to call SearchService::search, {req:?}"
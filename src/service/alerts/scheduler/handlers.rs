@@ -40,8 +40,6 @@ use infra::{
     db::{ORM_CLIENT, connect_to_orm},
     scheduler::get_scheduler_max_retries,
 };
-#[cfg(feature = "enterprise")]
-use o2_enterprise::enterprise::recommendations::service::QueryRecommendationService;
 use proto::cluster_rpc;
 
 #[cfg(feature = "enterprise")]
@@ -978,48 +976,115 @@ async fn handle_query_recommendations_triggers(
     trace_id: &str,
     trigger: db::scheduler::Trigger,
 ) -> Result<(), anyhow::Error> {
-    use std::{
-        sync::Arc,
-        time::{SystemTime, UNIX_EPOCH},
-    };
-
     use config::meta::triggers::TriggerStatus;
 
+    use crate::service::alerts::scheduler::query_optimization_recommendation::{
+        BreakerState, RecommendationTriggerData, backoff_delay, next_run_at, recommendation_service,
+    };
+
     let cfg = get_config();
-    let query_recommendation_analysis_interval = cfg.limit.query_recommendation_analysis_interval;
-    let next_run_at = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Basic relative time")
-        .as_micros() as i64
-        + query_recommendation_analysis_interval * 1_000_000;
-
-    log::info!("[QUERY_RECOMMENDATIONS] Generating Query Recommendations. trace_id={trace_id}");
-
-    let query_recommendation_service = QueryRecommendationService {
-        ctx: Arc::new(QueryOptimizerContext),
-        query_recommendation_analysis_interval: cfg.limit.query_recommendation_analysis_interval,
-        query_recommendation_duration: cfg.limit.query_recommendation_duration,
-        query_recommendation_top_k: cfg.limit.query_recommendation_top_k,
+    let mut trigger_data = RecommendationTriggerData::from_trigger_data(&trigger.data);
+    let ctx = QueryOptimizerContext::default().with_usage_watermark(trigger_data.usage_watermark);
+    let breaker = trigger_data.breaker_state(now_micros(), cfg.recommendations.breaker_cooldown);
+
+    let error = if cfg.recommendations.enabled && breaker == BreakerState::Open {
+        log::warn!(
+            "[QUERY_RECOMMENDATIONS] Circuit breaker is open after {} failed run(s) in a row, skipping run. trace_id={trace_id}",
+            trigger_data.consecutive_failures
+        );
+        None
+    } else if cfg.recommendations.enabled {
+        if breaker == BreakerState::HalfOpen {
+            log::info!(
+                "[QUERY_RECOMMENDATIONS] Circuit breaker is half open, testing whether runs succeed again. trace_id={trace_id}"
+            );
+        }
+        log::info!("[QUERY_RECOMMENDATIONS] Generating Query Recommendations. trace_id={trace_id}");
+
+        let query_recommendation_service =
+            recommendation_service(ctx.clone(), cfg.recommendations.duration);
+
+        let error = query_recommendation_service
+            .run()
+            .await
+            .inspect_err(|e| {
+                log::error!(
+                    "[QUERY_RECOMMENDATIONS] Recommendation service stopped with an error: Error={:?}",
+                    e
+                );
+            })
+            .inspect(|_| {
+                log::info!("[QUERY_RECOMMENDATIONS] Recommendation job completed successfully. trace_id={trace_id}");
+            })
+            .err();
+        match ctx.track_latency_trends(cfg.recommendations.duration).await {
+            // the next run only reads the usage recorded after this one
+            Ok(_) => trigger_data.usage_watermark = ctx.usage_watermark(),
+            Err(e) => log::error!(
+                "[QUERY_RECOMMENDATIONS] Failed to track query latency trends: {e}. trace_id={trace_id}"
+            ),
+        }
+        error
+    } else {
+        log::debug!("[QUERY_RECOMMENDATIONS] Query recommendations are disabled, skipping run");
+        None
     };
 
-    let result = query_recommendation_service
-        .run()
-        .await
-        .inspect_err(|e| {
-            log::error!(
-                "[QUERY_RECOMMENDATIONS] Recommendation service stopped with an error: Error={:?}",
-                e
+    // Back off after failed runs, so a struggling search backend isn't hit at every interval, and
+    // pause the runs altogether once too many failed in a row. A skipped run changes neither.
+    if !(cfg.recommendations.enabled && breaker == BreakerState::Open) {
+        trigger_data.record_run(
+            error.is_some() || ctx.timed_out(),
+            now_micros(),
+            cfg.recommendations.breaker_threshold,
+        );
+    }
+    let now = now_micros();
+    let new_breaker = trigger_data.breaker_state(now, cfg.recommendations.breaker_cooldown);
+    config::metrics::QUERY_RECOMMENDATIONS_BREAKER_STATE
+        .with_label_values::<&str>(&[])
+        .set(new_breaker.as_metric());
+    let next_run = match (
+        new_breaker,
+        trigger_data.breaker_closes_at(cfg.recommendations.breaker_cooldown),
+    ) {
+        (BreakerState::Open, Some(closes_at)) => {
+            if breaker != BreakerState::Open {
+                log::error!(
+                    "[QUERY_RECOMMENDATIONS] Circuit breaker opened after {} failed run(s) in a row, pausing the runs for {}s. trace_id={trace_id}",
+                    trigger_data.consecutive_failures,
+                    cfg.recommendations.breaker_cooldown
+                );
+            }
+            closes_at
+        }
+        _ => {
+            if new_breaker == BreakerState::Closed && breaker == BreakerState::HalfOpen {
+                log::info!(
+                    "[QUERY_RECOMMENDATIONS] Circuit breaker closed, the run succeeded again. trace_id={trace_id}"
+                );
+            }
+            let delay = backoff_delay(
+                cfg.recommendations.interval,
+                trigger_data.consecutive_failures,
+                cfg.recommendations.max_backoff,
             );
-        })
-        .inspect(|_| {
-            log::info!("[QUERY_RECOMMENDATIONS] Recommendation job completed successfully. trace_id={trace_id}");
-        });
+            if trigger_data.consecutive_failures > 0 {
+                log::warn!(
+                    "[QUERY_RECOMMENDATIONS] {} failed run(s) in a row, next run in {delay}s. trace_id={trace_id}",
+                    trigger_data.consecutive_failures
+                );
+            }
+            next_run_at(now, delay, cfg.recommendations.jitter_percent)
+        }
+    };
 
     // Always queue the next run, regardless of success or failure
     let new_trigger = db::scheduler::Trigger {
         status: TriggerStatus::Waiting,
         retries: 3,
-        next_run_at,
+        next_run_at: next_run,
+        data: trigger_data.to_json_string(),
         ..trigger
     };
 
@@ -1034,7 +1099,7 @@ async fn handle_query_recommendations_triggers(
 
     // If there was an error during generation, log it but don't prevent the next run from being
     // queued
-    if let Err(e) = result {
+    if let Some(e) = error {
         log::error!(
             "[QUERY_RECOMMENDATIONS] Query Recommendations Job operation encountered an error: e={:?}",
             e
@@ -13,27 +13,696 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{pin::Pin, sync::Arc};
+use std::{
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
-use config::{META_ORG_ID, get_config, meta::stream::StreamType, spawn_pausable_job};
+use arrow_schema::{DataType, Field, Schema};
+use config::{
+    META_ORG_ID, RwHashMap, TIMESTAMP_COL_NAME,
+    cluster::LOCAL_NODE,
+    get_config,
+    meta::{
+        cluster::Node,
+        search::{QueryCostEstimate, Request, Response, SearchPriority},
+        sql::{TableReferenceExt, resolve_stream_names_with_type},
+        stream::{StreamPartition, StreamStats, StreamType, UpdateStreamSettings},
+    },
+    spawn_pausable_job,
+    utils::{hash::Sum64, sql::equality_filter_columns, time::now_micros},
+};
+use hashbrown::HashMap;
+use infra::table::{
+    query_latency_stats::{self, LatencyStat},
+    recommendations::{self, Recommendation, RecommendationStatus, StatusChange},
+};
 use o2_enterprise::enterprise::recommendations::{
     meta::{OptimiserRecommendation, Stream},
     service::{QueryRecommendationEngine, QueryRecommendationService},
 };
+use once_cell::sync::Lazy;
 use proto::cluster_rpc::{IngestionData, IngestionRequest, IngestionResponse, IngestionType};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, oneshot};
 
-use crate::service::{
-    db::organization, ingestion::ingestion_service, search::search, stream::get_streams,
+use crate::{
+    common::infra::cluster,
+    service::{
+        db::{self, organization},
+        ingestion::ingestion_service,
+        search::{
+            cancel_query,
+            cardinality::{self, DistinctCache, DistinctOptions, FieldDistinct},
+            estimate, prepare_internal_search, search,
+            utils::validate_system_query,
+        },
+        stream::{get_streams, update_stream_settings},
+    },
 };
 
-#[derive(Clone)]
-pub struct QueryOptimizerContext;
+/// User that recommendation searches run as. These are system queries: the user never needs to
+/// exist in the user table, and the searches bypass the HTTP layer that applies user rate limits.
+pub const RECO_SERVICE_USER: &str = "query_reco_user";
+
+/// System source set in the search event context of recommendation searches.
+pub const RECOMMENDATION_SEARCH_SOURCE: &str = "query_recommendations";
+
+/// Smallest time window that recommendations are computed over.
+const MIN_RECOMMENDATION_WINDOW: i64 = 60 * 1_000_000; // microseconds
+
+/// The on-demand recommendation run in progress of each org.
+static ON_DEMAND_RUNS: Lazy<RwHashMap<String, OnDemandRun>> = Lazy::new(Default::default);
+
+/// Id of the next on-demand recommendation run.
+static ON_DEMAND_RUN_ID: AtomicU64 = AtomicU64::new(0);
+
+/// An on-demand recommendation run in progress, which a newer run of the same org cancels.
+struct OnDemandRun {
+    id: u64,
+    org_id: String,
+    cancel: oneshot::Sender<()>,
+    report: Arc<Mutex<RecommendationRunReport>>,
+}
+
+/// When each org last started a manual run, in microseconds. Scheduled runs aren't tracked.
+static LAST_MANUAL_RUN: Lazy<RwHashMap<String, i64>> = Lazy::new(Default::default);
+
+/// Responses of recent recommendation searches, so repeated on-demand runs over the same window
+/// don't scan the usage streams again. Kept apart from the result cache of user searches.
+static SEARCH_CACHE: Lazy<RwHashMap<String, SearchCacheEntry>> = Lazy::new(Default::default);
+
+/// Most responses kept in [`SEARCH_CACHE`]. The oldest ones are evicted to make room.
+const SEARCH_CACHE_MAX_ENTRIES: usize = 1024;
+
+#[derive(Clone, Debug)]
+struct SearchCacheEntry {
+    response: Response,
+    timestamp: i64,
+}
+
+/// A search run by the engine while generating recommendations.
+#[derive(Clone, Debug, Serialize)]
+pub struct RecommendationQueryTrace {
+    pub trace_id: String,
+    pub sql: String,
+    pub start_time: i64,
+    pub end_time: i64,
+}
+
+/// What an on-demand run produced, and the searches it ran to get there.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RecommendationRunReport {
+    pub recommendations: Vec<serde_json::Value>,
+    pub queries: Vec<RecommendationQueryTrace>,
+    pub latency_trends: Vec<LatencyTrend>,
+    pub partition_keys: Vec<PartitionKeyRecommendation>,
+}
+
+/// Reads the data the recommendation engine analyses: the streams of an org and the results of
+/// searches. [`ClusterSearcher`] reads them from the cluster, other implementations let the engine
+/// run against a fixed dataset, e.g. in tests.
+pub trait Searcher: Send + Sync {
+    /// Lists the streams of an org, with their stats and settings, of all types when
+    /// `stream_type` is None.
+    fn list_streams(
+        &self,
+        org_id: String,
+        stream_type: Option<StreamType>,
+    ) -> Pin<Box<dyn Future<Output = Vec<crate::common::meta::stream::Stream>> + Send>>;
+
+    /// Runs an engine search, already prepared as a system search, of streams of `stream_type`.
+    fn search(
+        &self,
+        trace_id: String,
+        org_id: String,
+        stream_type: StreamType,
+        request: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, anyhow::Error>> + Send>>;
+
+    /// Returns the distinct count, and example values when `options` asks for them, of each
+    /// field of a stream between `start_time` and `end_time`. Fields that are not part of the
+    /// stream are omitted.
+    #[allow(clippy::too_many_arguments)]
+    fn distinct_values(
+        &self,
+        org_id: String,
+        stream_type: StreamType,
+        stream_name: String,
+        field_names: Vec<String>,
+        start_time: i64,
+        end_time: i64,
+        options: DistinctOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<HashMap<String, FieldDistinct>, anyhow::Error>> + Send>>;
+
+    /// Estimates what a search of streams of `stream_type` would scan, from stream metadata,
+    /// without running it.
+    fn estimate(
+        &self,
+        org_id: String,
+        stream_type: StreamType,
+        request: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<QueryCostEstimate, anyhow::Error>> + Send>>;
+}
+
+/// Reads streams and search results from the cluster, searching as [`RECO_SERVICE_USER`].
+pub struct ClusterSearcher;
+
+impl Searcher for ClusterSearcher {
+    fn list_streams(
+        &self,
+        org_id: String,
+        stream_type: Option<StreamType>,
+    ) -> Pin<Box<dyn Future<Output = Vec<crate::common::meta::stream::Stream>> + Send>> {
+        Box::pin(async move { get_streams(&org_id, stream_type, true, None).await })
+    }
+
+    fn search(
+        &self,
+        trace_id: String,
+        org_id: String,
+        stream_type: StreamType,
+        request: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, anyhow::Error>> + Send>> {
+        Box::pin(async move { run_search(&trace_id, &org_id, stream_type, request).await })
+    }
+
+    fn distinct_values(
+        &self,
+        org_id: String,
+        stream_type: StreamType,
+        stream_name: String,
+        field_names: Vec<String>,
+        start_time: i64,
+        end_time: i64,
+        options: DistinctOptions,
+    ) -> Pin<Box<dyn Future<Output = Result<HashMap<String, FieldDistinct>, anyhow::Error>> + Send>>
+    {
+        Box::pin(async move {
+            cardinality::field_distinct_values(
+                &org_id,
+                &stream_name,
+                stream_type,
+                &field_names,
+                start_time,
+                end_time,
+                options,
+            )
+            .await
+            .map_err(Into::into)
+        })
+    }
+
+    fn estimate(
+        &self,
+        org_id: String,
+        stream_type: StreamType,
+        request: Request,
+    ) -> Pin<Box<dyn Future<Output = Result<QueryCostEstimate, anyhow::Error>> + Send>> {
+        Box::pin(async move {
+            estimate(&org_id, stream_type, &request)
+                .await
+                .map_err(Into::into)
+        })
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct QueryOptimizerContext {
+    /// Restricts the run to a single org. All orgs are analysed when None.
+    org_id: Option<String>,
+    /// Collects the generated recommendations and the searches run, in addition to ingesting
+    /// the recommendations.
+    report: Option<Arc<Mutex<RecommendationRunReport>>>,
+    /// Runs the full pipeline without ingesting the recommendations.
+    dry_run: bool,
+    /// Restricts the run to a single stream. All streams are analysed when None.
+    stream_filter: Option<(StreamType, String)>,
+    /// Set when a search of the run timed out, which the scheduler counts as a failed run.
+    timed_out: Arc<AtomicBool>,
+    /// Where the streams and search results come from, the cluster when None.
+    searcher: Option<Arc<dyn Searcher>>,
+    /// Time window analysed by the run, in microseconds, set by [`recommendation_service`]. The
+    /// distinct values of the recommended fields are computed over it.
+    window: Option<(i64, i64)>,
+    /// End of the usage window read by the last latency tracking of the context, in
+    /// microseconds, 0 before the first. The next one only reads the usage recorded after it.
+    usage_watermark: Arc<AtomicI64>,
+    /// Responses of the searches of the run, by their exact [`search_cache_key`], so a query the
+    /// engine repeats during the run, e.g. the distinct count of a field several of the
+    /// analysed queries filter on, is only run once. Unlike [`SEARCH_CACHE`] it doesn't depend
+    /// on `cache_ttl`, and lives as long as the run.
+    searches: Arc<RwHashMap<String, Response>>,
+    /// Distinct values of the recommended fields computed during the run, keyed by org, stream,
+    /// field and window, so each field is only computed once per run.
+    distinct_cache: DistinctCache,
+}
+
+impl QueryOptimizerContext {
+    /// Reads the streams and search results of the run from `searcher` instead of the cluster.
+    pub fn with_searcher(mut self, searcher: Arc<dyn Searcher>) -> Self {
+        self.searcher = Some(searcher);
+        self
+    }
+
+    fn searcher(&self) -> Arc<dyn Searcher> {
+        self.searcher
+            .clone()
+            .unwrap_or_else(|| Arc::new(ClusterSearcher))
+    }
+
+    /// Returns true if a search of the run timed out.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+
+    /// Starts the latency tracking from the usage `watermark` of an earlier run, see
+    /// [`Self::usage_watermark`]. The whole window is read when None.
+    pub fn with_usage_watermark(self, watermark: Option<i64>) -> Self {
+        self.usage_watermark
+            .store(watermark.unwrap_or_default(), Ordering::Relaxed);
+        self
+    }
+
+    /// Returns the end of the usage window read by the last latency tracking, None before the
+    /// first.
+    pub fn usage_watermark(&self) -> Option<i64> {
+        Some(self.usage_watermark.load(Ordering::Relaxed)).filter(|watermark| *watermark > 0)
+    }
+
+    /// Adds to the payload of each recommendation the `distinct_count` of its target field over
+    /// the window of the run, and the `sample_values` of it that `options` asks for, see
+    /// [`distinct_options`]. The fields of a stream are computed together, and the streams
+    /// concurrently, at most `ZO_QUERY_RECOMMENDATION_DISTINCT_CONCURRENCY` at a time, as low
+    /// priority internal searches. Values the engine already set are kept, and recommendations
+    /// without a key, or whose stream can't be read, are left as they are.
+    async fn attach_distinct_values(
+        &self,
+        recommendations: &mut [serde_json::Value],
+        options: DistinctOptions,
+    ) {
+        let Some((start_time, end_time)) = self.window else {
+            return;
+        };
+        let mut streams: HashMap<(String, StreamType, String), Vec<String>> = HashMap::new();
+        for recommendation in recommendations.iter() {
+            let Some(key) = recommendation_key(recommendation, self.org_id.as_deref()) else {
+                continue;
+            };
+            let fields = streams
+                .entry((
+                    key.org_id,
+                    recommendation_stream_type(recommendation),
+                    key.stream,
+                ))
+                .or_default();
+            if !fields.contains(&key.target_field) {
+                fields.push(key.target_field);
+            }
+        }
+
+        let ctx = self.clone();
+        let computed = bounded_join(
+            streams.into_iter().collect(),
+            get_config().recommendations.distinct_concurrency,
+            move |((org_id, stream_type, stream_name), field_names)| {
+                let ctx = ctx.clone();
+                async move {
+                    ctx.distinct_values(
+                        &org_id,
+                        stream_type,
+                        &stream_name,
+                        &field_names,
+                        (start_time, end_time),
+                        options,
+                    )
+                    .await
+                }
+            },
+        )
+        .await;
+        let mut distinct = HashMap::new();
+        for ((stream, _), values) in computed {
+            match values {
+                Ok(values) => {
+                    distinct.insert(stream, values);
+                }
+                Err(e) => {
+                    let (org_id, stream_type, stream_name) = stream;
+                    log::warn!(
+                        "[QUERY_RECOMMENDATIONS] org={org_id} failed to compute the distinct values of {stream_type}/{stream_name}: {e}"
+                    )
+                }
+            }
+        }
+
+        for recommendation in recommendations.iter_mut() {
+            let Some(key) = recommendation_key(recommendation, self.org_id.as_deref()) else {
+                continue;
+            };
+            let stream_type = recommendation_stream_type(recommendation);
+            if let Some(values) = distinct
+                .get(&(key.org_id, stream_type, key.stream))
+                .and_then(|values| values.get(&key.target_field))
+            {
+                attach_distinct(recommendation, values);
+            }
+        }
+    }
+
+    /// Returns the distinct values of the fields of a stream over `window`, only computing the
+    /// ones that aren't in the [`DistinctCache`] of the run yet.
+    async fn distinct_values(
+        &self,
+        org_id: &str,
+        stream_type: StreamType,
+        stream_name: &str,
+        field_names: &[String],
+        (start_time, end_time): (i64, i64),
+        options: DistinctOptions,
+    ) -> Result<HashMap<String, FieldDistinct>, anyhow::Error> {
+        let (mut values, missing) = self.distinct_cache.lookup(
+            org_id,
+            stream_name,
+            stream_type,
+            field_names,
+            start_time,
+            end_time,
+            options,
+        );
+        if missing.is_empty() {
+            return Ok(values);
+        }
+        let computed = self
+            .searcher()
+            .distinct_values(
+                org_id.to_string(),
+                stream_type,
+                stream_name.to_string(),
+                missing,
+                start_time,
+                end_time,
+                options,
+            )
+            .await?;
+        self.distinct_cache.insert(
+            org_id,
+            stream_name,
+            stream_type,
+            &computed,
+            start_time,
+            end_time,
+            options,
+        );
+        values.extend(computed);
+        Ok(values)
+    }
+
+    /// Measures the p95 latency per query fingerprint over the last `duration` microseconds, and
+    /// compares it with the previous run. Unless this is a dry run, the measurements are stored
+    /// for the next run to compare with, and the regressions as
+    /// [`LATENCY_REGRESSION_RECOMMENDATION`]s, at most `max_recommendations_per_run` of them,
+    /// ranked by [`latency_impact`]. Older regressions that no longer apply are auto resolved.
+    ///
+    /// Once the context has a usage watermark, only the usage recorded after it is read, see
+    /// [`usage_window`]. The stats of the new usage are merged into the latest stats stored for
+    /// the rest of the window, and the watermark moves to the end of the window read.
+    pub async fn track_latency_trends(
+        &self,
+        duration: i64,
+    ) -> Result<Vec<LatencyTrend>, anyhow::Error> {
+        let cfg = get_config();
+        let threshold_percent = cfg.recommendations.latency_regression_percent;
+        if threshold_percent == 0 {
+            return Ok(vec![]);
+        }
+        let now = now_micros();
+        let (window_start, _) = recommendation_window(now, duration);
+        let (start_time, end_time) = usage_window(now, duration, self.usage_watermark());
+        let usage = self.read_usage_stats(start_time, end_time).await?;
+
+        let dashboard_tags = usage
+            .iter()
+            .filter(|stat| stat.dashboard_id.is_some())
+            .map(|stat| {
+                (
+                    stat.fingerprint.clone(),
+                    (stat.dashboard_id.clone(), stat.panel_id.clone()),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let mut new_stats = latency_stats(usage);
+        let mut stored_stats = if start_time > window_start {
+            query_latency_stats::latest_since(self.org_id.as_deref(), window_start).await?
+        } else {
+            HashMap::new()
+        };
+        for org_id in new_stats.keys() {
+            stored_stats.entry(org_id.clone()).or_default();
+        }
+        let mut trends = vec![];
+        for (org_id, stored) in stored_stats {
+            let stats = new_stats.remove(&org_id).unwrap_or_default();
+            let current = merge_latency_stats(stored, &stats);
+            let fingerprints = current
+                .iter()
+                .map(|stat| stat.fingerprint.clone())
+                .collect::<Vec<_>>();
+            let previous = query_latency_stats::latest_p95(&org_id, &fingerprints).await?;
+            let mut org_trends = latency_trends(&org_id, &current, &previous, threshold_percent);
+            for trend in org_trends.iter_mut() {
+                if let Some((dashboard_id, panel_id)) = dashboard_tags.get(&trend.fingerprint) {
+                    trend.dashboard_id = dashboard_id.clone();
+                    trend.panel_id = panel_id.clone();
+                }
+            }
+            if !self.dry_run {
+                query_latency_stats::add_run(&org_id, &stats).await?;
+            }
+            trends.extend(org_trends);
+        }
+        self.estimate_regression_costs(&mut trends, window_start, now)
+            .await;
+        if !self.dry_run {
+            let regressions = trends.iter().filter(|trend| trend.regressed).cloned();
+            let dismiss_cooldown = cfg.recommendations.dismiss_cooldown * 1_000_000;
+            for trend in top_by_impact(
+                regressions,
+                regression_rank,
+                cfg.recommendations.max_recommendations_per_run,
+            ) {
+                add_latency_regression(&trend, dismiss_cooldown).await?;
+            }
+            query_latency_stats::delete_older_than(now_micros() - LATENCY_STATS_RETENTION).await?;
+            if cfg.recommendations.auto_resolve_after > 0 {
+                self.auto_resolve_latency_regressions(
+                    &trends,
+                    threshold_percent,
+                    cfg.recommendations.auto_resolve_after * 1_000_000,
+                )
+                .await?;
+            }
+        }
+        log::info!(
+            "[QUERY_RECOMMENDATIONS] {} query latency regression(s) out of {} fingerprint(s)",
+            trends.iter().filter(|trend| trend.regressed).count(),
+            trends.len()
+        );
+        if let Some(report) = &self.report {
+            report.lock().await.latency_trends = trends.clone();
+        }
+        self.usage_watermark.store(end_time, Ordering::Relaxed);
+        Ok(trends)
+    }
+
+    /// Recommends as partition keys the fields that the queries searched over the last
+    /// `duration` microseconds most often filter on for equality, see
+    /// [`partition_key_candidates`]. Fields that are not part of their stream, or already are one
+    /// of its partition keys, are skipped, as are the streams the run leaves out. Unless this is a
+    /// dry run, the recommendations are stored as [`PARTITION_KEY_RECOMMENDATION`]s, at most
+    /// `max_recommendations_per_run` of them, the most filtered on first.
+    pub async fn recommend_partition_keys(
+        &self,
+        duration: i64,
+    ) -> Result<Vec<PartitionKeyRecommendation>, anyhow::Error> {
+        let cfg = get_config();
+        let usage = self.usage_stats(duration).await?;
+        let searcher = self.searcher();
+        let mut streams: HashMap<(String, StreamType), Vec<crate::common::meta::stream::Stream>> =
+            HashMap::new();
+        let mut candidates = vec![];
+        for candidate in partition_key_candidates(&usage) {
+            if let Some((stream_type, stream_name)) = &self.stream_filter
+                && (candidate.stream_type != *stream_type || &candidate.stream != stream_name)
+            {
+                continue;
+            }
+            if is_excluded_stream(&candidate.stream, &excluded_streams(&candidate.org_id)) {
+                continue;
+            }
+            let key = (candidate.org_id.clone(), candidate.stream_type);
+            if !streams.contains_key(&key) {
+                let org_streams = searcher
+                    .list_streams(candidate.org_id.clone(), Some(candidate.stream_type))
+                    .await;
+                streams.insert(key.clone(), org_streams);
+            }
+            if streams[&key].iter().any(|stream| {
+                stream.name == candidate.stream && is_new_partition_key(stream, &candidate.field)
+            }) {
+                candidates.push(candidate);
+            }
+        }
+        let recommendations = top_by_impact(
+            candidates,
+            |candidate| candidate.query_hit_count,
+            cfg.recommendations.max_recommendations_per_run,
+        );
+        if !self.dry_run {
+            let dismiss_cooldown = cfg.recommendations.dismiss_cooldown * 1_000_000;
+            for recommendation in recommendations.iter() {
+                add_partition_key_recommendation(recommendation, dismiss_cooldown).await?;
+            }
+        }
+        log::info!(
+            "[QUERY_RECOMMENDATIONS] {} partition key recommendation(s) out of {} usage fingerprint(s)",
+            recommendations.len(),
+            usage.len()
+        );
+        if let Some(report) = &self.report {
+            report.lock().await.partition_keys = recommendations.clone();
+        }
+        Ok(recommendations)
+    }
+
+    /// Estimates what each regressed query would scan over `[start_time, end_time)` from stream
+    /// metadata, see [`estimate`], which ranks regressions of the same impact. Queries whose cost
+    /// can't be estimated, e.g. because their stream was deleted, keep no estimate.
+    async fn estimate_regression_costs(
+        &self,
+        trends: &mut [LatencyTrend],
+        start_time: i64,
+        end_time: i64,
+    ) {
+        let searcher = self.searcher();
+        for trend in trends.iter_mut().filter(|trend| trend.regressed) {
+            let request = Request {
+                query: config::meta::search::Query {
+                    sql: trend.sql.clone(),
+                    start_time,
+                    end_time,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            match searcher
+                .estimate(trend.org_id.clone(), StreamType::Logs, request)
+                .await
+            {
+                Ok(cost) => trend.estimated_cost = Some(cost),
+                Err(e) => log::debug!(
+                    "[QUERY_RECOMMENDATIONS] org={} failed to estimate the cost of query {}: {e}",
+                    trend.org_id,
+                    trend.fingerprint
+                ),
+            }
+        }
+    }
+
+    /// Returns the latency stats of the queries searched over the last `duration` microseconds,
+    /// in the org of the context or in all orgs, at most `ZO_QUERY_RECOMMENDATION_TOP_K` of them
+    /// in the `ZO_QUERY_RECOMMENDATION_USAGE_ORDER_BY` order. These are the queries the latency
+    /// tracking analyses.
+    pub async fn usage_stats(&self, duration: i64) -> Result<Vec<UsageStat>, anyhow::Error> {
+        let (start_time, end_time) = recommendation_window(now_micros(), duration);
+        self.read_usage_stats(start_time, end_time).await
+    }
+
+    /// Reads the latency stats of the queries searched in `[start_time, end_time)` from the
+    /// usage stream, see [`usage_stats`].
+    async fn read_usage_stats(
+        &self,
+        start_time: i64,
+        end_time: i64,
+    ) -> Result<Vec<UsageStat>, anyhow::Error> {
+        let cfg = get_config();
+        let order = UsageOrder::from(cfg.recommendations.usage_order_by.as_str());
+        let sources = if cfg.recommendations.dashboard_queries {
+            vec![UsageSource::AdHoc, UsageSource::Dashboards]
+        } else {
+            vec![UsageSource::All]
+        };
+        let mut stats = vec![];
+        for source in sources {
+            let request = Request {
+                query: config::meta::search::Query {
+                    sql: latency_stats_sql(
+                        self.org_id.as_deref(),
+                        source,
+                        order,
+                        cfg.recommendations.max_queries,
+                    ),
+                    start_time,
+                    end_time,
+                    size: cfg.recommendations.max_queries as i64,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let resp = self.search(META_ORG_ID.to_string(), request).await?;
+            stats.extend(usage_stats(&resp.hits, order));
+        }
+        sort_usage_stats(&mut stats, order);
+        Ok(stats)
+    }
+
+    /// Closes the open latency regressions older than `min_age` microseconds that no longer
+    /// apply given the `trends` of the run, see [`auto_resolutions`].
+    async fn auto_resolve_latency_regressions(
+        &self,
+        trends: &[LatencyTrend],
+        threshold_percent: u64,
+        min_age: i64,
+    ) -> Result<(), anyhow::Error> {
+        for org_id in self.get_all_org_ids(None).await? {
+            let open = recommendations::list_recommendations(
+                &org_id,
+                Some(RecommendationStatus::Open),
+                Some(LATENCY_REGRESSION_RECOMMENDATION),
+            )
+            .await?;
+            let org_trends = trends
+                .iter()
+                .filter(|trend| trend.org_id == org_id)
+                .cloned()
+                .collect::<Vec<_>>();
+            for (id, reason) in
+                auto_resolutions(&open, &org_trends, threshold_percent, now_micros(), min_age)
+            {
+                if recommendations::auto_resolve(&org_id, &id, &reason).await? {
+                    log::info!(
+                        "[QUERY_RECOMMENDATIONS] org={org_id} auto resolved recommendation {id}: {reason}"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
 impl QueryRecommendationEngine for QueryOptimizerContext {
     fn get_all_org_ids(
         &self,
         limit: Option<i64>,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<String>, anyhow::Error>> + Send>> {
+        if let Some(org_id) = self.org_id.clone() {
+            return Box::pin(async move { Ok(vec![org_id]) });
+        }
         Box::pin(async move {
             Ok(organization::list(limit)
                 .await?
@@ -47,10 +716,25 @@ impl QueryRecommendationEngine for QueryOptimizerContext {
         &self,
         org_id: String,
     ) -> Pin<Box<dyn Future<Output = Result<Vec<Stream>, anyhow::Error>> + Send>> {
+        let stream_filter = self.stream_filter.clone();
+        let searcher = self.searcher();
         Box::pin(async move {
-            Ok(get_streams(&org_id, None, true, None)
+            let excluded = excluded_streams(&org_id);
+            let min_records = get_config().recommendations.min_stream_records;
+            let stream_type = stream_filter.as_ref().map(|(stream_type, _)| *stream_type);
+            Ok(searcher
+                .list_streams(org_id.clone(), stream_type)
                 .await
                 .into_iter()
+                .filter(|stream| !is_excluded_stream(&stream.name, &excluded))
+                .filter(|stream| {
+                    stream_filter
+                        .as_ref()
+                        .is_none_or(|(_, stream_name)| &stream.name == stream_name)
+                })
+                .filter(|stream| {
+                    has_enough_records(&org_id, &stream.name, &stream.stats, min_records)
+                })
                 .map(Into::into)
                 .collect())
         })
@@ -62,10 +746,96 @@ impl QueryRecommendationEngine for QueryOptimizerContext {
         request: config::meta::search::Request,
     ) -> Pin<Box<dyn Future<Output = Result<config::meta::search::Response, anyhow::Error>> + Send>>
     {
+        let report = self.report.clone();
+        let stream_filter = self.stream_filter.clone();
+        let timed_out = self.timed_out.clone();
+        let searcher = self.searcher();
+        let searches = self.searches.clone();
         Box::pin(async move {
-            search("", &org_id, StreamType::Logs, None, &request)
-                .await
-                .map_err(Into::into)
+            let stream_type = request_stream_type(&request);
+            let usage_search = stream_filter.is_some() && is_usage_search(&org_id, &request);
+            if let Some((filter_type, filter_name)) = &stream_filter
+                && !usage_search
+                && !sql_touches_stream(&request.query.sql, *filter_type, filter_name)
+            {
+                log::debug!(
+                    "[QUERY_RECOMMENDATIONS] org={org_id} skipping search of streams other than {filter_type}/{filter_name}"
+                );
+                return Ok(config::meta::search::Response::new(
+                    request.query.from,
+                    request.query.size,
+                ));
+            }
+            validate_system_query(&request.query.sql, &[])?;
+            let timeout = get_config().recommendations.timeout;
+            let (trace_id, request) =
+                system_search_request(request, timeout, get_config().recommendations.low_priority);
+            let request = routed_request(
+                request,
+                split_config_list(&get_config().recommendations.regions),
+                split_config_list(&get_config().recommendations.clusters),
+            );
+            if !is_routable(&request) {
+                return Err(anyhow::anyhow!(
+                    "Recommendation search targets regions {:?} and clusters {:?}, but super cluster is not enabled",
+                    request.regions,
+                    request.clusters
+                ));
+            }
+            let (from, size) = (request.query.from, request.query.size);
+            let trace = RecommendationQueryTrace {
+                trace_id,
+                sql: request.query.sql.clone(),
+                start_time: request.query.start_time,
+                end_time: request.query.end_time,
+            };
+            log::debug!(
+                "[QUERY_RECOMMENDATIONS] trace_id={} org={org_id} start_time={} end_time={} sql={}",
+                trace.trace_id,
+                trace.start_time,
+                trace.end_time,
+                trace.sql
+            );
+            let trace_id = trace.trace_id.clone();
+            if let Some(report) = report {
+                report.lock().await.queries.push(trace);
+            }
+            let cache_ttl = get_config().recommendations.cache_ttl;
+            let run_key = search_cache_key(&org_id, &request, 0);
+            let cache_key = search_cache_key(&org_id, &request, cache_ttl * 1_000_000);
+            let filter_usage = |resp: Response| match &stream_filter {
+                Some((filter_type, filter_name)) if usage_search => {
+                    filter_usage_hits(resp, *filter_type, filter_name)
+                }
+                _ => resp,
+            };
+            if let Some(resp) = searches.get(&run_key).map(|resp| resp.clone()) {
+                log::debug!("[QUERY_RECOMMENDATIONS] trace_id={trace_id} search already run");
+                return Ok(filter_usage(resp));
+            }
+            if let Some(resp) = get_cached_search(&cache_key, cache_ttl, now_micros()) {
+                log::debug!("[QUERY_RECOMMENDATIONS] trace_id={trace_id} search cache hit");
+                searches.insert(run_key, resp.clone());
+                return Ok(filter_usage(resp));
+            }
+            search_with_timeout(
+                async move {
+                    let resp = searcher
+                        .search(trace_id, org_id, stream_type, request)
+                        .await?;
+                    if cache_ttl > 0 {
+                        cache_search(cache_key, resp.clone(), cache_ttl, now_micros());
+                    }
+                    searches.insert(run_key, resp.clone());
+                    Ok(resp)
+                },
+                Duration::from_secs(timeout),
+                &timed_out,
+                from,
+                size,
+            )
+            .await
+            .map(filter_usage)
         })
     }
 
@@ -73,13 +843,44 @@ impl QueryRecommendationEngine for QueryOptimizerContext {
         &self,
         recommendations: Vec<OptimiserRecommendation>,
     ) -> Pin<Box<dyn Future<Output = Result<IngestionResponse, anyhow::Error>> + Send>> {
+        let report = self.report.clone();
+        let dry_run = self.dry_run;
+        let org_id = self.org_id.clone();
+        let ctx = self.clone();
         Box::pin(async move {
+            let mut values = recommendations
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| anyhow::anyhow!("Recommendation serialization failed. Error={e:?}"))?;
+            ctx.attach_distinct_values(&mut values, distinct_options(&get_config()))
+                .await;
+            if let Some(report) = report {
+                report
+                    .lock()
+                    .await
+                    .recommendations
+                    .extend(values.iter().cloned());
+            }
+            if dry_run {
+                log::info!(
+                    "[QUERY_RECOMMENDATIONS] Dry run, not ingesting {} recommendation(s)",
+                    recommendations.len()
+                );
+                return Ok(IngestionResponse {
+                    status_code: 200,
+                    message: "dry run".to_string(),
+                });
+            }
+            let dismiss_cooldown = get_config().recommendations.dismiss_cooldown * 1_000_000;
+            let data = serde_json::to_vec_pretty(&values);
+            store_recommendations(values, org_id.as_deref(), dismiss_cooldown).await?;
             let request = IngestionRequest {
                 org_id: META_ORG_ID.to_string(),
                 stream_type: StreamType::Logs.to_string(),
                 stream_name: "query_recommendations".to_string(),
                 data: Some(IngestionData {
-                    data: serde_json::to_vec_pretty(&recommendations).map_err(|e| {
+                    data: data.map_err(|e| {
                         anyhow::anyhow!("Recommendation serialization failed. Error={e:?}")
                     })?,
                 }),
@@ -93,32 +894,3539 @@ impl QueryRecommendationEngine for QueryOptimizerContext {
     }
 }
 
-pub async fn run() {
-    let cfg = get_config();
-    let query_recommendation_analysis_interval = cfg.limit.query_recommendation_analysis_interval;
-    let query_recommendation_service = QueryRecommendationService {
-        ctx: Arc::new(QueryOptimizerContext),
-        query_recommendation_analysis_interval: cfg.limit.query_recommendation_analysis_interval,
-        query_recommendation_duration: cfg.limit.query_recommendation_duration,
-        query_recommendation_top_k: cfg.limit.query_recommendation_top_k,
+/// The columns a recommendation of the engine is stored and deduplicated under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecommendationKey {
+    org_id: String,
+    stream: String,
+    recommendation_type: String,
+    target_field: String,
+}
+
+/// Reads the key of a serialized engine recommendation. The engine names some of these fields
+/// differently depending on the recommendation, so the known aliases are all accepted. `org_id`
+/// is used when the recommendation doesn't name its org. Returns None if a field is missing.
+fn recommendation_key(
+    recommendation: &serde_json::Value,
+    org_id: Option<&str>,
+) -> Option<RecommendationKey> {
+    let field = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| recommendation.get(*name).and_then(|v| v.as_str()))
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
     };
+    Some(RecommendationKey {
+        org_id: field(&["org_id"]).or_else(|| org_id.map(str::to_string))?,
+        stream: field(&["stream", "stream_name"])?,
+        recommendation_type: field(&["recommendation_type", "type"])?,
+        target_field: field(&["target_field", "field"])?,
+    })
+}
 
-    spawn_pausable_job!(
-        "Query Optimization Recommendations",
-        query_recommendation_analysis_interval,
+/// Returns the stream type of a serialized engine recommendation, logs when it doesn't name one.
+fn recommendation_stream_type(recommendation: &serde_json::Value) -> StreamType {
+    recommendation
+        .get("stream_type")
+        .and_then(|v| v.as_str())
+        .map(StreamType::from)
+        .unwrap_or(StreamType::Logs)
+}
+
+/// Returns the options the distinct values of the recommended fields are computed with: up to
+/// `ZO_QUERY_RECOMMENDATION_SAMPLE_VALUES` example values of each field, over chunks of
+/// `ZO_QUERY_RECOMMENDATION_DISTINCT_CHUNK_INTERVAL` seconds of the window.
+fn distinct_options(cfg: &config::Config) -> DistinctOptions {
+    let chunk_interval = cfg.recommendations.distinct_chunk_interval;
+    DistinctOptions {
+        chunk_interval: (chunk_interval > 0).then_some(chunk_interval * 1_000_000),
+        sample_values: cfg.recommendations.sample_values,
+        ..Default::default()
+    }
+}
+
+/// Adds the distinct count and example values of its target field to the payload of a
+/// serialized recommendation, unless the payload already has them.
+fn attach_distinct(recommendation: &mut serde_json::Value, values: &FieldDistinct) {
+    let Some(payload) = recommendation.as_object_mut() else {
+        return;
+    };
+    payload
+        .entry("distinct_count")
+        .or_insert(values.distinct_count.into());
+    if !values.sample_values.is_empty() {
+        payload
+            .entry("sample_values")
+            .or_insert(values.sample_values.clone().into());
+    }
+}
+
+/// Stores the recommendations of a run so they can be listed, accepted and applied, deduplicated
+/// against the stored ones, see [`recommendations::add`]. Recommendations without a key are
+/// skipped. Returns the number of recommendations stored.
+async fn store_recommendations(
+    recommendations: Vec<serde_json::Value>,
+    org_id: Option<&str>,
+    dismiss_cooldown: i64,
+) -> Result<usize, anyhow::Error> {
+    let mut stored = 0;
+    for recommendation in recommendations {
+        let Some(key) = recommendation_key(&recommendation, org_id) else {
+            log::warn!(
+                "[QUERY_RECOMMENDATIONS] Skipping recommendation without org, stream, type or target field: {recommendation}"
+            );
+            continue;
+        };
+        if recommendations::add(
+            &key.org_id,
+            &key.stream,
+            &key.recommendation_type,
+            &key.target_field,
+            recommendation,
+            dismiss_cooldown,
+        )
+        .await?
+        .is_some()
         {
-            let _ = query_recommendation_service
-                .run()
-                .await
-                .inspect_err(|e| {
-                    log::error!(
-                        "Recommendation service stopped with an error: Error={:?}",
-                        e
-                    );
-                })
-                .inspect(|_| {
-                    log::warn!("Recommendation service quietly ended without an error!");
-                });
+            stored += 1;
+        }
+    }
+    Ok(stored)
+}
+
+/// Prepares an engine request to run as a system search tagged with
+/// [`RECOMMENDATION_SEARCH_SOURCE`], returning the trace id to run it with. Being a system
+/// search, the queries the engine runs over the usage stream are not themselves counted as usage.
+/// It runs at low priority like other system searches, unless `low_priority` is off.
+fn system_search_request(
+    mut request: Request,
+    timeout: u64,
+    low_priority: bool,
+) -> (String, Request) {
+    if request.timeout == 0 {
+        request.timeout = timeout as i64;
+    }
+    let (trace_id, mut request) = prepare_internal_search(request, RECOMMENDATION_SEARCH_SOURCE);
+    if !low_priority {
+        request.search_priority = SearchPriority::Normal;
+    }
+    (trace_id, request)
+}
+
+/// Routes an engine request to the configured `regions` and `clusters`, unless the engine picked
+/// them itself.
+fn routed_request(mut request: Request, regions: Vec<String>, clusters: Vec<String>) -> Request {
+    if request.regions.is_empty() && request.clusters.is_empty() {
+        request.regions = regions;
+        request.clusters = clusters;
+    }
+    request
+}
+
+/// Returns false if the request targets other regions or clusters and super cluster is not
+/// enabled, in which case the search would silently run on the local cluster instead.
+fn is_routable(request: &Request) -> bool {
+    (request.regions.is_empty() && request.clusters.is_empty())
+        || o2_enterprise::enterprise::common::config::get_config()
+            .super_cluster
+            .enabled
+}
+
+/// Returns true if an engine request can first be tried in local mode, which skips the fan-out
+/// to other queriers. Requests that target other regions or clusters, or that set the mode
+/// themselves, are left alone.
+fn prefers_local_mode(request: &Request) -> bool {
+    request.regions.is_empty() && request.clusters.is_empty() && request.local_mode.is_none()
+}
+
+/// Runs a recommendation search as [`RECO_SERVICE_USER`], trying local mode first when the request
+/// allows it.
+async fn run_search(
+    trace_id: &str,
+    org_id: &str,
+    stream_type: StreamType,
+    request: Request,
+) -> Result<Response, anyhow::Error> {
+    let user_id = Some(RECO_SERVICE_USER.to_string());
+    if prefers_local_mode(&request) {
+        let local_request = Request {
+            local_mode: Some(true),
+            ..request.clone()
+        };
+        let resp = search(
+            trace_id,
+            org_id,
+            stream_type,
+            user_id.clone(),
+            &local_request,
+        )
+        .await?;
+        if !resp.hits.is_empty() {
+            return Ok(resp);
+        }
+        // recent data may only be in the ingesters, which local mode skips
+        log::debug!(
+            "[QUERY_RECOMMENDATIONS] trace_id={trace_id} no local results, searching the cluster"
+        );
+    }
+    search(trace_id, org_id, stream_type, user_id, &request)
+        .await
+        .map_err(Into::into)
+}
+
+/// Returns the search cache key of a request. It covers the org, the SQL and where it runs, and
+/// the time window and page, so only identical searches share a cached response.
+///
+/// The time window is rounded down to multiples of `window_rounding` microseconds, so that the
+/// runs starting within the same interval, whose windows all end at the time they start, share
+/// their keys. A `window_rounding` of 0 keeps the exact window.
+fn search_cache_key(org_id: &str, request: &Request, window_rounding: i64) -> String {
+    let mut h = config::utils::hash::gxhash::new();
+    let fingerprint = h.sum64(
+        &[
+            request.query.sql.as_str(),
+            &request.regions.join(","),
+            &request.clusters.join(","),
+        ]
+        .join("/"),
+    );
+    let round = |time: i64| {
+        if window_rounding > 0 {
+            time - time.rem_euclid(window_rounding)
+        } else {
+            time
+        }
+    };
+    format!(
+        "query_recommendations/{org_id}/{fingerprint}/{}/{}/{}/{}",
+        round(request.query.start_time),
+        round(request.query.end_time),
+        request.query.from,
+        request.query.size
+    )
+}
+
+/// Returns the cached response for `key` if it was cached less than `ttl` seconds before `now`.
+fn get_cached_search(key: &str, ttl: i64, now: i64) -> Option<Response> {
+    if ttl <= 0 {
+        return None;
+    }
+    {
+        let entry = SEARCH_CACHE.get(key)?;
+        if now - entry.timestamp <= ttl * 1_000_000 {
+            return Some(entry.response.clone());
+        }
+    }
+    SEARCH_CACHE.remove(key);
+    None
+}
+
+/// Caches the response of a recommendation search made at `now`, see [`cache_search_in`].
+fn cache_search(key: String, response: Response, ttl: i64, now: i64) {
+    cache_search_in(
+        &SEARCH_CACHE,
+        key,
+        response,
+        ttl,
+        now,
+        SEARCH_CACHE_MAX_ENTRIES,
+    );
+}
+
+/// Caches the response of a search made at `now` in `cache`. The entries older than `ttl` seconds
+/// are dropped first, then the oldest ones until there is room for the response within
+/// `max_entries`, so the cache can't grow however many distinct searches are run.
+fn cache_search_in(
+    cache: &RwHashMap<String, SearchCacheEntry>,
+    key: String,
+    response: Response,
+    ttl: i64,
+    now: i64,
+    max_entries: usize,
+) {
+    cache.retain(|_, entry| now - entry.timestamp <= ttl * 1_000_000);
+    while cache.len() >= max_entries.max(1) && !cache.contains_key(&key) {
+        let Some(oldest) = cache
+            .iter()
+            .min_by_key(|entry| entry.value().timestamp)
+            .map(|entry| entry.key().clone())
+        else {
+            break;
+        };
+        cache.remove(&oldest);
+    }
+    cache.insert(
+        key,
+        SearchCacheEntry {
+            response,
+            timestamp: now,
+        },
+    );
+}
+
+/// Runs a recommendation search, returning an empty response and setting `timed_out` if it does
+/// not finish within `timeout`, so a slow query cannot block the recommendation job. A zero
+/// timeout disables the limit.
+async fn search_with_timeout<F>(
+    search: F,
+    timeout: Duration,
+    timed_out: &AtomicBool,
+    from: i64,
+    size: i64,
+) -> Result<config::meta::search::Response, anyhow::Error>
+where
+    F: Future<Output = Result<config::meta::search::Response, anyhow::Error>>,
+{
+    if timeout.is_zero() {
+        return search.await;
+    }
+    match tokio::time::timeout(timeout, search).await {
+        Ok(result) => result,
+        Err(_) => {
+            timed_out.store(true, Ordering::Relaxed);
+            log::warn!(
+                "[QUERY_RECOMMENDATIONS] Recommendation search timed out after {}s, continuing without results",
+                timeout.as_secs()
+            );
+            Ok(config::meta::search::Response::new(from, size))
+        }
+    }
+}
+
+/// Returns the stream type of the table queried by an engine request. Tables of streams other
+/// than logs are qualified with their stream type in the SQL, e.g. `"metrics"."cpu_usage"`.
+fn request_stream_type(request: &config::meta::search::Request) -> StreamType {
+    resolve_stream_names_with_type(&request.query.sql)
+        .ok()
+        .and_then(|tables| {
+            tables
+                .first()
+                .map(|table| table.get_stream_type(StreamType::Logs))
+        })
+        .unwrap_or(StreamType::Logs)
+}
+
+/// Returns true if an engine request searches the usage streams of the meta org, which record
+/// the queries that recommendations are computed from, rather than the streams being analysed.
+fn is_usage_search(org_id: &str, request: &Request) -> bool {
+    if org_id != META_ORG_ID {
+        return false;
+    }
+    let excluded = excluded_streams(org_id);
+    resolve_stream_names_with_type(&request.query.sql).is_ok_and(|tables| {
+        !tables.is_empty()
+            && tables
+                .iter()
+                .all(|table| is_excluded_stream(&table.stream_name(), &excluded))
+    })
+}
+
+/// Returns true if `sql` reads the stream `stream_name` of type `stream_type`.
+fn sql_touches_stream(sql: &str, stream_type: StreamType, stream_name: &str) -> bool {
+    resolve_stream_names_with_type(sql).is_ok_and(|tables| {
+        tables.iter().any(|table| {
+            table.stream_name() == stream_name
+                && table.get_stream_type(StreamType::Logs) == stream_type
+        })
+    })
+}
+
+/// Keeps the usage records of a response that are about queries reading the stream
+/// `stream_name` of type `stream_type`. Records are matched on the streams their query reads,
+/// or on their stream when the query can't be parsed. Rows that are not usage records, such as
+/// aggregations without a stream, are kept.
+fn filter_usage_hits(mut resp: Response, stream_type: StreamType, stream_name: &str) -> Response {
+    resp.hits.retain(|hit| {
+        if let Some(sql) = hit.get("request_body").and_then(|v| v.as_str())
+            && resolve_stream_names_with_type(sql).is_ok()
+        {
+            return sql_touches_stream(sql, stream_type, stream_name);
+        }
+        match hit.get("stream_name").and_then(|v| v.as_str()) {
+            Some(name) => {
+                name == stream_name
+                    && hit
+                        .get("stream_type")
+                        .and_then(|v| v.as_str())
+                        .is_none_or(|t| StreamType::from(t) == stream_type)
+            }
+            None => true,
         }
+    });
+    resp.total = resp.hits.len();
+    resp
+}
+
+/// Returns true if a stream holds at least `min_records` records, below which recommendations
+/// based on its distinct values would be statistically meaningless. A `min_records` of 0 lets all
+/// streams through. The record count comes from the stream stats, so it covers all of the stream's
+/// data, not just the recommendation window.
+fn has_enough_records(
+    org_id: &str,
+    stream_name: &str,
+    stats: &StreamStats,
+    min_records: i64,
+) -> bool {
+    if stats.doc_num >= min_records {
+        return true;
+    }
+    log::info!(
+        "[QUERY_RECOMMENDATIONS] org={org_id} skipping stream {stream_name}: {} records, fewer than the minimum of {min_records}",
+        stats.doc_num
     );
+    false
+}
+
+/// Returns the stream name patterns of `org_id` that must not be analysed. Only the meta org
+/// has internal streams, such as the usage streams the engine itself reads from.
+fn excluded_streams(org_id: &str) -> Vec<String> {
+    if org_id != META_ORG_ID {
+        return vec![];
+    }
+    split_config_list(&get_config().recommendations.excluded_streams)
+}
+
+/// Splits a comma separated config value, skipping empty entries.
+fn split_config_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .filter_map(|s| {
+            let s = s.trim();
+            if s.is_empty() {
+                None
+            } else {
+                Some(s.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Returns true if `stream_name` matches one of `patterns`, where a trailing `*` matches any
+/// suffix.
+fn is_excluded_stream(stream_name: &str, patterns: &[String]) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => stream_name.starts_with(prefix),
+            None => stream_name == pattern,
+        })
+}
+
+/// Runs `task` on each of `inputs` in a join set, at most `concurrency` at a time, and returns
+/// each input with its output, in the order they completed. A task that panics is logged and
+/// left out.
+async fn bounded_join<I, O, F, Fut>(inputs: Vec<I>, concurrency: usize, task: F) -> Vec<(I, O)>
+where
+    I: Clone + Send + 'static,
+    O: Send + 'static,
+    F: Fn(I) -> Fut,
+    Fut: Future<Output = O> + Send + 'static,
+{
+    let mut tasks = tokio::task::JoinSet::new();
+    let mut inputs = inputs.into_iter();
+    let mut outputs = vec![];
+    loop {
+        while tasks.len() < concurrency.max(1)
+            && let Some(input) = inputs.next()
+        {
+            let run = task(input.clone());
+            tasks.spawn(async move { (input, run.await) });
+        }
+        let Some(joined) = tasks.join_next().await else {
+            break;
+        };
+        match joined {
+            Ok(output) => outputs.push(output),
+            Err(e) => log::error!("[QUERY_RECOMMENDATIONS] task failed: {e}"),
+        }
+    }
+    outputs
+}
+
+/// Returns the time window, in microseconds, analysed by a run at `now`: the last `duration`
+/// microseconds, but at least [`MIN_RECOMMENDATION_WINDOW`]. A window reaching before the epoch
+/// starts at it.
+pub fn recommendation_window(now: i64, duration: i64) -> (i64, i64) {
+    let start_time = now.saturating_sub(duration.max(MIN_RECOMMENDATION_WINDOW));
+    (start_time.max(0), now)
+}
+
+/// Returns the number of microseconds in `minutes`, as many as fit in an i64 for intervals too
+/// large to be represented.
+pub fn interval_micros(minutes: i64) -> i64 {
+    minutes.saturating_mul(60 * 1_000_000)
+}
+
+/// Returns the time window, in microseconds, of the usage read by a run at `now`: the
+/// [`recommendation_window`], or only its part after the `watermark` left by the previous run,
+/// when the watermark is within it.
+fn usage_window(now: i64, duration: i64, watermark: Option<i64>) -> (i64, i64) {
+    let (start_time, end_time) = recommendation_window(now, duration);
+    match watermark {
+        Some(watermark) if watermark > start_time && watermark < end_time => (watermark, end_time),
+        _ => (start_time, end_time),
+    }
+}
+
+/// State of the scheduled recommendation run, kept in the data of its trigger.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RecommendationTriggerData {
+    /// Number of runs in a row that errored or timed out.
+    #[serde(default)]
+    pub consecutive_failures: u32,
+    /// End of the usage window read by the last run, in microseconds. The next run only reads
+    /// the usage recorded after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_watermark: Option<i64>,
+    /// When the circuit breaker last opened, in microseconds. Cleared by a successful run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breaker_opened_at: Option<i64>,
+}
+
+/// State of the circuit breaker of the scheduled runs, see [`RecommendationTriggerData::
+/// breaker_state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    /// The runs are scheduled as usual.
+    Closed,
+    /// The cooldown is over, the next run tests whether the runs succeed again.
+    HalfOpen,
+    /// The runs are paused until the cooldown is over.
+    Open,
+}
+
+impl BreakerState {
+    /// Value of the state in the `query_recommendations_breaker_state` metric.
+    pub fn as_metric(&self) -> i64 {
+        match self {
+            BreakerState::Closed => 0,
+            BreakerState::HalfOpen => 1,
+            BreakerState::Open => 2,
+        }
+    }
+}
+
+impl RecommendationTriggerData {
+    /// Parses the data of a trigger, which is empty for triggers created before it was tracked.
+    pub fn from_trigger_data(data: &str) -> Self {
+        config::utils::json::from_str(data).unwrap_or_default()
+    }
+
+    pub fn to_json_string(&self) -> String {
+        config::utils::json::to_string(self).unwrap()
+    }
+
+    /// Records the outcome of a run at `now`. The breaker opens, or opens again after a failed
+    /// half open run, once `threshold` runs in a row failed, and closes with the first run that
+    /// succeeds. A zero `threshold` turns the breaker off.
+    pub fn record_run(&mut self, failed: bool, now: i64, threshold: u32) {
+        if !failed {
+            self.consecutive_failures = 0;
+            self.breaker_opened_at = None;
+            return;
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if threshold > 0 && self.consecutive_failures >= threshold {
+            self.breaker_opened_at = Some(now);
+        }
+    }
+
+    /// Returns the state of the circuit breaker at `now`, which stays open for `cooldown`
+    /// seconds.
+    pub fn breaker_state(&self, now: i64, cooldown: i64) -> BreakerState {
+        match self.breaker_opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if now < opened_at.saturating_add(cooldown * 1_000_000) => {
+                BreakerState::Open
+            }
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Returns when the circuit breaker half opens, in microseconds, if it is open.
+    pub fn breaker_closes_at(&self, cooldown: i64) -> Option<i64> {
+        self.breaker_opened_at
+            .map(|opened_at| opened_at.saturating_add(cooldown * 1_000_000))
+    }
+}
+
+/// Clears the usage watermark of the scheduled recommendation runs, so that the next run reads
+/// the usage of the whole analysed window again. A run in progress still stores the watermark it
+/// reaches.
+pub async fn reset_usage_watermark() -> Result<(), anyhow::Error> {
+    let trigger = db::scheduler::get(
+        META_ORG_ID,
+        db::scheduler::TriggerModule::QueryRecommendations,
+        "QueryRecommendations",
+    )
+    .await?;
+    let mut data = RecommendationTriggerData::from_trigger_data(&trigger.data);
+    if data.usage_watermark.take().is_none() {
+        return Ok(());
+    }
+    let trigger = db::scheduler::Trigger {
+        data: data.to_json_string(),
+        ..trigger
+    };
+    db::scheduler::update_trigger(trigger, false, "").await?;
+    Ok(())
+}
+
+/// Returns the delay, in seconds, until the next scheduled run: `interval`, doubled for every
+/// failed run in a row, but never more than `max_backoff`. A zero `max_backoff` turns backoff
+/// off.
+pub fn backoff_delay(interval: i64, consecutive_failures: u32, max_backoff: i64) -> i64 {
+    let interval = interval.max(1);
+    if max_backoff <= 0 {
+        return interval;
+    }
+    interval
+        .saturating_mul(2_i64.saturating_pow(consecutive_failures))
+        .min(max_backoff.max(interval))
+}
+
+/// Spreads `delay` microseconds by up to `jitter_percent` percent, where `sample` in [-1, 1]
+/// picks the point within the spread.
+fn jittered(delay: i64, jitter_percent: u64, sample: f64) -> i64 {
+    let spread = delay as f64 * jitter_percent.min(100) as f64 / 100.0;
+    (delay + (spread * sample.clamp(-1.0, 1.0)) as i64).max(1_000_000)
+}
+
+/// Returns when the run following one at `now` is due, `delay` seconds later with up to
+/// `jitter_percent` percent of random spread.
+pub fn next_run_at(now: i64, delay: i64, jitter_percent: u64) -> i64 {
+    let sample = rand::rng().random_range(-1.0..=1.0);
+    now + jittered(delay.max(1) * 1_000_000, jitter_percent, sample)
+}
+
+/// Builds the recommendation service analysing the last `duration` microseconds.
+pub fn recommendation_service(
+    ctx: QueryOptimizerContext,
+    duration: i64,
+) -> QueryRecommendationService {
+    let cfg = get_config();
+    let (start_time, end_time) = recommendation_window(now_micros(), duration);
+    let ctx = QueryOptimizerContext {
+        window: Some((start_time, end_time)),
+        ..ctx
+    };
+    QueryRecommendationService {
+        ctx: Arc::new(ctx),
+        query_recommendation_analysis_interval: cfg.recommendations.interval,
+        query_recommendation_duration: end_time - start_time,
+        query_recommendation_top_k: cfg.recommendations.max_queries,
+    }
+}
+
+/// Records that a manual run of an org starts at `now`, unless the org started one less than
+/// `cooldown` seconds earlier, in which case the seconds until it may start another are
+/// returned. A `cooldown` of 0 disables the limit.
+pub fn try_start_manual_run(org_id: &str, now: i64, cooldown: i64) -> Result<(), u64> {
+    if cooldown <= 0 {
+        return Ok(());
+    }
+    let mut last_run = LAST_MANUAL_RUN.entry(org_id.to_string()).or_insert(0);
+    let next_run = last_run.saturating_add(cooldown * 1_000_000);
+    if *last_run > 0 && now < next_run {
+        // round up, so retrying after that many seconds succeeds
+        return Err(((next_run - now) as u64).div_ceil(1_000_000));
+    }
+    *last_run = now;
+    Ok(())
+}
+
+/// Forgets the manual run of an org started at `started_at`, for a run that failed, so that it
+/// doesn't hold back the next one. A run started since then is kept.
+pub fn abort_manual_run(org_id: &str, started_at: i64) {
+    LAST_MANUAL_RUN.remove_if(org_id, |_, last_run| *last_run == started_at);
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OnDemandRunError {
+    #[error("The recommendation run was cancelled by a newer run")]
+    Cancelled,
+    #[error(transparent)]
+    Run(#[from] anyhow::Error),
+}
+
+/// Generates the recommendations of an org immediately and returns them, along with the
+/// searches that were run. `duration` overrides the analysed time window, in microseconds.
+///
+/// With `dry_run` the recommendations are only returned, not ingested. With `stream_filter` only
+/// that stream is analysed, from the queries that read it.
+///
+/// Only one on-demand run per org can be in progress at a time: starting a run cancels the one in
+/// progress for the same org, which then returns [`OnDemandRunError::Cancelled`]. Runs of other
+/// orgs are not affected.
+pub async fn run_on_demand(
+    org_id: &str,
+    duration: Option<i64>,
+    dry_run: bool,
+    stream_filter: Option<(StreamType, String)>,
+) -> Result<RecommendationRunReport, OnDemandRunError> {
+    run_on_demand_with_searcher(
+        Arc::new(ClusterSearcher),
+        org_id,
+        duration,
+        dry_run,
+        stream_filter,
+    )
+    .await
+}
+
+/// Like [`run_on_demand`], reading the streams and search results from `searcher`.
+pub async fn run_on_demand_with_searcher(
+    searcher: Arc<dyn Searcher>,
+    org_id: &str,
+    duration: Option<i64>,
+    dry_run: bool,
+    stream_filter: Option<(StreamType, String)>,
+) -> Result<RecommendationRunReport, OnDemandRunError> {
+    let id = ON_DEMAND_RUN_ID.fetch_add(1, Ordering::SeqCst);
+    let (cancel, cancelled) = oneshot::channel();
+    let report = Arc::new(Mutex::new(RecommendationRunReport::default()));
+    let previous = ON_DEMAND_RUNS.insert(
+        org_id.to_string(),
+        OnDemandRun {
+            id,
+            org_id: org_id.to_string(),
+            cancel,
+            report: report.clone(),
+        },
+    );
+    if let Some(previous) = previous {
+        cancel_on_demand_run(previous).await;
+    }
+
+    let duration = duration.unwrap_or(get_config().recommendations.duration);
+    let ctx = QueryOptimizerContext {
+        org_id: Some(org_id.to_string()),
+        report: Some(report.clone()),
+        dry_run,
+        stream_filter,
+        ..Default::default()
+    }
+    .with_searcher(searcher);
+    let query_recommendation_service = recommendation_service(ctx.clone(), duration);
+    if dry_run {
+        log::info!("[QUERY_RECOMMENDATIONS] Starting dry run for org {org_id}");
+    }
+    let result = run_cancellable(
+        async {
+            query_recommendation_service.run().await?;
+            ctx.track_latency_trends(duration).await?;
+            ctx.recommend_partition_keys(duration).await
+        },
+        cancelled,
+    )
+    .await;
+    ON_DEMAND_RUNS.remove_if(org_id, |_, run| run.id == id);
+    result?;
+
+    Ok(std::mem::take(&mut *report.lock().await))
+}
+
+/// Cancels an on-demand run, along with the search it is running so the queriers stop working
+/// on it.
+async fn cancel_on_demand_run(run: OnDemandRun) {
+    log::info!(
+        "[QUERY_RECOMMENDATIONS] Cancelling on-demand run for org {} in favour of a newer run",
+        run.org_id
+    );
+    let _ = run.cancel.send(());
+    let trace_id = run
+        .report
+        .lock()
+        .await
+        .queries
+        .last()
+        .map(|trace| trace.trace_id.clone());
+    if let Some(trace_id) = trace_id
+        && let Err(e) = cancel_query(&run.org_id, &trace_id).await
+    {
+        log::warn!("[QUERY_RECOMMENDATIONS] trace_id={trace_id} failed to cancel search: {e}");
+    }
+}
+
+/// Runs `run` until it finishes, or until `cancelled` fires.
+async fn run_cancellable<F, T>(
+    run: F,
+    cancelled: oneshot::Receiver<()>,
+) -> Result<T, OnDemandRunError>
+where
+    F: Future<Output = Result<T, anyhow::Error>>,
+{
+    tokio::select! {
+        result = run => Ok(result?),
+        Ok(()) = cancelled => Err(OnDemandRunError::Cancelled),
+    }
+}
+
+/// Type of the recommendations to look into a query whose p95 latency regressed since the
+/// previous run.
+pub const LATENCY_REGRESSION_RECOMMENDATION: &str = "latency_regression";
+
+/// How long the latency stats of a run are kept to compare later runs with.
+const LATENCY_STATS_RETENTION: i64 = 30 * 24 * 3600 * 1_000_000; // microseconds
+
+/// How the p95 latency of the queries sharing a fingerprint changed since the previous run.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LatencyTrend {
+    pub org_id: String,
+    pub fingerprint: String,
+    pub sql: String,
+    pub p95_micros: i64,
+    pub queries: i64,
+    /// p95 latency measured by the previous run, None if the fingerprint is new.
+    pub previous_p95_micros: Option<i64>,
+    pub change_percent: Option<f64>,
+    /// Set when the p95 latency grew by more than the regression threshold, which makes the
+    /// query a higher priority than the other recommendations.
+    pub regressed: bool,
+    /// Dashboard and panel running the query, for the queries of the dashboard source.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dashboard_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panel_id: Option<String>,
+    /// What the query scans over the analysed window, estimated from stream metadata. Only set
+    /// for regressions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost: Option<QueryCostEstimate>,
+}
+
+/// Order of the queries returned by the [`latency_stats_sql`] query, which keeps the first ones.
+/// See `ZO_QUERY_RECOMMENDATION_USAGE_ORDER_BY`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum UsageOrder {
+    /// Slowest first.
+    #[default]
+    P95,
+    /// Most frequent first.
+    Count,
+    /// Highest [`latency_impact`] first.
+    Impact,
+}
+
+impl From<&str> for UsageOrder {
+    fn from(s: &str) -> Self {
+        match s.to_lowercase().as_str() {
+            "count" => Self::Count,
+            "impact" => Self::Impact,
+            _ => Self::P95,
+        }
+    }
+}
+
+impl UsageOrder {
+    fn order_by(&self) -> &'static str {
+        match self {
+            Self::P95 => "p95 DESC",
+            Self::Count => "queries DESC",
+            Self::Impact => "approx_percentile_cont(response_time, 0.95) * count(*) DESC",
+        }
+    }
+}
+
+/// Which queries of the usage a [`latency_stats_sql`] query reads. See
+/// `ZO_QUERY_RECOMMENDATION_DASHBOARD_QUERIES`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UsageSource {
+    /// Every query.
+    All,
+    /// The queries not run by dashboard panels.
+    AdHoc,
+    /// The queries run by dashboard panels, per dashboard and panel.
+    Dashboards,
+}
+
+/// Returns the usage query computing the p95 latency, in seconds, of each query of `source`
+/// searched in `org_id`, or in all orgs when None, the first `limit` in `order`.
+fn latency_stats_sql(
+    org_id: Option<&str>,
+    source: UsageSource,
+    order: UsageOrder,
+    limit: usize,
+) -> String {
+    let org_filter = org_id
+        .map(|org_id| format!(" AND org_id = '{}'", org_id.replace('\'', "''")))
+        .unwrap_or_default();
+    let (dashboard_columns, source_filter) = match source {
+        UsageSource::All => ("", ""),
+        UsageSource::AdHoc => (
+            "",
+            " AND (search_type IS NULL OR search_type <> 'dashboards')",
+        ),
+        UsageSource::Dashboards => (
+            ", dashboard_id, dashboard_info_panel_id",
+            " AND search_type = 'dashboards'",
+        ),
+    };
+    format!(
+        "SELECT org_id, request_body{dashboard_columns}, \
+         approx_percentile_cont(response_time, 0.95) AS p95, \
+         max(response_time) AS max_response_time, count(*) AS queries \
+         FROM \"usage\" WHERE event = 'Search'{org_filter}{source_filter} \
+         GROUP BY org_id, request_body{dashboard_columns} ORDER BY {} LIMIT {limit}",
+        order.order_by()
+    )
+}
+
+/// Returns the fingerprint the latency stats of `sql` are stored under, shared by the queries
+/// that only differ in literal values or formatting.
+fn latency_fingerprint(sql: &str) -> String {
+    let mut h = config::utils::hash::gxhash::new();
+    format!(
+        "{:016x}",
+        h.sum64(&config::utils::sql::fingerprint_sql(sql))
+    )
+}
+
+/// Latency stats of the queries of an org sharing a fingerprint, read from the usage stream.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageStat {
+    pub org_id: String,
+    pub fingerprint: String,
+    /// The query of the fingerprint with the slowest p95.
+    pub sql: String,
+    pub queries: i64,
+    pub p95_micros: i64,
+    pub max_micros: i64,
+    /// Dashboard and panel running the queries, for the queries of the dashboard source. Their
+    /// fingerprint is then specific to the panel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dashboard_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub panel_id: Option<String>,
+}
+
+/// Groups the rows of the [`latency_stats_sql`] query by org and query fingerprint, in `order`.
+/// A fingerprint keeps the slowest p95 and max of its queries, and the total number of queries.
+/// The queries of dashboard panels are grouped per panel.
+fn usage_stats(hits: &[serde_json::Value], order: UsageOrder) -> Vec<UsageStat> {
+    let mut stats: HashMap<(String, String), UsageStat> = HashMap::new();
+    for hit in hits {
+        let (Some(org_id), Some(sql), Some(p95)) = (
+            hit.get("org_id").and_then(|v| v.as_str()),
+            hit.get("request_body").and_then(|v| v.as_str()),
+            hit.get("p95").and_then(|v| v.as_f64()),
+        ) else {
+            continue;
+        };
+        let queries = hit.get("queries").and_then(|v| v.as_i64()).unwrap_or(1);
+        let p95_micros = (p95 * 1_000_000.0) as i64;
+        let max_micros = hit
+            .get("max_response_time")
+            .and_then(|v| v.as_f64())
+            .map(|max| (max * 1_000_000.0) as i64)
+            .unwrap_or(p95_micros);
+        let dashboard_id = hit
+            .get("dashboard_id")
+            .and_then(|v| v.as_str())
+            .filter(|id| !id.is_empty());
+        let panel_id = hit
+            .get("dashboard_info_panel_id")
+            .and_then(|v| v.as_str())
+            .filter(|id| !id.is_empty());
+        let fingerprint = match dashboard_id {
+            Some(dashboard_id) => {
+                let mut h = config::utils::hash::gxhash::new();
+                let key = format!(
+                    "{}/{dashboard_id}/{}",
+                    latency_fingerprint(sql),
+                    panel_id.unwrap_or_default()
+                );
+                format!("{:016x}", h.sum64(&key))
+            }
+            None => latency_fingerprint(sql),
+        };
+        stats
+            .entry((org_id.to_string(), fingerprint.clone()))
+            .and_modify(|stat| {
+                if p95_micros > stat.p95_micros {
+                    stat.p95_micros = p95_micros;
+                    stat.sql = sql.to_string();
+                }
+                stat.max_micros = stat.max_micros.max(max_micros);
+                stat.queries += queries;
+            })
+            .or_insert_with(|| UsageStat {
+                org_id: org_id.to_string(),
+                fingerprint,
+                sql: sql.to_string(),
+                queries,
+                p95_micros,
+                max_micros,
+                dashboard_id: dashboard_id.map(str::to_string),
+                panel_id: panel_id.map(str::to_string),
+            });
+    }
+    let mut stats = stats.into_values().collect::<Vec<_>>();
+    sort_usage_stats(&mut stats, order);
+    stats
+}
+
+fn sort_usage_stats(stats: &mut [UsageStat], order: UsageOrder) {
+    stats.sort_by_key(|stat| {
+        std::cmp::Reverse(match order {
+            UsageOrder::P95 => stat.p95_micros,
+            UsageOrder::Count => stat.queries,
+            UsageOrder::Impact => stat.p95_micros.saturating_mul(stat.queries),
+        })
+    });
+}
+
+/// Groups the usage stats by org, as the latency stats stored for the next runs.
+fn latency_stats(stats: Vec<UsageStat>) -> HashMap<String, Vec<LatencyStat>> {
+    let mut by_org: HashMap<String, Vec<LatencyStat>> = HashMap::new();
+    for stat in stats {
+        by_org.entry(stat.org_id).or_default().push(LatencyStat {
+            fingerprint: stat.fingerprint,
+            sql: stat.sql,
+            p95_micros: stat.p95_micros,
+            queries: stat.queries,
+        });
+    }
+    by_org
+}
+
+/// Returns the usage stats of the queries searched in `org_id` over the last `duration`
+/// microseconds, slowest first unless `ZO_QUERY_RECOMMENDATION_USAGE_ORDER_BY` says otherwise.
+/// The meta org gets the stats of every org.
+pub async fn top_usage_stats(org_id: &str, duration: i64) -> Result<Vec<UsageStat>, anyhow::Error> {
+    let ctx = QueryOptimizerContext {
+        org_id: (org_id != META_ORG_ID).then(|| org_id.to_string()),
+        dry_run: true,
+        ..Default::default()
+    };
+    ctx.usage_stats(duration).await
+}
+
+/// Compares the latency stats of a run of an org with the p95 latency measured by the previous
+/// run, and flags the fingerprints that got more than `threshold_percent` slower. Regressions
+/// come first, the largest first.
+fn latency_trends(
+    org_id: &str,
+    current: &[LatencyStat],
+    previous: &HashMap<String, i64>,
+    threshold_percent: u64,
+) -> Vec<LatencyTrend> {
+    let mut trends = current
+        .iter()
+        .map(|stat| {
+            let previous_p95_micros = previous.get(&stat.fingerprint).copied();
+            let change_percent = previous_p95_micros
+                .filter(|previous| *previous > 0)
+                .map(|previous| (stat.p95_micros - previous) as f64 * 100.0 / previous as f64);
+            LatencyTrend {
+                org_id: org_id.to_string(),
+                fingerprint: stat.fingerprint.clone(),
+                sql: stat.sql.clone(),
+                p95_micros: stat.p95_micros,
+                queries: stat.queries,
+                previous_p95_micros,
+                change_percent,
+                regressed: change_percent.is_some_and(|change| change > threshold_percent as f64),
+                dashboard_id: None,
+                panel_id: None,
+                estimated_cost: None,
+            }
+        })
+        .collect::<Vec<_>>();
+    trends.sort_by(|a, b| {
+        b.regressed.cmp(&a.regressed).then(
+            b.change_percent
+                .unwrap_or(f64::MIN)
+                .total_cmp(&a.change_percent.unwrap_or(f64::MIN)),
+        )
+    });
+    trends
+}
+
+/// Merges the `stats` of the usage read by a run into the latest `stored` stats of the analysed
+/// window. The new stats replace the stored ones of the same fingerprint.
+fn merge_latency_stats(stored: Vec<LatencyStat>, stats: &[LatencyStat]) -> Vec<LatencyStat> {
+    let mut merged = stored
+        .into_iter()
+        .filter(|stored| {
+            stats
+                .iter()
+                .all(|stat| stat.fingerprint != stored.fingerprint)
+        })
+        .collect::<Vec<_>>();
+    merged.extend_from_slice(stats);
+    merged
+}
+
+/// Returns the impact of a latency trend: the time its queries spent waiting at p95 latency, so a
+/// slow query that runs often outranks an even slower one that rarely runs.
+fn latency_impact(trend: &LatencyTrend) -> i64 {
+    trend.p95_micros.saturating_mul(trend.queries)
+}
+
+/// Returns the rank of a latency regression: its [`latency_impact`], then the number of records
+/// it is estimated to scan, so that of two regressions of the same impact the one scanning more
+/// comes first.
+fn regression_rank(trend: &LatencyTrend) -> (i64, usize) {
+    (
+        latency_impact(trend),
+        trend
+            .estimated_cost
+            .as_ref()
+            .map(|cost| cost.records)
+            .unwrap_or_default(),
+    )
+}
+
+/// Returns the `max` candidates of highest `impact`, highest first. All candidates are kept when
+/// `max` is 0. The others are dropped, they come back in a later run if they are still relevant.
+fn top_by_impact<T, K: Ord>(
+    candidates: impl IntoIterator<Item = T>,
+    impact: impl Fn(&T) -> K,
+    max: usize,
+) -> Vec<T> {
+    let mut candidates = candidates.into_iter().collect::<Vec<_>>();
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(impact(candidate)));
+    if max > 0 {
+        candidates.truncate(max);
+    }
+    candidates
+}
+
+/// Returns the ids of the open latency regressions of an org that no longer apply, with the
+/// reason, given the `trends` of the org measured by the run at `now`. A regression no longer
+/// applies when its query is not among the queries of the analysed window any more, or when its
+/// p95 latency is back within the regression threshold of the p95 measured before the
+/// regression. Regressions younger than `min_age` microseconds are left open.
+fn auto_resolutions(
+    open: &[Recommendation],
+    trends: &[LatencyTrend],
+    threshold_percent: u64,
+    now: i64,
+    min_age: i64,
+) -> Vec<(String, String)> {
+    open.iter()
+        .filter(|recommendation| {
+            recommendation.recommendation_type == LATENCY_REGRESSION_RECOMMENDATION
+                && recommendation.status == RecommendationStatus::Open
+                && recommendation.age(now) >= min_age
+        })
+        .filter_map(|recommendation| {
+            let Some(trend) = trends
+                .iter()
+                .find(|trend| trend.fingerprint == recommendation.target_field)
+            else {
+                return Some((
+                    recommendation.id.clone(),
+                    "the query no longer appears in the analysed window".to_string(),
+                ));
+            };
+            let baseline = recommendation
+                .payload
+                .get("previous_p95_micros")
+                .and_then(|v| v.as_i64())?;
+            let limit = baseline as f64 * (1.0 + threshold_percent as f64 / 100.0);
+            ((trend.p95_micros as f64) <= limit).then(|| {
+                (
+                    recommendation.id.clone(),
+                    format!(
+                        "the query is fast again, p95 of {}ms against {}ms before the regression",
+                        trend.p95_micros / 1000,
+                        baseline / 1000
+                    ),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Stores a latency regression as a high priority recommendation, deduplicated like the other
+/// recommendations on its fingerprint.
+async fn add_latency_regression(
+    trend: &LatencyTrend,
+    dismiss_cooldown: i64,
+) -> Result<Option<String>, anyhow::Error> {
+    let stream = resolve_stream_names_with_type(&trend.sql)
+        .ok()
+        .and_then(|tables| tables.first().map(|table| table.stream_name()))
+        .unwrap_or_default();
+    let mut payload = serde_json::json!({
+        "sql": trend.sql,
+        "p95_micros": trend.p95_micros,
+        "previous_p95_micros": trend.previous_p95_micros,
+        "change_percent": trend.change_percent,
+        "priority": "high",
+    });
+    if let Some(cost) = &trend.estimated_cost {
+        payload["estimated_cost"] = serde_json::to_value(cost)?;
+    }
+    if let Some(dashboard_id) = &trend.dashboard_id {
+        payload["source"] = "dashboard".into();
+        payload["dashboard_id"] = dashboard_id.as_str().into();
+        if let Some(panel_id) = &trend.panel_id {
+            payload["panel_id"] = panel_id.as_str().into();
+        }
+    }
+    Ok(recommendations::add(
+        &trend.org_id,
+        &stream,
+        LATENCY_REGRESSION_RECOMMENDATION,
+        &trend.fingerprint,
+        payload,
+        dismiss_cooldown,
+    )
+    .await?)
+}
+
+/// A field that the analysed queries of an org often filter on for equality, which makes it a
+/// good partition key of its stream. See [`partition_key_candidates`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PartitionKeyRecommendation {
+    pub org_id: String,
+    pub stream: String,
+    pub stream_type: StreamType,
+    pub field: String,
+    /// Number of analysed queries that filter on the field for equality.
+    pub query_hit_count: i64,
+}
+
+/// Counts, per org and stream, the `usage` queries that compare each column with a literal for
+/// equality, see [`equality_filter_columns`]. A fingerprint counts for all of its queries. Only
+/// queries of a single stream are counted, the stream of a column is ambiguous otherwise, and
+/// never the timestamp column. The most filtered on fields come first.
+fn partition_key_candidates(usage: &[UsageStat]) -> Vec<PartitionKeyRecommendation> {
+    let mut counts: HashMap<(String, StreamType, String, String), i64> = HashMap::new();
+    for stat in usage {
+        let Ok(tables) = resolve_stream_names_with_type(&stat.sql) else {
+            continue;
+        };
+        let [table] = tables.as_slice() else {
+            continue;
+        };
+        let stream_type = table.get_stream_type(StreamType::Logs);
+        for field in equality_filter_columns(&stat.sql) {
+            if field == TIMESTAMP_COL_NAME {
+                continue;
+            }
+            *counts
+                .entry((stat.org_id.clone(), stream_type, table.stream_name(), field))
+                .or_default() += stat.queries;
+        }
+    }
+    let mut candidates = counts
+        .into_iter()
+        .map(
+            |((org_id, stream_type, stream, field), query_hit_count)| PartitionKeyRecommendation {
+                org_id,
+                stream,
+                stream_type,
+                field,
+                query_hit_count,
+            },
+        )
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| {
+        b.query_hit_count
+            .cmp(&a.query_hit_count)
+            .then_with(|| (&a.org_id, &a.stream, &a.field).cmp(&(&b.org_id, &b.stream, &b.field)))
+    });
+    candidates
+}
+
+/// Returns true if `field` is part of the schema of `stream` but not one of its partition keys
+/// yet.
+fn is_new_partition_key(stream: &crate::common::meta::stream::Stream, field: &str) -> bool {
+    stream.schema.iter().any(|f| f.name == field)
+        && !stream
+            .settings
+            .partition_keys
+            .iter()
+            .any(|key| key.field == field)
+}
+
+/// Stores a partition key recommendation, unless it is suppressed, see [`recommendations::add`].
+async fn add_partition_key_recommendation(
+    recommendation: &PartitionKeyRecommendation,
+    dismiss_cooldown: i64,
+) -> Result<Option<String>, anyhow::Error> {
+    let payload = serde_json::json!({
+        "stream": recommendation.stream,
+        "stream_type": recommendation.stream_type.to_string(),
+        "field": recommendation.field,
+        "query_hit_count": recommendation.query_hit_count,
+    });
+    Ok(recommendations::add(
+        &recommendation.org_id,
+        &recommendation.stream,
+        PARTITION_KEY_RECOMMENDATION,
+        &recommendation.field,
+        payload,
+        dismiss_cooldown,
+    )
+    .await?)
+}
+
+/// Type of the recommendations to add their target field to the secondary index of the stream.
+pub const INDEX_RECOMMENDATION: &str = "index";
+
+/// Type of the recommendations to partition the stream by their target field.
+pub const PARTITION_KEY_RECOMMENDATION: &str = "partition_key";
+
+/// Returns why a recommendation of `recommendation_type` doesn't fit the type of `field`, if it
+/// doesn't. Indexes only help string fields, and partition keys are only worth it for categorical
+/// fields: strings, booleans and integers other than the timestamp. Recommendations of other
+/// types fit any field.
+fn field_type_mismatch(recommendation_type: &str, field: &Field) -> Option<String> {
+    let data_type = field.data_type();
+    let fits = match recommendation_type {
+        INDEX_RECOMMENDATION => matches!(
+            data_type,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+        ),
+        PARTITION_KEY_RECOMMENDATION => {
+            field.name() != TIMESTAMP_COL_NAME
+                && (data_type.is_integer()
+                    || matches!(
+                        data_type,
+                        DataType::Utf8
+                            | DataType::LargeUtf8
+                            | DataType::Utf8View
+                            | DataType::Boolean
+                    ))
+        }
+        _ => true,
+    };
+    (!fits).then(|| {
+        format!(
+            "Recommendations of type {recommendation_type} don't apply to field {} of type {data_type}",
+            field.name()
+        )
+    })
+}
+
+/// Checks that the target field of a recommendation is in the stream `schema` and that its type
+/// fits the recommendation. See [`field_type_mismatch`].
+fn check_target_field(recommendation: &Recommendation, schema: &Schema) -> Result<(), String> {
+    let field = schema
+        .field_with_name(&recommendation.target_field)
+        .map_err(|_| {
+            format!(
+                "Field {} is not in the schema of stream {}",
+                recommendation.target_field, recommendation.stream
+            )
+        })?;
+    match field_type_mismatch(&recommendation.recommendation_type, field) {
+        Some(mismatch) => Err(mismatch),
+        None => Ok(()),
+    }
+}
+
+/// Outcome of applying one recommendation. See [`apply_recommendations`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ApplyResult {
+    pub id: String,
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ApplyResult {
+    fn applied(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            applied: true,
+            error: None,
+        }
+    }
+
+    fn failed(id: &str, error: impl ToString) -> Self {
+        Self {
+            id: id.to_string(),
+            applied: false,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Applies the accepted index and partition key recommendations `ids` of an org to the settings
+/// of their streams, and marks them as applied. Each recommendation is applied on its own: one
+/// failing is reported in its result and doesn't stop the others.
+///
+/// A recommendation is only marked as applied once its stream settings are updated. If marking
+/// it fails, applying it again is harmless, as the settings already hold its target field.
+pub async fn apply_recommendations(org_id: &str, ids: &[String]) -> Vec<ApplyResult> {
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        results.push(apply_recommendation(org_id, id).await);
+    }
+    results
+}
+
+async fn apply_recommendation(org_id: &str, id: &str) -> ApplyResult {
+    let recommendation = match recommendations::get(org_id, id).await {
+        Ok(Some(recommendation)) => recommendation,
+        Ok(None) => return ApplyResult::failed(id, "Recommendation not found"),
+        Err(e) => return ApplyResult::failed(id, e),
+    };
+    let (stream_type, settings) = match settings_update(&recommendation) {
+        Ok(update) => update,
+        Err(e) => return ApplyResult::failed(id, e),
+    };
+    match infra::schema::get(org_id, &recommendation.stream, stream_type).await {
+        Ok(schema) => {
+            if let Err(e) = check_target_field(&recommendation, &schema) {
+                return ApplyResult::failed(id, e);
+            }
+        }
+        Err(e) => return ApplyResult::failed(id, e),
+    }
+    match update_stream_settings(org_id, &recommendation.stream, stream_type, settings).await {
+        Ok(resp) if resp.status().is_success() => {}
+        Ok(resp) => {
+            return ApplyResult::failed(
+                id,
+                format!(
+                    "Updating the stream settings failed with status {}",
+                    resp.status()
+                ),
+            );
+        }
+        Err(e) => return ApplyResult::failed(id, e),
+    }
+    match recommendations::update_status(org_id, id, RecommendationStatus::Applied).await {
+        Ok(StatusChange::Updated) => ApplyResult::applied(id),
+        Ok(update) => {
+            log::warn!(
+                "[QUERY_RECOMMENDATIONS] Applied recommendation {id} of org {org_id} but could not mark it as applied: {update:?}"
+            );
+            ApplyResult::applied(id)
+        }
+        Err(e) => {
+            log::error!(
+                "[QUERY_RECOMMENDATIONS] Applied recommendation {id} of org {org_id} but failed to mark it as applied: {e}"
+            );
+            ApplyResult::failed(id, e)
+        }
+    }
+}
+
+/// Returns the stream settings update that applies an accepted recommendation, and the type of
+/// the stream to update. The stream type is read from the `stream_type` of the payload, logs by
+/// default.
+fn settings_update(
+    recommendation: &Recommendation,
+) -> Result<(StreamType, UpdateStreamSettings), String> {
+    if recommendation.status != RecommendationStatus::Accepted {
+        return Err(format!(
+            "Only accepted recommendations can be applied, this one is {}",
+            recommendation.status
+        ));
+    }
+    let field = recommendation.target_field.clone();
+    let mut settings = UpdateStreamSettings::default();
+    match recommendation.recommendation_type.as_str() {
+        INDEX_RECOMMENDATION => settings.index_fields.add.push(field),
+        PARTITION_KEY_RECOMMENDATION => settings
+            .partition_keys
+            .add
+            .push(StreamPartition::new(&field)),
+        other => return Err(format!("Recommendations of type {other} can't be applied")),
+    }
+    let stream_type = recommendation
+        .payload
+        .get("stream_type")
+        .and_then(|v| v.as_str())
+        .map(StreamType::from)
+        .unwrap_or_default();
+    Ok((stream_type, settings))
+}
+
+/// Columns of a recommendations CSV export that come from the fields of the recommendations.
+/// They are followed by a `payload.<key>` column for each key of the payloads.
+const CSV_EXPORT_COLUMNS: [&str; 8] = [
+    "id",
+    "org_id",
+    "stream",
+    "recommendation_type",
+    "target_field",
+    "status",
+    "created_at",
+    "updated_at",
+];
+
+/// Serializes recommendations as CSV, one row per recommendation. The payload is flattened into a
+/// `payload.<key>` column for each top-level key found in any payload, sorted by key. String
+/// values are written as is, other values as JSON, and missing keys are left empty. A payload that
+/// is not an object goes into a single `payload` column.
+pub fn recommendations_to_csv(recommendations: &[Recommendation]) -> Result<String, anyhow::Error> {
+    let payloads = recommendations
+        .iter()
+        .map(|recommendation| flatten_payload(&recommendation.payload))
+        .collect::<Vec<_>>();
+    let payload_columns = payloads
+        .iter()
+        .flat_map(|payload| payload.keys().cloned())
+        .collect::<std::collections::BTreeSet<_>>();
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(
+        CSV_EXPORT_COLUMNS
+            .iter()
+            .map(|column| column.to_string())
+            .chain(payload_columns.iter().cloned()),
+    )?;
+    for (recommendation, payload) in recommendations.iter().zip(payloads) {
+        let fields = [
+            recommendation.id.clone(),
+            recommendation.org_id.clone(),
+            recommendation.stream.clone(),
+            recommendation.recommendation_type.clone(),
+            recommendation.target_field.clone(),
+            recommendation.status.to_string(),
+            recommendation.created_at.to_string(),
+            recommendation.updated_at.to_string(),
+        ];
+        let payload_fields = payload_columns
+            .iter()
+            .map(|column| payload.get(column).cloned().unwrap_or_default());
+        writer.write_record(fields.into_iter().chain(payload_fields))?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Flattens a recommendation payload into its CSV columns. See [`recommendations_to_csv`].
+fn flatten_payload(payload: &serde_json::Value) -> std::collections::BTreeMap<String, String> {
+    let cell = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.to_string(),
+        value => value.to_string(),
+    };
+    match payload {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| (format!("payload.{key}"), cell(value)))
+            .collect(),
+        serde_json::Value::Null => Default::default(),
+        value => [("payload".to_string(), cell(value))].into(),
+    }
+}
+
+/// Returns the uuid of the node that runs the recommendation job: the online alert manager with
+/// the smallest uuid.
+fn recommendation_leader(nodes: &[Node]) -> Option<&str> {
+    nodes.iter().map(|node| node.uuid.as_str()).min()
+}
+
+/// Returns true if this node runs the recommendation job, so the expensive searches and writes
+/// of a cycle happen on exactly one node of the cluster.
+async fn is_recommendation_leader() -> bool {
+    match cluster::get_cached_online_alert_manager_nodes().await {
+        Some(nodes) if !nodes.is_empty() => {
+            recommendation_leader(&nodes) == Some(LOCAL_NODE.uuid.as_str())
+        }
+        // If we can't get cached nodes, assume single node and run
+        _ => true,
+    }
+}
+
+pub async fn run() {
+    let cfg = get_config();
+    if !cfg.recommendations.enabled {
+        log::info!("[QUERY_RECOMMENDATIONS] Query recommendations are disabled");
+        return;
+    }
+    let query_recommendation_analysis_interval = cfg.recommendations.interval;
+    let mut usage_watermark = None;
+
+    spawn_pausable_job!(
+        "Query Optimization Recommendations",
+        query_recommendation_analysis_interval,
+        {
+            if !is_recommendation_leader().await {
+                log::debug!(
+                    "[QUERY_RECOMMENDATIONS] Node {} is not the recommendation leader, skipping",
+                    LOCAL_NODE.name
+                );
+                continue;
+            }
+            log::info!(
+                "[QUERY_RECOMMENDATIONS] Node {} is acting as the recommendation leader",
+                LOCAL_NODE.name
+            );
+            // each cycle analyses its own window
+            let ctx = QueryOptimizerContext::default().with_usage_watermark(usage_watermark);
+            let query_recommendation_service =
+                recommendation_service(ctx.clone(), cfg.recommendations.duration);
+            let _ = query_recommendation_service
+                .run()
+                .await
+                .inspect_err(|e| {
+                    log::error!(
+                        "Recommendation service stopped with an error: Error={:?}",
+                        e
+                    );
+                })
+                .inspect(|_| {
+                    log::warn!("Recommendation service quietly ended without an error!");
+                });
+            if let Err(e) = ctx.track_latency_trends(cfg.recommendations.duration).await {
+                log::error!("[QUERY_RECOMMENDATIONS] Failed to track query latency trends: {e}");
+            }
+            if let Err(e) = ctx
+                .recommend_partition_keys(cfg.recommendations.duration)
+                .await
+            {
+                log::error!("[QUERY_RECOMMENDATIONS] Failed to recommend partition keys: {e}");
+            }
+            usage_watermark = ctx.usage_watermark();
+        }
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use config::meta::search::SearchEventType;
+
+    use super::*;
+    use crate::service::search::utils::validate_time_range;
+
+    #[tokio::test]
+    async fn test_dry_run_skips_ingestion() {
+        let report = Arc::new(Mutex::new(RecommendationRunReport::default()));
+        let ctx = QueryOptimizerContext {
+            org_id: Some("default".to_string()),
+            report: Some(report.clone()),
+            dry_run: true,
+            ..Default::default()
+        };
+        let resp = ctx.ingest_recommendations(vec![]).await.unwrap();
+        assert_eq!(resp.status_code, 200);
+        assert_eq!(resp.message, "dry run");
+        assert!(report.lock().await.recommendations.is_empty());
+    }
+
+    #[test]
+    fn test_recommendation_key() {
+        let key = recommendation_key(
+            &serde_json::json!({"org_id": "default", "stream": "app", "recommendation_type": "index", "target_field": "status"}),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            key,
+            RecommendationKey {
+                org_id: "default".to_string(),
+                stream: "app".to_string(),
+                recommendation_type: "index".to_string(),
+                target_field: "status".to_string(),
+            }
+        );
+        // aliases, and the org of the run when the recommendation has none
+        let key = recommendation_key(
+            &serde_json::json!({"stream_name": "app", "type": "partition_key", "field": "host"}),
+            Some("other"),
+        )
+        .unwrap();
+        assert_eq!(key.org_id, "other");
+        assert_eq!(key.stream, "app");
+        assert_eq!(key.recommendation_type, "partition_key");
+        assert_eq!(key.target_field, "host");
+        assert!(
+            recommendation_key(
+                &serde_json::json!({"stream": "app", "type": "index", "field": ""}),
+                Some("default")
+            )
+            .is_none()
+        );
+        assert!(
+            recommendation_key(
+                &serde_json::json!({"stream": "app", "type": "index", "field": "status"}),
+                None
+            )
+            .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stored_recommendations_can_be_listed() {
+        use sea_orm::{ConnectionTrait, Schema};
+
+        infra::db::init().await.unwrap();
+        let client = infra::db::ORM_CLIENT
+            .get_or_init(infra::db::connect_to_orm)
+            .await;
+        let backend = client.get_database_backend();
+        let mut create = Schema::new(backend)
+            .create_table_from_entity(infra::table::entity::recommendations::Entity);
+        create.if_not_exists();
+        client.execute(backend.build(&create)).await.unwrap();
+
+        let org_id = &format!("store_recommendations_{}", now_micros());
+        let recommendation = serde_json::json!({
+            "stream": "app",
+            "recommendation_type": INDEX_RECOMMENDATION,
+            "target_field": "status",
+            "rationale": "filtered on, often",
+        });
+        let stored = store_recommendations(
+            vec![
+                recommendation.clone(),
+                serde_json::json!({"rationale": "no key"}),
+            ],
+            Some(org_id),
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stored, 1);
+
+        let listed =
+            recommendations::list_recommendations(org_id, Some(RecommendationStatus::Open), None)
+                .await
+                .unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].stream, "app");
+        assert_eq!(listed[0].recommendation_type, INDEX_RECOMMENDATION);
+        assert_eq!(listed[0].target_field, "status");
+        assert_eq!(listed[0].payload, recommendation);
+
+        // storing it again is deduplicated
+        let stored = store_recommendations(vec![recommendation], Some(org_id), 0)
+            .await
+            .unwrap();
+        assert_eq!(stored, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_run_returns_promptly() {
+        let (cancel, cancelled) = oneshot::channel();
+        let run = tokio::spawn(run_cancellable(
+            std::future::pending::<Result<(), anyhow::Error>>(),
+            cancelled,
+        ));
+        cancel.send(()).unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(1), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(result, Err(OnDemandRunError::Cancelled)));
+
+        // a finished run is not affected by a dropped sender
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        drop(cancel);
+        let result = run_cancellable(async { Ok(5) }, cancelled).await;
+        assert_eq!(result.unwrap(), 5);
+    }
+
+    #[test]
+    fn test_prefers_local_mode() {
+        assert!(prefers_local_mode(&Request::default()));
+        assert!(!prefers_local_mode(&Request {
+            regions: vec!["us".to_string()],
+            ..Default::default()
+        }));
+        assert!(!prefers_local_mode(&Request {
+            clusters: vec!["c1".to_string()],
+            ..Default::default()
+        }));
+        assert!(!prefers_local_mode(&Request {
+            local_mode: Some(false),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_routed_request() {
+        let regions = vec!["us".to_string()];
+        let clusters = vec!["c1".to_string()];
+
+        let request = routed_request(Request::default(), regions.clone(), clusters.clone());
+        assert_eq!(request.regions, regions);
+        assert_eq!(request.clusters, clusters);
+
+        let request = Request {
+            regions: vec!["eu".to_string()],
+            ..Default::default()
+        };
+        let request = routed_request(request, regions, clusters);
+        assert_eq!(request.regions, vec!["eu".to_string()]);
+        assert!(request.clusters.is_empty());
+
+        assert!(is_routable(&routed_request(
+            Request::default(),
+            vec![],
+            vec![]
+        )));
+    }
+
+    #[test]
+    fn test_search_cache() {
+        let request = Request {
+            query: config::meta::search::Query {
+                sql: "SELECT count(*) FROM \"usage\"".to_string(),
+                start_time: 1_000_000,
+                end_time: 2_000_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let rounding = 60 * 1_000_000;
+        let key = search_cache_key("test_search_cache", &request, rounding);
+        assert_eq!(
+            key,
+            search_cache_key("test_search_cache", &request, rounding)
+        );
+        assert_ne!(key, search_cache_key("other_org", &request, rounding));
+        let now = now_micros();
+        cache_search(key.clone(), Response::new(0, 10), 60, now);
+
+        // identical query within the ttl hits the cache
+        assert!(get_cached_search(&key, 60, now + 1_000_000).is_some());
+        assert!(get_cached_search(&key, 0, now).is_none());
+
+        // so does a window moved within the same rounding interval, e.g. by a run started a few
+        // seconds later
+        let mut later = request.clone();
+        later.query.start_time += 10_000_000;
+        later.query.end_time += 10_000_000;
+        assert_eq!(key, search_cache_key("test_search_cache", &later, rounding));
+        assert_ne!(
+            search_cache_key("test_search_cache", &request, 0),
+            search_cache_key("test_search_cache", &later, 0)
+        );
+
+        // a changed time window misses
+        let mut moved = request.clone();
+        moved.query.end_time += 2 * rounding;
+        let moved_key = search_cache_key("test_search_cache", &moved, rounding);
+        assert_ne!(key, moved_key);
+        assert!(get_cached_search(&moved_key, 60, now).is_none());
+
+        // expired entries are dropped
+        assert!(get_cached_search(&key, 60, now + 61_000_000).is_none());
+        assert!(get_cached_search(&key, 60, now).is_none());
+    }
+
+    #[test]
+    fn test_search_cache_is_bounded() {
+        let cache = RwHashMap::default();
+        let now = 1_000 * 1_000_000;
+        cache_search_in(
+            &cache,
+            "expired".to_string(),
+            Response::new(0, 10),
+            60,
+            now,
+            3,
+        );
+        // entries that expired are swept by the next insert
+        let later = now + 61_000_000;
+        for i in 0..3 {
+            cache_search_in(
+                &cache,
+                format!("key_{i}"),
+                Response::new(0, 10),
+                60,
+                later + i,
+                3,
+            );
+        }
+        assert!(!cache.contains_key("expired"));
+        assert_eq!(cache.len(), 3);
+
+        // the oldest entry makes room for a new one
+        cache_search_in(
+            &cache,
+            "key_3".to_string(),
+            Response::new(0, 10),
+            60,
+            later + 3,
+            3,
+        );
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.contains_key("key_0"));
+        assert!(cache.contains_key("key_3"));
+
+        // caching a key again replaces it without evicting another one
+        cache_search_in(
+            &cache,
+            "key_3".to_string(),
+            Response::new(0, 10),
+            60,
+            later + 4,
+            3,
+        );
+        assert_eq!(cache.len(), 3);
+        assert!(cache.contains_key("key_1"));
+    }
+
+    #[test]
+    fn test_split_config_list() {
+        assert!(split_config_list("").is_empty());
+        assert_eq!(
+            split_config_list(" us, ,eu "),
+            vec!["us".to_string(), "eu".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_system_search_request() {
+        let request = Request {
+            search_type: Some(SearchEventType::UI),
+            ..Default::default()
+        };
+        let (trace_id, request) = system_search_request(request, 30, true);
+        assert!(!trace_id.is_empty());
+        assert_eq!(request.timeout, 30);
+        assert_eq!(request.search_type, Some(SearchEventType::Other));
+        assert_eq!(request.search_priority, SearchPriority::Low);
+        assert_eq!(
+            request
+                .search_event_context
+                .unwrap()
+                .system_source
+                .as_deref(),
+            Some(RECOMMENDATION_SEARCH_SOURCE)
+        );
+
+        let request = Request {
+            timeout: 5,
+            ..Default::default()
+        };
+        let (_, request) = system_search_request(request, 30, false);
+        assert_eq!(request.timeout, 5);
+        assert_eq!(request.search_priority, SearchPriority::Normal);
+    }
+
+    #[test]
+    fn test_stream_filter_searches() {
+        let request = |sql: &str| Request {
+            query: config::meta::search::Query {
+                sql: sql.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(is_usage_search(
+            META_ORG_ID,
+            &request("SELECT request_body FROM usage")
+        ));
+        assert!(!is_usage_search(
+            "default",
+            &request("SELECT request_body FROM usage")
+        ));
+        assert!(!is_usage_search(
+            META_ORG_ID,
+            &request("SELECT DISTINCT host FROM default")
+        ));
+
+        assert!(sql_touches_stream(
+            "SELECT * FROM a JOIN b ON a.id = b.id",
+            StreamType::Logs,
+            "b"
+        ));
+        assert!(sql_touches_stream(
+            r#"SELECT * FROM "metrics"."cpu""#,
+            StreamType::Metrics,
+            "cpu"
+        ));
+        assert!(!sql_touches_stream(
+            r#"SELECT * FROM "metrics"."cpu""#,
+            StreamType::Logs,
+            "cpu"
+        ));
+        assert!(!sql_touches_stream("SELECT", StreamType::Logs, "a"));
+    }
+
+    #[test]
+    fn test_filter_usage_hits() {
+        let resp = Response {
+            hits: vec![
+                serde_json::json!({"request_body": "SELECT * FROM app", "stream_name": "app"}),
+                serde_json::json!({"request_body": "SELECT * FROM other JOIN app ON true"}),
+                serde_json::json!({"request_body": "SELECT * FROM other", "stream_name": "other"}),
+                serde_json::json!({"request_body": "not sql", "stream_name": "app", "stream_type": "logs"}),
+                serde_json::json!({"request_body": "not sql", "stream_name": "app", "stream_type": "traces"}),
+                serde_json::json!({"total": 3}),
+            ],
+            total: 6,
+            ..Default::default()
+        };
+        let resp = filter_usage_hits(resp, StreamType::Logs, "app");
+        assert_eq!(resp.total, 4);
+        assert_eq!(
+            resp.hits,
+            vec![
+                serde_json::json!({"request_body": "SELECT * FROM app", "stream_name": "app"}),
+                serde_json::json!({"request_body": "SELECT * FROM other JOIN app ON true"}),
+                serde_json::json!({"request_body": "not sql", "stream_name": "app", "stream_type": "logs"}),
+                serde_json::json!({"total": 3}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_excluded_stream() {
+        let patterns = vec!["usage".to_string(), "query_*".to_string()];
+        assert!(is_excluded_stream("usage", &patterns));
+        assert!(is_excluded_stream("query_recommendations", &patterns));
+        assert!(!is_excluded_stream("usage_archive", &patterns));
+        assert!(!is_excluded_stream("default", &patterns));
+        assert!(!is_excluded_stream("usage", &[]));
+    }
+
+    #[test]
+    fn test_has_enough_records() {
+        let tiny = StreamStats {
+            doc_num: 12,
+            ..Default::default()
+        };
+        let large = StreamStats {
+            doc_num: 50_000,
+            ..Default::default()
+        };
+        // a tiny stream is never handed to the engine, so it gets no recommendations
+        assert!(!has_enough_records("default", "tiny", &tiny, 10_000));
+        assert!(has_enough_records("default", "large", &large, 10_000));
+        assert!(has_enough_records("default", "exact", &large, 50_000));
+        assert!(has_enough_records("default", "tiny", &tiny, 0));
+        assert!(has_enough_records(
+            "default",
+            "empty",
+            &StreamStats::default(),
+            0
+        ));
+    }
+
+    #[test]
+    fn test_excluded_streams_only_apply_to_meta_org() {
+        assert!(excluded_streams("default").is_empty());
+        assert!(excluded_streams(META_ORG_ID).contains(&"usage".to_string()));
+    }
+
+    #[test]
+    fn test_try_start_manual_run() {
+        let now = now_micros();
+        assert!(try_start_manual_run("rate_limited_org", now, 300).is_ok());
+        // a second trigger right away is rejected until the cooldown has passed
+        assert_eq!(
+            try_start_manual_run("rate_limited_org", now + 1_000_000, 300),
+            Err(299)
+        );
+        assert_eq!(
+            try_start_manual_run("rate_limited_org", now + 1, 300),
+            Err(300)
+        );
+        // other orgs are limited separately
+        assert!(try_start_manual_run("other_rate_limited_org", now, 300).is_ok());
+        assert!(try_start_manual_run("rate_limited_org", now + 300_000_000, 300).is_ok());
+        // no cooldown, no limit
+        assert!(try_start_manual_run("unlimited_org", now, 0).is_ok());
+        assert!(try_start_manual_run("unlimited_org", now, 0).is_ok());
+
+        // a failed run doesn't hold back the next one
+        assert!(try_start_manual_run("failed_run_org", now, 300).is_ok());
+        abort_manual_run("failed_run_org", now);
+        assert!(try_start_manual_run("failed_run_org", now + 1_000_000, 300).is_ok());
+        // unless another run started since then
+        abort_manual_run("failed_run_org", now);
+        assert!(try_start_manual_run("failed_run_org", now + 2_000_000, 300).is_err());
+    }
+
+    #[test]
+    fn test_recommendations_to_csv() {
+        let recommendation = |id: &str, payload| Recommendation {
+            id: id.to_string(),
+            org_id: "default".to_string(),
+            stream: "app".to_string(),
+            recommendation_type: INDEX_RECOMMENDATION.to_string(),
+            target_field: "status".to_string(),
+            payload,
+            status: RecommendationStatus::Open,
+            created_at: 1,
+            updated_at: 2,
+        };
+        let recommendations = vec![
+            recommendation(
+                "1",
+                serde_json::json!({"rationale": "filtered on, often", "queries": 12}),
+            ),
+            recommendation(
+                "2",
+                serde_json::json!({"stream_type": "logs", "fields": ["a", "b"]}),
+            ),
+        ];
+        assert_eq!(
+            recommendations_to_csv(&recommendations).unwrap(),
+            format!(
+                "id,org_id,stream,recommendation_type,target_field,status,created_at,updated_at,payload.fields,payload.queries,payload.rationale,payload.stream_type\n\
+                 1,default,app,{INDEX_RECOMMENDATION},status,open,1,2,,12,\"filtered on, often\",\n\
+                 2,default,app,{INDEX_RECOMMENDATION},status,open,1,2,\"[\"\"a\"\",\"\"b\"\"]\",,,logs\n"
+            )
+        );
+
+        // no recommendations, only the header
+        assert_eq!(
+            recommendations_to_csv(&[]).unwrap(),
+            "id,org_id,stream,recommendation_type,target_field,status,created_at,updated_at\n"
+        );
+    }
+
+    #[test]
+    fn test_flatten_payload() {
+        assert!(flatten_payload(&serde_json::Value::Null).is_empty());
+        assert_eq!(
+            flatten_payload(&serde_json::json!("text")),
+            [("payload".to_string(), "text".to_string())].into()
+        );
+        assert_eq!(
+            flatten_payload(&serde_json::json!({"nested": {"a": 1}})),
+            [("payload.nested".to_string(), r#"{"a":1}"#.to_string())].into()
+        );
+    }
+
+    #[test]
+    fn test_settings_update() {
+        let recommendation = |recommendation_type: &str, status| Recommendation {
+            id: "1".to_string(),
+            org_id: "default".to_string(),
+            stream: "app".to_string(),
+            recommendation_type: recommendation_type.to_string(),
+            target_field: "status".to_string(),
+            payload: serde_json::json!({}),
+            status,
+            created_at: 0,
+            updated_at: 0,
+        };
+
+        let (stream_type, settings) = settings_update(&recommendation(
+            INDEX_RECOMMENDATION,
+            RecommendationStatus::Accepted,
+        ))
+        .unwrap();
+        assert_eq!(stream_type, StreamType::Logs);
+        assert_eq!(settings.index_fields.add, vec!["status".to_string()]);
+        assert!(settings.partition_keys.add.is_empty());
+
+        let mut partition_key =
+            recommendation(PARTITION_KEY_RECOMMENDATION, RecommendationStatus::Accepted);
+        partition_key.payload = serde_json::json!({"stream_type": "traces"});
+        let (stream_type, settings) = settings_update(&partition_key).unwrap();
+        assert_eq!(stream_type, StreamType::Traces);
+        assert_eq!(
+            settings.partition_keys.add,
+            vec![StreamPartition::new("status")]
+        );
+        assert!(settings.index_fields.add.is_empty());
+
+        // only accepted recommendations of known types apply
+        assert!(
+            settings_update(&recommendation(
+                INDEX_RECOMMENDATION,
+                RecommendationStatus::Open
+            ))
+            .is_err()
+        );
+        assert!(
+            settings_update(&recommendation(
+                INDEX_RECOMMENDATION,
+                RecommendationStatus::Applied
+            ))
+            .is_err()
+        );
+        assert!(settings_update(&recommendation("fts", RecommendationStatus::Accepted)).is_err());
+    }
+
+    #[test]
+    fn test_check_target_field_filters_by_type() {
+        let schema = Schema::new(vec![
+            Field::new(TIMESTAMP_COL_NAME, DataType::Int64, false),
+            Field::new("status", DataType::Utf8, true),
+            Field::new("code", DataType::Int32, true),
+            Field::new("ok", DataType::Boolean, true),
+            Field::new("took", DataType::Float64, true),
+            Field::new(
+                "seen_at",
+                DataType::Timestamp(arrow_schema::TimeUnit::Microsecond, None),
+                true,
+            ),
+        ]);
+        let check = |recommendation_type: &str, field: &str| {
+            let recommendation = Recommendation {
+                id: "1".to_string(),
+                org_id: "default".to_string(),
+                stream: "app".to_string(),
+                recommendation_type: recommendation_type.to_string(),
+                target_field: field.to_string(),
+                payload: serde_json::json!({}),
+                status: RecommendationStatus::Accepted,
+                created_at: 0,
+                updated_at: 0,
+            };
+            check_target_field(&recommendation, &schema)
+        };
+        let fits =
+            |recommendation_type: &str, field: &str| check(recommendation_type, field).is_ok();
+
+        // only string fields are indexed
+        assert!(fits(INDEX_RECOMMENDATION, "status"));
+        for field in ["code", "ok", "took", "seen_at", TIMESTAMP_COL_NAME] {
+            assert!(!fits(INDEX_RECOMMENDATION, field), "{field}");
+        }
+
+        // only categorical fields partition the stream
+        for field in ["status", "code", "ok"] {
+            assert!(fits(PARTITION_KEY_RECOMMENDATION, field), "{field}");
+        }
+        for field in ["took", "seen_at", TIMESTAMP_COL_NAME] {
+            assert!(!fits(PARTITION_KEY_RECOMMENDATION, field), "{field}");
+        }
+
+        assert!(fits(LATENCY_REGRESSION_RECOMMENDATION, "took"));
+        assert_eq!(
+            check(INDEX_RECOMMENDATION, "missing"),
+            Err("Field missing is not in the schema of stream app".to_string())
+        );
+        assert_eq!(
+            check(INDEX_RECOMMENDATION, "took"),
+            Err(
+                "Recommendations of type index don't apply to field took of type Float64"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_recommendation_leader() {
+        let node = |uuid: &str| Node {
+            uuid: uuid.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(recommendation_leader(&[]), None);
+        assert_eq!(
+            recommendation_leader(&[node("c"), node("a"), node("b")]),
+            Some("a")
+        );
+    }
+
+    #[test]
+    fn test_recommendation_window() {
+        let now = 1_700_000_000_000_000;
+        assert_eq!(
+            recommendation_window(now, 3600 * 1_000_000),
+            (now - 3600 * 1_000_000, now)
+        );
+        // windows shorter than the minimum are widened
+        assert_eq!(
+            recommendation_window(now, 1_000_000),
+            (now - MIN_RECOMMENDATION_WINDOW, now)
+        );
+        assert_eq!(
+            recommendation_window(now, 0),
+            (now - MIN_RECOMMENDATION_WINDOW, now)
+        );
+        assert_eq!(
+            recommendation_window(now, -5),
+            (now - MIN_RECOMMENDATION_WINDOW, now)
+        );
+        // windows reaching before the epoch start at it, however large
+        assert_eq!(recommendation_window(now, now + 1), (0, now));
+        assert_eq!(recommendation_window(now, i64::MAX), (0, now));
+        // every window is a valid search time range
+        for duration in [-5, 0, 1_000_000, 3600 * 1_000_000, i64::MAX] {
+            let (start, end) = recommendation_window(now, duration);
+            assert!(validate_time_range(start, end).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_interval_micros() {
+        assert_eq!(interval_micros(60), 3600 * 1_000_000);
+        assert_eq!(interval_micros(1), 60_000_000);
+        assert_eq!(interval_micros(0), 0);
+        let now = 1_700_000_000_000_000;
+        assert_eq!(
+            recommendation_window(now, interval_micros(60)),
+            (now - 3_600_000_000, now)
+        );
+        // huge intervals don't overflow
+        assert_eq!(interval_micros(i64::MAX), i64::MAX);
+        assert_eq!(interval_micros(i64::MAX / 60_000_000 + 1), i64::MAX);
+        assert_eq!(interval_micros(i64::MIN), i64::MIN);
+        assert_eq!(
+            recommendation_window(now, interval_micros(i64::MAX)),
+            (0, now)
+        );
+    }
+
+    #[test]
+    fn test_next_run_at() {
+        let now = 1_700_000_000_000_000;
+        assert_eq!(next_run_at(now, 30, 0), now + 30 * 1_000_000);
+        assert_eq!(next_run_at(now, 0, 0), now + 1_000_000);
+        for _ in 0..100 {
+            let next = next_run_at(now, 100, 10);
+            assert!((now + 90 * 1_000_000..=now + 110 * 1_000_000).contains(&next));
+        }
+    }
+
+    #[test]
+    fn test_jittered() {
+        assert_eq!(jittered(100_000_000, 10, 0.0), 100_000_000);
+        assert_eq!(jittered(100_000_000, 10, 1.0), 110_000_000);
+        assert_eq!(jittered(100_000_000, 10, -1.0), 90_000_000);
+        assert_eq!(jittered(100_000_000, 0, 1.0), 100_000_000);
+        // never earlier than a second from now
+        assert_eq!(jittered(1_000_000, 100, -1.0), 1_000_000);
+    }
+
+    #[test]
+    fn test_backoff_grows_on_failures_and_resets_on_success() {
+        let mut data = RecommendationTriggerData::from_trigger_data("");
+        assert_eq!(backoff_delay(3600, data.consecutive_failures, 86400), 3600);
+
+        let mut delays = vec![];
+        for _ in 0..6 {
+            data.record_run(true, 0, 0);
+            delays.push(backoff_delay(3600, data.consecutive_failures, 86400));
+        }
+        assert_eq!(delays, vec![7200, 14400, 28800, 57600, 86400, 86400]);
+
+        let data_str = data.to_json_string();
+        let mut data = RecommendationTriggerData::from_trigger_data(&data_str);
+        assert_eq!(data.consecutive_failures, 6);
+        data.record_run(false, 0, 0);
+        assert_eq!(data.consecutive_failures, 0);
+        assert_eq!(backoff_delay(3600, data.consecutive_failures, 86400), 3600);
+
+        // no backoff when turned off, and the cap never shortens the interval
+        assert_eq!(backoff_delay(3600, 3, 0), 3600);
+        assert_eq!(backoff_delay(3600, 3, 60), 3600);
+        assert_eq!(backoff_delay(3600, u32::MAX, 86400), 86400);
+    }
+
+    #[test]
+    fn test_breaker_transitions() {
+        let cooldown = 600;
+        let mut data = RecommendationTriggerData::from_trigger_data("");
+        assert_eq!(data.breaker_state(0, cooldown), BreakerState::Closed);
+
+        // closed until the third failure in a row
+        data.record_run(true, 1_000_000, 3);
+        data.record_run(true, 2_000_000, 3);
+        assert_eq!(
+            data.breaker_state(2_000_000, cooldown),
+            BreakerState::Closed
+        );
+        data.record_run(true, 3_000_000, 3);
+        assert_eq!(data.breaker_state(3_000_000, cooldown), BreakerState::Open);
+        assert_eq!(data.breaker_closes_at(cooldown), Some(603_000_000));
+
+        // the state survives the trigger data round trip
+        let data_str = data.to_json_string();
+        let mut data = RecommendationTriggerData::from_trigger_data(&data_str);
+        assert_eq!(
+            data.breaker_state(602_999_999, cooldown),
+            BreakerState::Open
+        );
+        assert_eq!(
+            data.breaker_state(603_000_000, cooldown),
+            BreakerState::HalfOpen
+        );
+
+        // a failed half open run opens it again for a whole cooldown
+        data.record_run(true, 604_000_000, 3);
+        assert_eq!(
+            data.breaker_state(604_000_000, cooldown),
+            BreakerState::Open
+        );
+        assert_eq!(
+            data.breaker_state(1_203_999_999, cooldown),
+            BreakerState::Open
+        );
+        assert_eq!(
+            data.breaker_state(1_204_000_000, cooldown),
+            BreakerState::HalfOpen
+        );
+
+        // a successful half open run closes it
+        data.record_run(false, 1_205_000_000, 3);
+        assert_eq!(data.consecutive_failures, 0);
+        assert_eq!(
+            data.breaker_state(1_205_000_000, cooldown),
+            BreakerState::Closed
+        );
+        assert_eq!(data.breaker_closes_at(cooldown), None);
+        assert!(!data.to_json_string().contains("breaker_opened_at"));
+
+        // never opens when turned off
+        let mut data = RecommendationTriggerData::from_trigger_data("");
+        for _ in 0..10 {
+            data.record_run(true, 1_000_000, 0);
+        }
+        assert_eq!(
+            data.breaker_state(1_000_000, cooldown),
+            BreakerState::Closed
+        );
+    }
+
+    /// Serves a fixed dataset to the engine: the seeded streams, and for a search the seeded hits
+    /// of every stream its SQL reads. Hits are returned as they are, whatever the SQL computes,
+    /// except that hits with a `_timestamp` outside the time range of the search are left out.
+    #[derive(Default)]
+    struct StubSearcher {
+        streams: Vec<crate::common::meta::stream::Stream>,
+        hits: hashbrown::HashMap<String, Vec<serde_json::Value>>,
+        /// SQL of the searches that reached the stub.
+        searches: Arc<std::sync::Mutex<Vec<String>>>,
+        /// Stream and fields of the distinct values computed by the stub.
+        distinct_lookups: Arc<std::sync::Mutex<Vec<(String, Vec<String>)>>>,
+    }
+
+    /// Returns true if a seeded hit is within `[start_time, end_time)`, or has no `_timestamp`.
+    /// An `end_time` of 0 takes every hit.
+    fn stub_hit_in_range(hit: &serde_json::Value, start_time: i64, end_time: i64) -> bool {
+        hit.get(TIMESTAMP_COL_NAME)
+            .and_then(|v| v.as_i64())
+            .is_none_or(|ts| end_time == 0 || (start_time..end_time).contains(&ts))
+    }
+
+    impl StubSearcher {
+        fn with_stream(mut self, name: &str, doc_num: i64, hits: Vec<serde_json::Value>) -> Self {
+            self.streams.push(crate::common::meta::stream::Stream {
+                name: name.to_string(),
+                storage_type: "local".to_string(),
+                stream_type: StreamType::Logs,
+                stats: StreamStats {
+                    doc_num,
+                    ..Default::default()
+                },
+                schema: vec![],
+                uds_schema: vec![],
+                settings: Default::default(),
+                metrics_meta: None,
+                total_fields: 0,
+                pattern_associations: vec![],
+                is_derived: None,
+            });
+            self.hits.insert(name.to_string(), hits);
+            self
+        }
+    }
+
+    impl Searcher for StubSearcher {
+        fn list_streams(
+            &self,
+            _org_id: String,
+            stream_type: Option<StreamType>,
+        ) -> Pin<Box<dyn Future<Output = Vec<crate::common::meta::stream::Stream>> + Send>>
+        {
+            let streams = self
+                .streams
+                .iter()
+                .filter(|stream| stream_type.is_none_or(|t| stream.stream_type == t))
+                .cloned()
+                .collect();
+            Box::pin(async move { streams })
+        }
+
+        fn search(
+            &self,
+            _trace_id: String,
+            _org_id: String,
+            _stream_type: StreamType,
+            request: Request,
+        ) -> Pin<Box<dyn Future<Output = Result<Response, anyhow::Error>> + Send>> {
+            self.searches
+                .lock()
+                .unwrap()
+                .push(request.query.sql.clone());
+            let mut resp = Response::new(request.query.from, request.query.size);
+            for table in resolve_stream_names_with_type(&request.query.sql).unwrap_or_default() {
+                for hit in self.hits.get(&table.stream_name()).into_iter().flatten() {
+                    if stub_hit_in_range(hit, request.query.start_time, request.query.end_time) {
+                        resp.add_hit(hit);
+                    }
+                }
+            }
+            Box::pin(async move { Ok(resp) })
+        }
+
+        /// Counts the distinct values a field has in the seeded hits of the stream, as strings,
+        /// and samples the first ones. Fields no hit has are omitted.
+        fn distinct_values(
+            &self,
+            _org_id: String,
+            _stream_type: StreamType,
+            stream_name: String,
+            field_names: Vec<String>,
+            start_time: i64,
+            end_time: i64,
+            options: DistinctOptions,
+        ) -> Pin<
+            Box<dyn Future<Output = Result<HashMap<String, FieldDistinct>, anyhow::Error>> + Send>,
+        > {
+            self.distinct_lookups
+                .lock()
+                .unwrap()
+                .push((stream_name.clone(), field_names.clone()));
+            let hits = self
+                .hits
+                .get(&stream_name)
+                .into_iter()
+                .flatten()
+                .filter(|hit| stub_hit_in_range(hit, start_time, end_time))
+                .collect::<Vec<_>>();
+            let mut values = HashMap::new();
+            for field_name in field_names {
+                let mut distinct: Vec<String> = vec![];
+                for value in hits.iter().filter_map(|hit| hit.get(&field_name)) {
+                    let value = match value {
+                        serde_json::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    };
+                    if !distinct.contains(&value) {
+                        distinct.push(value);
+                    }
+                }
+                if distinct.is_empty() {
+                    continue;
+                }
+                values.insert(
+                    field_name,
+                    FieldDistinct {
+                        distinct_count: distinct.len() as u64,
+                        sample_values: distinct.into_iter().take(options.sample_values).collect(),
+                    },
+                );
+            }
+            Box::pin(async move { Ok(values) })
+        }
+
+        /// Estimates that a search scans every record of the streams its SQL reads, and fails
+        /// when one of them doesn't exist.
+        fn estimate(
+            &self,
+            _org_id: String,
+            _stream_type: StreamType,
+            request: Request,
+        ) -> Pin<Box<dyn Future<Output = Result<QueryCostEstimate, anyhow::Error>> + Send>>
+        {
+            let mut cost = QueryCostEstimate::default();
+            let mut missing = None;
+            for table in resolve_stream_names_with_type(&request.query.sql).unwrap_or_default() {
+                let stream_name = table.stream_name();
+                match self
+                    .streams
+                    .iter()
+                    .find(|stream| stream.name == stream_name)
+                {
+                    Some(stream) => {
+                        cost.streams += 1;
+                        cost.records += stream.stats.doc_num as usize;
+                        cost.partitions += 1;
+                    }
+                    None => missing = Some(stream_name),
+                }
+            }
+            Box::pin(async move {
+                match missing {
+                    Some(stream_name) => Err(anyhow::anyhow!("stream {stream_name} not found")),
+                    None => Ok(cost),
+                }
+            })
+        }
+    }
+
+    /// A usage stream recording searches that filter `app` on `status`, plus the streams read.
+    fn stub_dataset() -> StubSearcher {
+        let usage = (0..20)
+            .map(|i| {
+                serde_json::json!({
+                    "_timestamp": now_micros() - i * 1_000_000,
+                    "event": "Search",
+                    "org_id": "harness_org",
+                    "stream_name": "app",
+                    "stream_type": "logs",
+                    "request_body": format!("SELECT * FROM app WHERE status = {}", 200 + i % 3),
+                    "response_time": 2.5,
+                    "scan_records": 50_000,
+                })
+            })
+            .collect();
+        let app = (0..50)
+            .map(|i| serde_json::json!({"status": 200 + i % 3, "host": format!("host-{i}")}))
+            .collect();
+        StubSearcher::default()
+            .with_stream("usage", 50_000, usage)
+            .with_stream("app", 50_000, app)
+            .with_stream("tiny", 5, vec![serde_json::json!({"status": 200})])
+    }
+
+    #[tokio::test]
+    async fn test_stub_searcher_serves_engine_reads() {
+        let searcher = Arc::new(stub_dataset());
+        let report = Arc::new(Mutex::new(RecommendationRunReport::default()));
+        let ctx = QueryOptimizerContext {
+            org_id: Some("harness_org".to_string()),
+            report: Some(report.clone()),
+            dry_run: true,
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone());
+
+        // the stream below the record threshold is left out
+        let streams = ctx
+            .get_all_stream_settings("harness_org".to_string())
+            .await
+            .unwrap();
+        let mut names = streams
+            .iter()
+            .map(|stream| stream.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["app".to_string(), "usage".to_string()]);
+
+        let request = Request {
+            query: config::meta::search::Query {
+                sql: "SELECT * FROM app".to_string(),
+                end_time: now_micros(),
+                size: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let resp = ctx
+            .search("harness_org".to_string(), request)
+            .await
+            .unwrap();
+        assert_eq!(resp.hits.len(), 50);
+        assert_eq!(
+            *searcher.searches.lock().unwrap(),
+            vec!["SELECT * FROM app".to_string()]
+        );
+        assert_eq!(report.lock().await.queries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_search_runs_once_per_run() {
+        let searcher = Arc::new(stub_dataset());
+        let ctx = QueryOptimizerContext {
+            org_id: Some("harness_memo_org".to_string()),
+            dry_run: true,
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone());
+        let end_time = now_micros();
+        let request = |sql: &str| Request {
+            query: config::meta::search::Query {
+                sql: sql.to_string(),
+                start_time: end_time - 3600 * 1_000_000,
+                end_time,
+                size: 100,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let distinct = "SELECT COUNT(DISTINCT status) FROM app";
+        for _ in 0..3 {
+            ctx.search("harness_memo_org".to_string(), request(distinct))
+                .await
+                .unwrap();
+        }
+        ctx.search("harness_memo_org".to_string(), request("SELECT * FROM app"))
+            .await
+            .unwrap();
+        assert_eq!(
+            *searcher.searches.lock().unwrap(),
+            vec![distinct.to_string(), "SELECT * FROM app".to_string()]
+        );
+        assert_eq!(ctx.searches.len(), 2);
+        // clones of the context share the searches of the run
+        assert_eq!(ctx.clone().searches.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_distinct_values_are_attached_to_recommendations() {
+        let searcher = Arc::new(stub_dataset());
+        let end_time = now_micros();
+        let ctx = QueryOptimizerContext {
+            org_id: Some("harness_org".to_string()),
+            window: Some((end_time - 3600 * 1_000_000, end_time)),
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone());
+        let recommendation = |field: &str| {
+            serde_json::json!({
+                "stream": "app",
+                "recommendation_type": INDEX_RECOMMENDATION,
+                "target_field": field,
+            })
+        };
+        let mut recommendations = vec![
+            recommendation("status"),
+            recommendation("host"),
+            recommendation("missing"),
+            serde_json::json!({"rationale": "no key"}),
+        ];
+        let sampling = DistinctOptions {
+            sample_values: 2,
+            ..Default::default()
+        };
+        ctx.attach_distinct_values(&mut recommendations, sampling)
+            .await;
+        assert_eq!(recommendations[0]["distinct_count"], 3);
+        assert_eq!(
+            recommendations[0]["sample_values"],
+            serde_json::json!(["200", "201"])
+        );
+        assert_eq!(recommendations[1]["distinct_count"], 50);
+        assert_eq!(
+            recommendations[1]["sample_values"],
+            serde_json::json!(["host-0", "host-1"])
+        );
+        // fields missing from the stream and recommendations without a key are left as they are
+        assert_eq!(recommendations[2], recommendation("missing"));
+        assert_eq!(
+            recommendations[3],
+            serde_json::json!({"rationale": "no key"})
+        );
+        // the fields of a stream are computed together
+        assert_eq!(
+            *searcher.distinct_lookups.lock().unwrap(),
+            vec![(
+                "app".to_string(),
+                vec![
+                    "status".to_string(),
+                    "host".to_string(),
+                    "missing".to_string()
+                ]
+            )]
+        );
+
+        // without sampling only the distinct counts are attached
+        let mut recommendations = vec![recommendation("status")];
+        ctx.attach_distinct_values(&mut recommendations, DistinctOptions::default())
+            .await;
+        assert_eq!(recommendations[0]["distinct_count"], 3);
+        assert!(recommendations[0].get("sample_values").is_none());
+
+        // a count the engine already set is kept
+        let mut recommendations = vec![serde_json::json!({
+            "stream": "app",
+            "recommendation_type": INDEX_RECOMMENDATION,
+            "target_field": "status",
+            "distinct_count": 7,
+        })];
+        ctx.attach_distinct_values(&mut recommendations, sampling)
+            .await;
+        assert_eq!(recommendations[0]["distinct_count"], 7);
+        assert_eq!(
+            recommendations[0]["sample_values"],
+            serde_json::json!(["200", "201"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_distinct_values_are_cached_per_run() {
+        let searcher = Arc::new(stub_dataset());
+        let end_time = now_micros();
+        let ctx = QueryOptimizerContext {
+            org_id: Some("harness_org".to_string()),
+            window: Some((end_time - 3600 * 1_000_000, end_time)),
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone());
+        let recommendation = |field: &str| {
+            serde_json::json!({
+                "stream": "app",
+                "recommendation_type": INDEX_RECOMMENDATION,
+                "target_field": field,
+            })
+        };
+
+        let mut recommendations = vec![recommendation("status")];
+        ctx.attach_distinct_values(&mut recommendations, DistinctOptions::default())
+            .await;
+        assert_eq!(recommendations[0]["distinct_count"], 3);
+        assert_eq!(searcher.distinct_lookups.lock().unwrap().len(), 1);
+
+        // the second lookup of the field is served from the cache, also through a clone of the
+        // context, and only the new field reaches the searcher
+        let mut recommendations = vec![recommendation("status"), recommendation("host")];
+        ctx.clone()
+            .attach_distinct_values(&mut recommendations, DistinctOptions::default())
+            .await;
+        assert_eq!(recommendations[0]["distinct_count"], 3);
+        assert_eq!(recommendations[1]["distinct_count"], 50);
+        assert_eq!(
+            searcher.distinct_lookups.lock().unwrap()[1],
+            ("app".to_string(), vec!["host".to_string()])
+        );
+        let mut recommendations = vec![recommendation("status"), recommendation("host")];
+        ctx.attach_distinct_values(&mut recommendations, DistinctOptions::default())
+            .await;
+        assert_eq!(recommendations[1]["distinct_count"], 50);
+        assert_eq!(searcher.distinct_lookups.lock().unwrap().len(), 2);
+
+        // another window is another key
+        let ctx = QueryOptimizerContext {
+            window: Some((end_time - 7200 * 1_000_000, end_time)),
+            ..ctx
+        };
+        let mut recommendations = vec![recommendation("status")];
+        ctx.attach_distinct_values(&mut recommendations, DistinctOptions::default())
+            .await;
+        assert_eq!(searcher.distinct_lookups.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_join_limits_concurrency() {
+        let running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let max_running = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let task = {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            move |i: u64| {
+                let running = running.clone();
+                let max_running = max_running.clone();
+                async move {
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now_running, Ordering::SeqCst);
+                    // finish out of order
+                    tokio::time::sleep(Duration::from_millis(10 - i % 5)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i * 10
+                }
+            }
+        };
+
+        let mut outputs = bounded_join((0..12).collect(), 3, task.clone()).await;
+        assert_eq!(max_running.load(Ordering::SeqCst), 3);
+        outputs.sort();
+        assert_eq!(outputs, (0..12).map(|i| (i, i * 10)).collect::<Vec<_>>());
+
+        // a zero concurrency still makes progress, one task at a time
+        max_running.store(0, Ordering::SeqCst);
+        let outputs = bounded_join((0..4).collect(), 0, task).await;
+        assert_eq!(outputs.len(), 4);
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+
+        // a task that panics is left out, the others complete
+        let mut outputs = bounded_join(vec![1, 2, 3], 2, |i: u64| async move {
+            assert_ne!(i, 2, "task {i} panicked");
+            i
+        })
+        .await;
+        outputs.sort();
+        assert_eq!(outputs, vec![(1, 1), (3, 3)]);
+    }
+
+    #[test]
+    fn test_distinct_options() {
+        let mut cfg = config::Config::default();
+        assert_eq!(distinct_options(&cfg), DistinctOptions::default());
+        cfg.recommendations.sample_values = 5;
+        cfg.recommendations.distinct_chunk_interval = 3600;
+        assert_eq!(
+            distinct_options(&cfg),
+            DistinctOptions {
+                chunk_interval: Some(3600 * 1_000_000),
+                sample_values: 5,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_engine_runs_against_stub_dataset() {
+        let searcher = Arc::new(stub_dataset());
+        let report = Arc::new(Mutex::new(RecommendationRunReport::default()));
+        let ctx = QueryOptimizerContext {
+            org_id: Some("harness_org".to_string()),
+            report: Some(report.clone()),
+            dry_run: true,
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone());
+        recommendation_service(ctx, 3600 * 1_000_000)
+            .run()
+            .await
+            .unwrap();
+
+        // every search of the engine was served from the dataset, none reached the cluster
+        let searches = searcher.searches.lock().unwrap().clone();
+        let report = report.lock().await;
+        assert!(!searches.is_empty());
+        assert!(searches.len() <= report.queries.len());
+        // recommendations can only be about the streams of the dataset that were analysed
+        for recommendation in &report.recommendations {
+            let recommendation = recommendation.to_string();
+            assert!(!recommendation.contains("tiny"), "{recommendation}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_on_demand_with_searcher() {
+        let searcher = Arc::new(stub_dataset());
+        let report = run_on_demand_with_searcher(
+            searcher.clone(),
+            "harness_on_demand_org",
+            Some(3600 * 1_000_000),
+            true,
+            None,
+        )
+        .await
+        .unwrap();
+        let searches = searcher.searches.lock().unwrap();
+        assert!(!searches.is_empty());
+        assert!(searches.len() <= report.queries.len());
+    }
+
+    #[tokio::test]
+    async fn test_run_on_demand_keeps_runs_of_other_orgs() {
+        let (cancel, mut cancelled) = oneshot::channel();
+        let other_org = "harness_on_demand_other_org";
+        ON_DEMAND_RUNS.insert(
+            other_org.to_string(),
+            OnDemandRun {
+                id: u64::MAX,
+                org_id: other_org.to_string(),
+                cancel,
+                report: Default::default(),
+            },
+        );
+        run_on_demand_with_searcher(
+            Arc::new(stub_dataset()),
+            "harness_on_demand_own_org",
+            Some(3600 * 1_000_000),
+            true,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(cancelled.try_recv().is_err());
+        assert!(ON_DEMAND_RUNS.contains_key(other_org));
+        assert!(!ON_DEMAND_RUNS.contains_key("harness_on_demand_own_org"));
+        ON_DEMAND_RUNS.remove(other_org);
+    }
+
+    #[test]
+    fn test_usage_window() {
+        let now = 100 * 3600 * 1_000_000;
+        let duration = 3600 * 1_000_000;
+        let (start, end) = recommendation_window(now, duration);
+        assert_eq!(usage_window(now, duration, None), (start, end));
+        // only the usage after the watermark is read
+        assert_eq!(
+            usage_window(now, duration, Some(start + 10)),
+            (start + 10, end)
+        );
+        // a watermark outside the window, e.g. from a run long ago, reads the whole window
+        assert_eq!(usage_window(now, duration, Some(start - 10)), (start, end));
+        assert_eq!(usage_window(now, duration, Some(end + 10)), (start, end));
+    }
+
+    #[test]
+    fn test_trigger_data_keeps_usage_watermark() {
+        let data = RecommendationTriggerData::from_trigger_data(r#"{"consecutive_failures":2}"#);
+        assert_eq!(data.usage_watermark, None);
+        assert!(!data.to_json_string().contains("usage_watermark"));
+
+        let data = RecommendationTriggerData {
+            usage_watermark: Some(42),
+            ..data
+        };
+        let data = RecommendationTriggerData::from_trigger_data(&data.to_json_string());
+        assert_eq!(data.usage_watermark, Some(42));
+        assert_eq!(data.consecutive_failures, 2);
+    }
+
+    #[test]
+    fn test_merge_latency_stats() {
+        let stat = |fingerprint: &str, p95_micros: i64| LatencyStat {
+            fingerprint: fingerprint.to_string(),
+            sql: format!("SELECT {fingerprint} FROM logs"),
+            p95_micros,
+            queries: 1,
+        };
+        let mut merged = merge_latency_stats(
+            vec![stat("a", 100), stat("b", 200)],
+            &[stat("b", 900), stat("c", 300)],
+        )
+        .into_iter()
+        .map(|stat| (stat.fingerprint, stat.p95_micros))
+        .collect::<Vec<_>>();
+        merged.sort();
+        assert_eq!(
+            merged,
+            vec![
+                ("a".to_string(), 100),
+                ("b".to_string(), 900),
+                ("c".to_string(), 300)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_second_run_only_reads_new_usage() {
+        let org_id = "harness_watermark_org";
+        let usage = (0..3)
+            .map(|i| {
+                serde_json::json!({
+                    "_timestamp": now_micros() - (i + 1) * 1_000_000,
+                    "org_id": org_id,
+                    "request_body": format!("SELECT * FROM app WHERE field_{i} = 1"),
+                    "p95": 1.5,
+                    "queries": 4,
+                })
+            })
+            .collect();
+        let searcher = Arc::new(StubSearcher::default().with_stream("usage", 50_000, usage));
+        let ctx = QueryOptimizerContext {
+            org_id: Some(org_id.to_string()),
+            dry_run: true,
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone());
+        let duration = 3600 * 1_000_000;
+
+        assert_eq!(ctx.usage_watermark(), None);
+        let first = ctx.track_latency_trends(duration).await.unwrap();
+        assert_eq!(first.len(), 3);
+        let watermark = ctx.usage_watermark().unwrap();
+
+        // the next run, e.g. the next scheduled one, starts from the stored watermark
+        let ctx = QueryOptimizerContext {
+            org_id: Some(org_id.to_string()),
+            dry_run: true,
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone())
+        .with_usage_watermark(Some(watermark));
+        let second = ctx.track_latency_trends(duration).await.unwrap();
+        // the usage recorded before the watermark isn't read again, and the dry runs stored no
+        // stats to merge it with
+        assert!(second.is_empty());
+        assert!(ctx.usage_watermark().unwrap() >= watermark);
+        assert_eq!(searcher.searches.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_partition_key_candidates() {
+        let stat = |sql: &str, queries: i64| UsageStat {
+            org_id: "default".to_string(),
+            fingerprint: latency_fingerprint(sql),
+            sql: sql.to_string(),
+            queries,
+            p95_micros: 1_000_000,
+            max_micros: 1_000_000,
+            dashboard_id: None,
+            panel_id: None,
+        };
+        let usage = vec![
+            stat("SELECT * FROM app WHERE status = 500 AND host = 'a'", 4),
+            stat(
+                "SELECT * FROM app WHERE status IN (200, 404) AND code > 1",
+                3,
+            ),
+            stat(
+                "SELECT * FROM \"traces\".\"spans\" WHERE service = 'api'",
+                2,
+            ),
+            // only equality filters on literals count
+            stat("SELECT * FROM app WHERE host != 'a' AND env = region", 10),
+            // the stream of a column is ambiguous in a join
+            stat(
+                "SELECT * FROM app a JOIN tiny t ON a.id = t.id WHERE a.level = 'error'",
+                10,
+            ),
+            stat("SELECT * FROM app WHERE _timestamp = 1", 10),
+            stat("INVALID SQL", 10),
+        ];
+        let candidates = partition_key_candidates(&usage)
+            .into_iter()
+            .map(|c| (c.stream_type, c.stream, c.field, c.query_hit_count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            candidates,
+            vec![
+                (StreamType::Logs, "app".to_string(), "status".to_string(), 7),
+                (StreamType::Logs, "app".to_string(), "host".to_string(), 4),
+                (
+                    StreamType::Traces,
+                    "spans".to_string(),
+                    "service".to_string(),
+                    2
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partition_keys_are_recommended_from_usage() {
+        let org_id = "harness_partition_org";
+        let usage = [
+            ("SELECT * FROM app WHERE status = 500 AND host = 'a'", 4),
+            ("SELECT * FROM app WHERE status = 404", 3),
+            ("SELECT * FROM app WHERE level = 'error'", 10),
+            ("SELECT * FROM app WHERE missing = 'x'", 10),
+            ("SELECT * FROM gone WHERE status = 1", 10),
+        ]
+        .into_iter()
+        .map(|(sql, queries)| {
+            serde_json::json!({
+                "_timestamp": now_micros() - 1_000_000,
+                "org_id": org_id,
+                "request_body": sql,
+                "p95": 1.5,
+                "queries": queries,
+            })
+        })
+        .collect();
+        let mut searcher = StubSearcher::default()
+            .with_stream("usage", 50_000, usage)
+            .with_stream("app", 50_000, vec![]);
+        let app = &mut searcher.streams[1];
+        app.schema = ["status", "host", "level"]
+            .into_iter()
+            .map(|name| config::meta::stream::StreamField {
+                name: name.to_string(),
+                r#type: "Utf8".to_string(),
+            })
+            .collect();
+        // already a partition key
+        app.settings.partition_keys = vec![StreamPartition::new("level")];
+        let report = Arc::new(Mutex::new(RecommendationRunReport::default()));
+        let ctx = QueryOptimizerContext {
+            org_id: Some(org_id.to_string()),
+            report: Some(report.clone()),
+            dry_run: true,
+            ..Default::default()
+        }
+        .with_searcher(Arc::new(searcher));
+
+        let recommendations = ctx
+            .recommend_partition_keys(3600 * 1_000_000)
+            .await
+            .unwrap();
+        let fields = recommendations
+            .iter()
+            .map(|r| (r.stream.as_str(), r.field.as_str(), r.query_hit_count))
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec![("app", "status", 7), ("app", "host", 4)]);
+        assert_eq!(report.lock().await.partition_keys, recommendations);
+
+        // a run restricted to another stream recommends nothing
+        let ctx = QueryOptimizerContext {
+            stream_filter: Some((StreamType::Logs, "tiny".to_string())),
+            ..ctx
+        };
+        assert!(
+            ctx.recommend_partition_keys(3600 * 1_000_000)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_usage_stats_are_scoped_and_capped() {
+        let usage = vec![serde_json::json!({
+            "_timestamp": now_micros() - 1_000_000,
+            "org_id": "harness_usage_org",
+            "request_body": "SELECT * FROM app WHERE status = 500",
+            "p95": 1.5,
+            "max_response_time": 3.0,
+            "queries": 4,
+        })];
+        let searcher = Arc::new(StubSearcher::default().with_stream("usage", 50_000, usage));
+        let ctx = QueryOptimizerContext {
+            org_id: Some("harness_usage_org".to_string()),
+            dry_run: true,
+            ..Default::default()
+        }
+        .with_searcher(searcher.clone());
+
+        let stats = ctx.usage_stats(3600 * 1_000_000).await.unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].org_id, "harness_usage_org");
+        assert_eq!(stats[0].queries, 4);
+        assert_eq!(stats[0].p95_micros, 1_500_000);
+        assert_eq!(stats[0].max_micros, 3_000_000);
+
+        let searches = searcher.searches.lock().unwrap();
+        assert_eq!(searches.len(), 1);
+        assert!(searches[0].contains("AND org_id = 'harness_usage_org'"));
+        assert!(searches[0].ends_with(&format!(
+            " LIMIT {}",
+            get_config().recommendations.max_queries
+        )));
+    }
+
+    #[test]
+    fn test_latency_stats_sql() {
+        assert_eq!(
+            latency_stats_sql(Some("o'rg"), UsageSource::All, UsageOrder::P95, 10),
+            "SELECT org_id, request_body, approx_percentile_cont(response_time, 0.95) AS p95, max(response_time) AS max_response_time, count(*) AS queries FROM \"usage\" WHERE event = 'Search' AND org_id = 'o''rg' GROUP BY org_id, request_body ORDER BY p95 DESC LIMIT 10"
+        );
+        assert!(
+            !latency_stats_sql(None, UsageSource::All, UsageOrder::P95, 10).contains("AND org_id")
+        );
+    }
+
+    #[test]
+    fn test_latency_stats_sql_sources() {
+        assert_eq!(
+            latency_stats_sql(None, UsageSource::AdHoc, UsageOrder::P95, 10),
+            "SELECT org_id, request_body, approx_percentile_cont(response_time, 0.95) AS p95, max(response_time) AS max_response_time, count(*) AS queries FROM \"usage\" WHERE event = 'Search' AND (search_type IS NULL OR search_type <> 'dashboards') GROUP BY org_id, request_body ORDER BY p95 DESC LIMIT 10"
+        );
+        assert_eq!(
+            latency_stats_sql(None, UsageSource::Dashboards, UsageOrder::P95, 10),
+            "SELECT org_id, request_body, dashboard_id, dashboard_info_panel_id, approx_percentile_cont(response_time, 0.95) AS p95, max(response_time) AS max_response_time, count(*) AS queries FROM \"usage\" WHERE event = 'Search' AND search_type = 'dashboards' GROUP BY org_id, request_body, dashboard_id, dashboard_info_panel_id ORDER BY p95 DESC LIMIT 10"
+        );
+    }
+
+    #[test]
+    fn test_usage_stats_of_dashboards() {
+        let hits = vec![
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 500", "dashboard_id": "d1", "dashboard_info_panel_id": "p1", "p95": 2.0, "queries": 5}),
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 200", "dashboard_id": "d1", "dashboard_info_panel_id": "p2", "p95": 1.0, "queries": 5}),
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 404", "p95": 0.5, "queries": 5}),
+        ];
+        let stats = usage_stats(&hits, UsageOrder::P95);
+        // the same query shape is tracked per panel, apart from the ad-hoc queries
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[0].dashboard_id.as_deref(), Some("d1"));
+        assert_eq!(stats[0].panel_id.as_deref(), Some("p1"));
+        assert_eq!(stats[1].panel_id.as_deref(), Some("p2"));
+        assert_eq!(stats[2].dashboard_id, None);
+        assert_ne!(stats[0].fingerprint, stats[1].fingerprint);
+        assert_ne!(stats[1].fingerprint, stats[2].fingerprint);
+        assert_eq!(
+            stats[2].fingerprint,
+            latency_fingerprint("SELECT * FROM app WHERE status = 404")
+        );
+        let json = serde_json::to_value(&stats[2]).unwrap();
+        assert!(json.get("dashboard_id").is_none());
+    }
+
+    #[test]
+    fn test_latency_stats_sql_order() {
+        assert_eq!(UsageOrder::from("P95"), UsageOrder::P95);
+        assert_eq!(UsageOrder::from("count"), UsageOrder::Count);
+        assert_eq!(UsageOrder::from("Impact"), UsageOrder::Impact);
+        assert_eq!(UsageOrder::from(""), UsageOrder::default());
+
+        for (order, order_by) in [
+            (UsageOrder::P95, " ORDER BY p95 DESC LIMIT 5"),
+            (UsageOrder::Count, " ORDER BY queries DESC LIMIT 5"),
+            (
+                UsageOrder::Impact,
+                " ORDER BY approx_percentile_cont(response_time, 0.95) * count(*) DESC LIMIT 5",
+            ),
+        ] {
+            let sql = latency_stats_sql(None, UsageSource::All, order, 5);
+            assert!(sql.ends_with(order_by), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_latency_stats_groups_by_fingerprint() {
+        let hits = vec![
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 200", "p95": 0.5, "queries": 3}),
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 500", "p95": 1.5, "queries": 2}),
+            serde_json::json!({"org_id": "default", "request_body": "SELECT count(*) FROM app", "p95": 0.1, "queries": 1}),
+            serde_json::json!({"org_id": "other", "request_body": "SELECT * FROM app WHERE status = 200", "p95": 2.0, "queries": 1}),
+            // not a latency row
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app"}),
+        ];
+        let stats = latency_stats(usage_stats(&hits, UsageOrder::P95));
+        assert_eq!(stats.len(), 2);
+        let mut default = stats["default"].clone();
+        default.sort_by_key(|stat| stat.p95_micros);
+        assert_eq!(default.len(), 2);
+        assert_eq!(default[1].p95_micros, 1_500_000);
+        assert_eq!(default[1].queries, 5);
+        assert_eq!(default[1].sql, "SELECT * FROM app WHERE status = 500");
+        assert_eq!(
+            default[1].fingerprint,
+            latency_fingerprint("SELECT * FROM app WHERE status = 404")
+        );
+        assert_ne!(default[0].fingerprint, default[1].fingerprint);
+        // the same query is tracked separately per org
+        assert_eq!(stats["other"][0].fingerprint, default[1].fingerprint);
+    }
+
+    #[test]
+    fn test_usage_stats_order() {
+        let hits = vec![
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 200", "p95": 2.0, "max_response_time": 4.0, "queries": 1}),
+            serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 500", "p95": 1.0, "max_response_time": 9.0, "queries": 2}),
+            serde_json::json!({"org_id": "default", "request_body": "SELECT count(*) FROM app", "p95": 0.5, "queries": 10}),
+        ];
+        let sqls = |order| {
+            usage_stats(&hits, order)
+                .into_iter()
+                .map(|stat| stat.sql)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sqls(UsageOrder::P95),
+            vec![
+                "SELECT * FROM app WHERE status = 200",
+                "SELECT count(*) FROM app"
+            ]
+        );
+        assert_eq!(
+            sqls(UsageOrder::Count),
+            vec![
+                "SELECT count(*) FROM app",
+                "SELECT * FROM app WHERE status = 200"
+            ]
+        );
+
+        let stats = usage_stats(&hits, UsageOrder::Impact);
+        assert_eq!(stats.len(), 2);
+        // the two status queries share a fingerprint: 2s * 3 queries against 0.5s * 10 queries
+        assert_eq!(stats[0].sql, "SELECT * FROM app WHERE status = 200");
+        assert_eq!(stats[0].queries, 3);
+        assert_eq!(stats[0].p95_micros, 2_000_000);
+        assert_eq!(stats[0].max_micros, 9_000_000);
+        // without a max, the p95 is the slowest known
+        assert_eq!(stats[1].max_micros, 500_000);
+    }
+
+    fn latency_regression(id: &str, fingerprint: &str, created_at: i64) -> Recommendation {
+        Recommendation {
+            id: id.to_string(),
+            org_id: "default".to_string(),
+            stream: "app".to_string(),
+            recommendation_type: LATENCY_REGRESSION_RECOMMENDATION.to_string(),
+            target_field: fingerprint.to_string(),
+            payload: serde_json::json!({
+                "sql": "SELECT * FROM app",
+                "p95_micros": 2_000_000,
+                "previous_p95_micros": 1_000_000,
+                "priority": "high",
+            }),
+            status: RecommendationStatus::Open,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    fn latency_trend(fingerprint: &str, p95_micros: i64) -> LatencyTrend {
+        LatencyTrend {
+            org_id: "default".to_string(),
+            fingerprint: fingerprint.to_string(),
+            sql: "SELECT * FROM app".to_string(),
+            p95_micros,
+            queries: 10,
+            previous_p95_micros: Some(2_000_000),
+            change_percent: None,
+            regressed: false,
+            dashboard_id: None,
+            panel_id: None,
+            estimated_cost: None,
+        }
+    }
+
+    #[test]
+    fn test_auto_resolutions_when_pattern_disappeared() {
+        let day = 24 * 3600 * 1_000_000;
+        let now = 10 * day;
+        let open = vec![
+            latency_regression("gone", "a", now - 2 * day),
+            latency_regression("still_slow", "b", now - 2 * day),
+            // too recent to be closed
+            latency_regression("young", "c", now - 1000),
+        ];
+        let trends = vec![latency_trend("b", 2_000_000)];
+        assert_eq!(
+            auto_resolutions(&open, &trends, 20, now, day),
+            vec![(
+                "gone".to_string(),
+                "the query no longer appears in the analysed window".to_string()
+            )]
+        );
+        // nothing is too recent without a minimum age
+        let resolved = auto_resolutions(&open, &trends, 20, now, 0);
+        let ids = resolved
+            .iter()
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["gone", "young"]);
+    }
+
+    #[test]
+    fn test_auto_resolutions_when_now_fast() {
+        let day = 24 * 3600 * 1_000_000;
+        let now = 10 * day;
+        let open = vec![
+            latency_regression("fast", "a", now - 2 * day),
+            latency_regression("within_threshold", "b", now - 2 * day),
+            latency_regression("slow", "c", now - 2 * day),
+        ];
+        let trends = vec![
+            latency_trend("a", 900_000),
+            latency_trend("b", 1_150_000),
+            latency_trend("c", 1_500_000),
+        ];
+        assert_eq!(
+            auto_resolutions(&open, &trends, 20, now, day),
+            vec![
+                (
+                    "fast".to_string(),
+                    "the query is fast again, p95 of 900ms against 1000ms before the regression"
+                        .to_string()
+                ),
+                (
+                    "within_threshold".to_string(),
+                    "the query is fast again, p95 of 1150ms against 1000ms before the regression"
+                        .to_string()
+                ),
+            ]
+        );
+
+        // other types of recommendations and closed ones are left alone
+        let mut other = latency_regression("index", "a", now - 2 * day);
+        other.recommendation_type = INDEX_RECOMMENDATION.to_string();
+        let mut dismissed = latency_regression("dismissed", "a", now - 2 * day);
+        dismissed.status = RecommendationStatus::Dismissed;
+        assert!(auto_resolutions(&[other, dismissed], &[], 20, now, day).is_empty());
+    }
+
+    #[test]
+    fn test_top_by_impact_keeps_highest_impact() {
+        let trend = |fingerprint: &str, p95_micros: i64, queries: i64| LatencyTrend {
+            org_id: "default".to_string(),
+            fingerprint: fingerprint.to_string(),
+            sql: "SELECT * FROM app".to_string(),
+            p95_micros,
+            queries,
+            previous_p95_micros: Some(1),
+            change_percent: Some(100.0),
+            regressed: true,
+            dashboard_id: None,
+            panel_id: None,
+            estimated_cost: None,
+        };
+        let candidates = vec![
+            trend("slow_rare", 10_000_000, 1),
+            trend("fast_frequent", 100_000, 1_000),
+            trend("fast_rare", 100_000, 1),
+            trend("medium", 1_000_000, 50),
+        ];
+        let fingerprints = |trends: Vec<LatencyTrend>| {
+            trends
+                .into_iter()
+                .map(|trend| trend.fingerprint)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            fingerprints(top_by_impact(candidates.clone(), latency_impact, 2)),
+            vec!["fast_frequent", "medium"]
+        );
+        assert_eq!(
+            fingerprints(top_by_impact(candidates.clone(), latency_impact, 0)),
+            vec!["fast_frequent", "medium", "slow_rare", "fast_rare"]
+        );
+        assert_eq!(top_by_impact(candidates, latency_impact, 10).len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_regressions_are_ranked_by_estimated_cost() {
+        let searcher = Arc::new(stub_dataset());
+        let ctx = QueryOptimizerContext::default().with_searcher(searcher);
+        let trend = |fingerprint: &str, sql: &str, regressed: bool| LatencyTrend {
+            sql: sql.to_string(),
+            regressed,
+            ..latency_trend(fingerprint, 2_000_000)
+        };
+        let mut trends = vec![
+            trend("tiny", "SELECT * FROM tiny", true),
+            trend("app", "SELECT * FROM app", true),
+            trend("steady", "SELECT * FROM app", false),
+            trend("unknown", "SELECT * FROM deleted", true),
+        ];
+        ctx.estimate_regression_costs(&mut trends, 0, now_micros())
+            .await;
+        let records = trends
+            .iter()
+            .map(|trend| trend.estimated_cost.as_ref().map(|cost| cost.records))
+            .collect::<Vec<_>>();
+        // only regressions are estimated, and those whose stream is gone keep no estimate
+        assert_eq!(records, vec![Some(5), Some(50_000), None, None]);
+
+        // of regressions of the same impact, the one scanning more comes first
+        let ranked = top_by_impact(
+            trends.into_iter().filter(|trend| trend.regressed),
+            regression_rank,
+            0,
+        );
+        let fingerprints = ranked
+            .iter()
+            .map(|trend| trend.fingerprint.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fingerprints, vec!["app", "tiny", "unknown"]);
+    }
+
+    #[test]
+    fn test_latency_trends_flag_regressions_across_runs() {
+        let run = |slow_p95: f64| {
+            let hits = vec![
+                serde_json::json!({"org_id": "default", "request_body": "SELECT * FROM app WHERE status = 500", "p95": slow_p95, "queries": 10}),
+                serde_json::json!({"org_id": "default", "request_body": "SELECT count(*) FROM app", "p95": 0.2, "queries": 10}),
+            ];
+            latency_stats(usage_stats(&hits, UsageOrder::P95))
+                .remove("default")
+                .unwrap()
+        };
+
+        // nothing to compare the first run with
+        let first = run(1.0);
+        let trends = latency_trends("default", &first, &HashMap::new(), 20);
+        assert_eq!(trends.len(), 2);
+        assert!(trends.iter().all(|trend| !trend.regressed));
+        assert!(
+            trends
+                .iter()
+                .all(|trend| trend.previous_p95_micros.is_none())
+        );
+
+        // the second run compares with what the first one stored
+        let previous = first
+            .iter()
+            .map(|stat| (stat.fingerprint.clone(), stat.p95_micros))
+            .collect::<HashMap<_, _>>();
+        let trends = latency_trends("default", &run(1.5), &previous, 20);
+        assert!(trends[0].regressed);
+        assert_eq!(trends[0].sql, "SELECT * FROM app WHERE status = 500");
+        assert_eq!(trends[0].previous_p95_micros, Some(1_000_000));
+        assert_eq!(trends[0].p95_micros, 1_500_000);
+        assert_eq!(trends[0].change_percent, Some(50.0));
+        assert!(!trends[1].regressed);
+        assert_eq!(trends[1].change_percent, Some(0.0));
+
+        // a slowdown within the threshold is not a regression
+        let trends = latency_trends("default", &run(1.1), &previous, 20);
+        assert!(trends.iter().all(|trend| !trend.regressed));
+    }
+
+    #[tokio::test]
+    async fn test_search_timeout_returns_empty_response() {
+        let timed_out = AtomicBool::new(false);
+        let resp = search_with_timeout(
+            std::future::pending(),
+            Duration::from_millis(10),
+            &timed_out,
+            0,
+            100,
+        )
+        .await
+        .unwrap();
+        assert!(resp.hits.is_empty());
+        assert_eq!(resp.total, 0);
+        assert!(timed_out.load(Ordering::Relaxed));
+    }
 }
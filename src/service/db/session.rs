@@ -16,18 +16,32 @@
 use std::sync::Arc;
 
 use bytes::Bytes;
-use infra::db::{delete_from_db_coordinator, put_into_db_coordinator};
+use config::{get_config, utils::time::now_micros};
+use infra::{
+    db::{delete_from_db_coordinator, put_into_db_coordinator},
+    table::sessions::CachedSession,
+};
 #[cfg(feature = "enterprise")]
 use o2_enterprise::enterprise::common::config::get_config as get_o2_config;
 
-use crate::common::infra::config::USER_SESSIONS;
+use crate::common::infra::config::{USER_SESSIONS, USER_SESSIONS_LAST_USED};
 
 // Key prefix for session events in coordinator
 pub const USER_SESSION_KEY: &str = "/user_sessions/";
 
+// Minimum interval between two last_used_at updates of the same session
+const TOUCH_INTERVAL: i64 = 60 * 1_000_000; // microseconds
+
 pub async fn get(session_id: &str) -> Result<String, anyhow::Error> {
-    if let Some(val) = USER_SESSIONS.get(session_id) {
-        return Ok(val.to_string());
+    let cached = USER_SESSIONS.get(session_id).map(|session| session.clone());
+    if let Some(session) = cached {
+        if !session.is_expired(now_micros()) {
+            return Ok(session.access_token);
+        }
+        // the db removes the expired session when it is read below
+        log::debug!("[SESSION] Cached session expired, evicting: {session_id}");
+        USER_SESSIONS.remove(session_id);
+        USER_SESSIONS_LAST_USED.remove(session_id);
     }
 
     // get from db
@@ -40,7 +54,7 @@ pub async fn get(session_id: &str) -> Result<String, anyhow::Error> {
             let access_token = session.access_token.clone();
             // Cache it in memory
             if !access_token.is_empty() {
-                USER_SESSIONS.insert(session_id.to_string(), access_token.clone());
+                USER_SESSIONS.insert(session_id.to_string(), CachedSession::from(session));
             }
             Ok(access_token)
         }
@@ -48,8 +62,13 @@ pub async fn get(session_id: &str) -> Result<String, anyhow::Error> {
     }
 }
 
-pub async fn set(session_id: &str, val: &str) -> Result<(), anyhow::Error> {
-    infra::table::sessions::set(session_id, val).await?;
+pub async fn set(
+    session_id: &str,
+    val: &str,
+    user_id: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    infra::table::sessions::set(session_id, val, user_id, user_agent).await?;
     let key = format!("{USER_SESSION_KEY}{session_id}");
     if let Err(e) = put_into_db_coordinator(&key, Bytes::new(), true, None).await {
         log::error!("[SESSION] Failed to sync session to coordinator: {key} - {e}");
@@ -65,13 +84,96 @@ pub async fn set(session_id: &str, val: &str) -> Result<(), anyhow::Error> {
                 });
     }
 
-    USER_SESSIONS.insert(session_id.to_string(), val.to_string());
+    USER_SESSIONS.insert(
+        session_id.to_string(),
+        CachedSession {
+            access_token: val.to_string(),
+            expires_at: infra::table::sessions::expires_at(now_micros()),
+        },
+    );
+
+    if let Some(user_id) = user_id {
+        let revoked = enforce_max_sessions(user_id).await?;
+        if !revoked.is_empty() {
+            log::info!(
+                "[SESSION] Revoked {} oldest session(s) of user {user_id} exceeding the session limit: {revoked:?}",
+                revoked.len()
+            );
+        }
+    }
 
     Ok(())
 }
 
+/// Revokes the oldest sessions of a user beyond the configured maximum number of concurrent
+/// sessions, returning the removed session_ids. Does nothing when the limit is disabled.
+pub async fn enforce_max_sessions(user_id: &str) -> Result<Vec<String>, anyhow::Error> {
+    let max_sessions = get_config().auth.max_sessions_per_user;
+    if max_sessions == 0 {
+        return Ok(vec![]);
+    }
+    let session_ids =
+        infra::table::sessions::revoke_overflow_for_user(user_id, max_sessions).await?;
+    for session_id in session_ids.iter() {
+        remove_from_cluster(session_id).await;
+    }
+    Ok(session_ids)
+}
+
+/// Deletes up to `limit` sessions that expired before `now`, or that were last used before
+/// `idle_cutoff`, returning the removed session_ids.
+pub async fn delete_stale(
+    now: i64,
+    idle_cutoff: Option<i64>,
+    limit: u64,
+) -> Result<Vec<String>, anyhow::Error> {
+    let session_ids = infra::table::sessions::delete_stale(now, idle_cutoff, limit).await?;
+    for session_id in session_ids.iter() {
+        remove_from_cluster(session_id).await;
+    }
+    Ok(session_ids)
+}
+
 pub async fn delete(session_id: &str) -> Result<(), anyhow::Error> {
     infra::table::sessions::delete(session_id).await?;
+    remove_from_cluster(session_id).await;
+    Ok(())
+}
+
+/// Records that a session was used. Writes are throttled to at most one per `TOUCH_INTERVAL`
+/// per session, both on this node and across the cluster.
+pub async fn touch(session_id: &str) -> Result<(), anyhow::Error> {
+    let now = now_micros();
+    if let Some(last_used) = USER_SESSIONS_LAST_USED.get(session_id)
+        && now - *last_used < TOUCH_INTERVAL
+    {
+        return Ok(());
+    }
+    USER_SESSIONS_LAST_USED.insert(session_id.to_string(), now);
+    infra::table::sessions::touch(session_id, now, TOUCH_INTERVAL).await?;
+    Ok(())
+}
+
+/// Revokes a session, returning whether a session was actually removed.
+pub async fn revoke(session_id: &str) -> Result<bool, anyhow::Error> {
+    let revoked = infra::table::sessions::revoke(session_id).await?;
+    if revoked {
+        remove_from_cluster(session_id).await;
+    }
+    Ok(revoked)
+}
+
+/// Revokes all sessions holding the given access token, returning the removed session_ids.
+pub async fn revoke_all_for_token(access_token: &str) -> Result<Vec<String>, anyhow::Error> {
+    let session_ids = infra::table::sessions::revoke_by_access_token(access_token).await?;
+    for session_id in session_ids.iter() {
+        remove_from_cluster(session_id).await;
+    }
+    Ok(session_ids)
+}
+
+/// Notifies the cluster that a session was removed and evicts it from the local cache.
+async fn remove_from_cluster(session_id: &str) {
     let key = format!("{USER_SESSION_KEY}{session_id}");
     if let Err(e) = delete_from_db_coordinator(&key, false, true, None).await {
         log::error!("[SESSION] Failed to delete session from coordinator: {key} - {e}");
@@ -87,8 +189,7 @@ pub async fn delete(session_id: &str) -> Result<(), anyhow::Error> {
     }
 
     USER_SESSIONS.remove(session_id);
-
-    Ok(())
+    USER_SESSIONS_LAST_USED.remove(session_id);
 }
 
 pub async fn watch() -> Result<(), anyhow::Error> {
@@ -111,7 +212,8 @@ pub async fn watch() -> Result<(), anyhow::Error> {
                 match infra::table::sessions::get(session_id).await {
                     Ok(Some(session)) => {
                         if !session.access_token.is_empty() {
-                            USER_SESSIONS.insert(session_id.to_string(), session.access_token);
+                            USER_SESSIONS
+                                .insert(session_id.to_string(), CachedSession::from(session));
                             log::debug!("Session added to cache: {}", session_id);
                         }
                     }
@@ -145,7 +247,7 @@ pub async fn cache() -> Result<(), anyhow::Error> {
 
     for session in sessions_list {
         if !session.access_token.is_empty() {
-            USER_SESSIONS.insert(session.session_id, session.access_token);
+            USER_SESSIONS.insert(session.session_id.clone(), CachedSession::from(session));
         }
     }
 
@@ -785,6 +785,7 @@ pub(crate) async fn get_series(
         use_cache: default_use_cache(),
         clear_cache: false,
         local_mode: None,
+        search_priority: Default::default(),
     };
     let series = match search_service::search("", org_id, StreamType::Metrics, None, &req).await {
         Err(err) => {
@@ -930,6 +931,7 @@ pub(crate) async fn get_label_values(
         use_cache: default_use_cache(),
         clear_cache: false,
         local_mode: None,
+        search_priority: Default::default(),
     };
     let mut label_values = match search_service::search("", org_id, stream_type, None, &req).await {
         Ok(resp) => resp
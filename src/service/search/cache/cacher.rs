@@ -123,7 +123,7 @@ pub async fn check_cache(
     // skip the count queries & queries first order by is not _timestamp field
     // Exception: Allow histogram queries even if ORDER BY is not on timestamp,
     // because histogram is plotted based on timestamp
-    if req.query.track_total_hits
+    if req.query.track_total_hits.is_enabled()
         || (!order_by.is_empty()
             && order_by.first().as_ref().unwrap().0 != TIMESTAMP_COL_NAME
             && !is_histogram_query
@@ -940,7 +940,10 @@ mod tests {
 
     use arrow_schema::{Field, Schema};
     use config::meta::{
-        search::{Query, Request, RequestEncoding, Response, ResponseTook, SearchEventType},
+        search::{
+            Query, Request, RequestEncoding, Response, ResponseTook, SearchEventType,
+            TrackTotalHits,
+        },
         sql::OrderBy,
     };
     use datafusion::common::TableReference;
@@ -1197,7 +1200,7 @@ mod tests {
                 end_time: 1641081600000000,
                 from: 0,
                 size: 100,
-                track_total_hits: false,
+                track_total_hits: TrackTotalHits::Off,
                 query_fn: None,
                 quick_mode: false,
                 query_type: "sql".to_string(),
@@ -1219,6 +1222,7 @@ mod tests {
             use_cache: true,
             clear_cache: false,
             local_mode: None,
+            search_priority: Default::default(),
         };
         let mut origin_sql = req.query.sql.clone();
         let mut file_path = "test_org/logs/test_stream".to_string();
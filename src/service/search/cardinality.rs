@@ -13,10 +13,17 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use config::{RwHashMap, meta::stream::StreamType, utils::time::now_micros};
+use std::sync::Arc;
+
+use config::{
+    RwHashMap,
+    meta::stream::StreamType,
+    utils::{sql::quote_identifier, time::now_micros},
+};
 use hashbrown::HashMap;
 use infra::errors::{Error, Result};
 use once_cell::sync::Lazy;
+use serde::Serialize;
 
 use crate::service::search as searchService;
 
@@ -58,24 +65,27 @@ use crate::service::search as searchService;
 ///
 /// ### Single Field
 /// ```sql
-/// SELECT approx_distinct("field_name") AS cnt FROM "stream_name"
+/// SELECT approx_distinct("field_name") AS distinct_0 FROM "stream_name"
 /// ```
 ///
 /// ### Multiple Fields
 /// ```sql
 /// SELECT
-///     approx_distinct("field1") AS field1_cnt,
-///     approx_distinct("field2") AS field2_cnt,
-///     approx_distinct("field3") AS field3_cnt
+///     approx_distinct("field1") AS distinct_0,
+///     approx_distinct("field2") AS distinct_1,
+///     approx_distinct("field3") AS distinct_2
 /// FROM "stream_name"
 /// ```
 ///
+/// Columns are aliased by the position of their field, so fields named like SQL keywords or
+/// differing only by case can't collide, and are mapped back to the field names by position.
+///
 /// ## Response Format
 /// ```json
 /// {
-///     "field1_cnt": 1000,
-///     "field2_cnt": 2000,
-///     "field3_cnt": 500
+///     "distinct_0": 1000,
+///     "distinct_1": 2000,
+///     "distinct_2": 500
 /// }
 /// ```
 ///
@@ -266,69 +276,363 @@ async fn get_cardinality(
         now_micros()
     };
 
-    // Build SQL query with multiple approx_distinct calls
-    let mut select_clauses = Vec::new();
-    for field_name in &valid_fields {
-        let clause = format!(
-            "approx_distinct(\"{}\") AS {}_cnt",
-            field_name,
-            field_name.replace('"', "")
-        );
-        select_clauses.push(clause);
+    let resp = searchService::internal_search(
+        org_id,
+        stream_type,
+        distinct_count_sql(stream_name, &valid_fields, DistinctMode::Approx),
+        query_time - CACHE_EXPIRATION_MICROS,
+        query_time,
+    )
+    .await?;
+    let results = parse_distinct_counts(&valid_fields, &resp)
+        .into_iter()
+        .map(|(field_name, count)| (field_name, count as f64))
+        .collect();
+    Ok(results)
+}
+
+/// How distinct values of a field are counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistinctMode {
+    /// Uses `approx_distinct`, which is fast and uses little memory but is an estimate.
+    #[default]
+    Approx,
+    /// Uses `count(distinct ..)`, which is exact but needs memory proportional to the number
+    /// of distinct values, so it should only be used on small streams.
+    Exact,
+}
+
+/// Options of [`field_cardinality`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DistinctOptions {
+    pub mode: DistinctMode,
+    /// Splits the time range into windows of this many microseconds, queried one after the
+    /// other, so that no single query has to scan the whole range of a huge stream. None queries
+    /// the whole range at once.
+    ///
+    /// A value can appear in several windows, so the counts of the windows can't be added up.
+    /// The largest count of any window is used instead, which is a lower bound of the distinct
+    /// count of the whole range: exact if the values are spread evenly over time, an
+    /// underestimate if they change from one window to the next.
+    pub chunk_interval: Option<i64>,
+    /// Number of example values fetched for each field by [`field_distinct_values`], which costs
+    /// one more query per field. 0 fetches none. Fields with sensitive names, see
+    /// [`is_sensitive_field`], are never sampled.
+    pub sample_values: usize,
+}
+
+/// Distinct values of a field, as put in the payload of the recommendations about the field.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FieldDistinct {
+    pub distinct_count: u64,
+    /// A few example values of the field, empty unless sampling is enabled.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sample_values: Vec<String>,
+}
+
+/// Field names containing any of these, ignoring case, hold values that must not be sampled.
+const SENSITIVE_FIELD_MARKERS: [&str; 12] = [
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "authorization",
+    "credential",
+    "cookie",
+    "private_key",
+    "credit_card",
+    "ssn",
+];
+
+/// Returns the number of distinct values of each field of a stream between `start_time` and
+/// `end_time`. Unlike [`check_cardinality`] the result is not cached, and fields that are not part
+/// of the stream schema are omitted.
+pub async fn field_cardinality(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    field_names: &[String],
+    start_time: i64,
+    end_time: i64,
+    options: DistinctOptions,
+) -> Result<HashMap<String, u64>> {
+    let schema = infra::schema::get_cache(org_id, stream_name, stream_type).await?;
+    let fields = field_names
+        .iter()
+        .filter(|field_name| schema.field_with_name(field_name).is_some())
+        .cloned()
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return Ok(HashMap::new());
     }
 
-    let sql = format!(
-        "SELECT {} FROM \"{}\"",
-        select_clauses.join(", "),
-        stream_name
-    );
-
-    let req = config::meta::search::Request {
-        query: config::meta::search::Query {
-            sql,
-            start_time: query_time - CACHE_EXPIRATION_MICROS,
-            end_time: query_time,
-            from: 0,
-            size: 10,
-            ..Default::default()
-        },
-        use_cache: false,
-        ..Default::default()
-    };
+    let sql = distinct_count_sql(stream_name, &fields, options.mode);
+    let mut counts = HashMap::new();
+    for (start_time, end_time) in time_chunks(start_time, end_time, options.chunk_interval) {
+        let resp =
+            searchService::internal_search(org_id, stream_type, sql.clone(), start_time, end_time)
+                .await?;
+        merge_distinct_counts(&mut counts, parse_distinct_counts(&fields, &resp));
+    }
+    Ok(counts)
+}
 
-    let trace_id = config::ider::generate_trace_id();
-    let resp = searchService::search(&trace_id, org_id, stream_type, None, &req).await?;
+/// Like [`field_cardinality`], and also fetches up to [`DistinctOptions::sample_values`] example
+/// values of each field.
+pub async fn field_distinct_values(
+    org_id: &str,
+    stream_name: &str,
+    stream_type: StreamType,
+    field_names: &[String],
+    start_time: i64,
+    end_time: i64,
+    options: DistinctOptions,
+) -> Result<HashMap<String, FieldDistinct>> {
+    let counts = field_cardinality(
+        org_id,
+        stream_name,
+        stream_type,
+        field_names,
+        start_time,
+        end_time,
+        options,
+    )
+    .await?;
+    let fields = counts.keys().cloned().collect::<Vec<_>>();
+    let mut samples = HashMap::new();
+    for field_name in sampled_fields(&fields, options) {
+        let sql = sample_values_sql(stream_name, &field_name, options.sample_values);
+        let resp =
+            searchService::internal_search(org_id, stream_type, sql, start_time, end_time).await?;
+        samples.insert(field_name, parse_sample_values(&resp));
+    }
+    Ok(attach_samples(counts, samples))
+}
 
-    let mut results = HashMap::new();
+/// Distinct values computed during a recommendation run, so that each field of a stream is
+/// counted at most once per time window, however many recommendations of the run are about it.
+/// Clones share the values. Unlike [`check_cardinality`], nothing expires: a cache is meant to
+/// live as long as the run that created it.
+#[derive(Debug, Clone, Default)]
+pub struct DistinctCache {
+    values: Arc<RwHashMap<String, FieldDistinct>>,
+}
 
-    if resp.hits.is_empty() {
-        // Return 0.0 for all fields if no data
-        for field_name in valid_fields {
-            results.insert(field_name.to_string(), 0.0);
+impl DistinctCache {
+    /// Splits `field_names` into the values already computed for the window and the fields that
+    /// still have to be computed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn lookup(
+        &self,
+        org_id: &str,
+        stream_name: &str,
+        stream_type: StreamType,
+        field_names: &[String],
+        start_time: i64,
+        end_time: i64,
+        options: DistinctOptions,
+    ) -> (HashMap<String, FieldDistinct>, Vec<String>) {
+        let mut values = HashMap::new();
+        let mut missing = vec![];
+        for field_name in field_names {
+            let key = distinct_cache_key(
+                org_id,
+                stream_type,
+                stream_name,
+                field_name,
+                start_time,
+                end_time,
+                options,
+            );
+            match self.values.get(&key) {
+                Some(value) => {
+                    values.insert(field_name.to_string(), value.clone());
+                }
+                None => missing.push(field_name.to_string()),
+            }
         }
-        return Ok(results);
-    }
-
-    // Extract cardinality values from response
-    let hit = &resp.hits[0];
-    for field_name in valid_fields {
-        let column_name = format!("{}_cnt", field_name.replace('"', ""));
-        if let Some(val) = hit.get(&column_name) {
-            let cardinality = match val {
-                serde_json::Value::Number(n) => n.as_f64().unwrap_or(0.0),
-                serde_json::Value::String(s) => s.parse::<f64>().unwrap_or(0.0),
-                _ => 0.0,
-            };
-            results.insert(field_name.to_string(), cardinality);
-        } else {
-            log::warn!(
-                "Column '{column_name}' not found in search response for field '{field_name}'"
+        (values, missing)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn insert(
+        &self,
+        org_id: &str,
+        stream_name: &str,
+        stream_type: StreamType,
+        values: &HashMap<String, FieldDistinct>,
+        start_time: i64,
+        end_time: i64,
+        options: DistinctOptions,
+    ) {
+        for (field_name, value) in values {
+            let key = distinct_cache_key(
+                org_id,
+                stream_type,
+                stream_name,
+                field_name,
+                start_time,
+                end_time,
+                options,
             );
-            results.insert(field_name.to_string(), 0.0);
+            self.values.insert(key, value.clone());
         }
     }
+}
 
-    Ok(results)
+/// Builds the key distinct values are cached under in a [`DistinctCache`]. The options are part
+/// of the key, an exact count and an estimate of the same field are different counts.
+fn distinct_cache_key(
+    org_id: &str,
+    stream_type: StreamType,
+    stream_name: &str,
+    field_name: &str,
+    start_time: i64,
+    end_time: i64,
+    options: DistinctOptions,
+) -> String {
+    format!(
+        "distinct/{org_id}/{stream_type}/{stream_name}/{field_name}/{start_time}/{end_time}/{:?}/{:?}/{}",
+        options.mode, options.chunk_interval, options.sample_values
+    )
+}
+
+/// Returns true if the values of a field are likely to be sensitive, going by its name.
+fn is_sensitive_field(field_name: &str) -> bool {
+    let field_name = field_name.to_lowercase();
+    SENSITIVE_FIELD_MARKERS
+        .iter()
+        .any(|marker| field_name.contains(marker))
+}
+
+/// Returns the fields to fetch example values of: none unless sampling is enabled, and never the
+/// sensitive ones.
+fn sampled_fields(field_names: &[String], options: DistinctOptions) -> Vec<String> {
+    if options.sample_values == 0 {
+        return vec![];
+    }
+    field_names
+        .iter()
+        .filter(|field_name| !is_sensitive_field(field_name))
+        .cloned()
+        .collect()
+}
+
+/// Column the values of a [`sample_values_sql`] result are in.
+const SAMPLE_VALUE_ALIAS: &str = "sample_value";
+
+/// Builds the query fetching up to `limit` distinct values of a field.
+fn sample_values_sql(stream_name: &str, field_name: &str, limit: usize) -> String {
+    let field = quote_identifier(field_name);
+    format!(
+        "SELECT DISTINCT {field} AS {SAMPLE_VALUE_ALIAS} FROM {} WHERE {field} IS NOT NULL LIMIT {limit}",
+        quote_identifier(stream_name)
+    )
+}
+
+/// Returns the values of a [`sample_values_sql`] result, as strings.
+fn parse_sample_values(resp: &config::meta::search::Response) -> Vec<String> {
+    resp.column(SAMPLE_VALUE_ALIAS)
+        .into_iter()
+        .filter_map(|value| match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(value) => Some(value),
+            value => Some(value.to_string()),
+        })
+        .collect()
+}
+
+/// Attaches the example values of each field to its distinct count.
+fn attach_samples(
+    counts: HashMap<String, u64>,
+    mut samples: HashMap<String, Vec<String>>,
+) -> HashMap<String, FieldDistinct> {
+    counts
+        .into_iter()
+        .map(|(field_name, distinct_count)| {
+            let sample_values = samples.remove(&field_name).unwrap_or_default();
+            (
+                field_name,
+                FieldDistinct {
+                    distinct_count,
+                    sample_values,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Splits `[start_time, end_time)` into consecutive windows of at most `chunk_interval`
+/// microseconds. A missing or non-positive interval returns the whole range as one window.
+fn time_chunks(start_time: i64, end_time: i64, chunk_interval: Option<i64>) -> Vec<(i64, i64)> {
+    let Some(chunk_interval) = chunk_interval.filter(|interval| *interval > 0) else {
+        return vec![(start_time, end_time)];
+    };
+    let mut chunks = vec![];
+    let mut chunk_start = start_time;
+    while chunk_start < end_time {
+        let chunk_end = chunk_start.saturating_add(chunk_interval).min(end_time);
+        chunks.push((chunk_start, chunk_end));
+        chunk_start = chunk_end;
+    }
+    if chunks.is_empty() {
+        chunks.push((start_time, end_time));
+    }
+    chunks
+}
+
+/// Merges the distinct counts of a time window into `counts`, keeping the largest count of each
+/// field. See [`DistinctOptions::chunk_interval`] for why the counts are not added up.
+fn merge_distinct_counts(counts: &mut HashMap<String, u64>, chunk: HashMap<String, u64>) {
+    for (field_name, count) in chunk {
+        let entry = counts.entry(field_name).or_insert(0);
+        *entry = (*entry).max(count);
+    }
+}
+
+/// Builds the query counting the distinct values of each field. Columns are aliased by position
+/// so that field names can neither collide nor break the SQL.
+fn distinct_count_sql(stream_name: &str, field_names: &[String], mode: DistinctMode) -> String {
+    let select_clauses = field_names
+        .iter()
+        .enumerate()
+        .map(|(i, field_name)| {
+            let field = quote_identifier(field_name);
+            match mode {
+                DistinctMode::Approx => {
+                    format!("approx_distinct({field}) AS {}", distinct_alias(i))
+                }
+                DistinctMode::Exact => format!("count(distinct {field}) AS {}", distinct_alias(i)),
+            }
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "SELECT {} FROM {}",
+        select_clauses.join(", "),
+        quote_identifier(stream_name)
+    )
+}
+
+fn distinct_alias(i: usize) -> String {
+    format!("distinct_{i}")
+}
+
+/// Maps the columns of a [`distinct_count_sql`] result back to field names. Fields without a
+/// value, e.g. because the stream has no data in the time range, count 0.
+fn parse_distinct_counts(
+    field_names: &[String],
+    resp: &config::meta::search::Response,
+) -> HashMap<String, u64> {
+    field_names
+        .iter()
+        .enumerate()
+        .map(|(i, field_name)| {
+            let count = resp.scalar_u64(&distinct_alias(i)).unwrap_or(0);
+            (field_name.to_string(), count)
+        })
+        .collect()
 }
 
 /// Get cache statistics for monitoring
@@ -352,6 +656,242 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_distinct_cache_serves_repeated_fields() {
+        let cache = DistinctCache::default();
+        let fields = vec!["status".to_string(), "host".to_string()];
+        let options = DistinctOptions::default();
+        let lookup = |cache: &DistinctCache, fields: &[String], start_time: i64| {
+            cache.lookup(
+                "default",
+                "app",
+                StreamType::Logs,
+                fields,
+                start_time,
+                2000,
+                options,
+            )
+        };
+        let distinct = |distinct_count| FieldDistinct {
+            distinct_count,
+            sample_values: vec![],
+        };
+
+        // the first lookup computes every field
+        let (values, missing) = lookup(&cache, &fields, 1000);
+        assert!(values.is_empty());
+        assert_eq!(missing, fields);
+        cache.insert(
+            "default",
+            "app",
+            StreamType::Logs,
+            &HashMap::from([
+                ("status".to_string(), distinct(5)),
+                ("host".to_string(), distinct(40)),
+            ]),
+            1000,
+            2000,
+            options,
+        );
+        assert_eq!(cache.values.len(), 2);
+
+        // the second one for the same key is served from the cache, also through a clone
+        let (values, missing) = lookup(&cache.clone(), &fields[..1], 1000);
+        assert!(missing.is_empty());
+        assert_eq!(values.get("status"), Some(&distinct(5)));
+
+        // only the fields not computed yet are missing
+        let (values, missing) = lookup(&cache, &["host".to_string(), "level".to_string()], 1000);
+        assert_eq!(values.get("host"), Some(&distinct(40)));
+        assert_eq!(missing, vec!["level".to_string()]);
+
+        // another window, stream type, mode or sampling is another key
+        let (values, _) = lookup(&cache, &fields, 1500);
+        assert!(values.is_empty());
+        let (values, _) = cache.lookup(
+            "default",
+            "app",
+            StreamType::Traces,
+            &fields,
+            1000,
+            2000,
+            options,
+        );
+        assert!(values.is_empty());
+        for other in [
+            DistinctOptions {
+                mode: DistinctMode::Exact,
+                ..options
+            },
+            DistinctOptions {
+                sample_values: 3,
+                ..options
+            },
+        ] {
+            let (values, _) = cache.lookup(
+                "default",
+                "app",
+                StreamType::Logs,
+                &fields,
+                1000,
+                2000,
+                other,
+            );
+            assert!(values.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_distinct_count_sql() {
+        let fields = vec![
+            "k8s.pod".to_string(),
+            "user agent".to_string(),
+            r#"a"b"#.to_string(),
+        ];
+        assert_eq!(
+            distinct_count_sql("my stream", &fields, DistinctMode::Approx),
+            r#"SELECT approx_distinct("k8s.pod") AS distinct_0, approx_distinct("user agent") AS distinct_1, approx_distinct("a""b") AS distinct_2 FROM "my stream""#
+        );
+        assert_eq!(
+            distinct_count_sql("logs", &fields[..1], DistinctMode::Exact),
+            r#"SELECT count(distinct "k8s.pod") AS distinct_0 FROM "logs""#
+        );
+    }
+
+    #[test]
+    fn test_distinct_count_sql_colliding_fields() {
+        // keywords and names that differ only by case get distinct aliases
+        let fields = vec![
+            "select".to_string(),
+            "from".to_string(),
+            "Status".to_string(),
+            "status".to_string(),
+        ];
+        assert_eq!(
+            distinct_count_sql("logs", &fields, DistinctMode::Approx),
+            r#"SELECT approx_distinct("select") AS distinct_0, approx_distinct("from") AS distinct_1, approx_distinct("Status") AS distinct_2, approx_distinct("status") AS distinct_3 FROM "logs""#
+        );
+
+        let mut resp = config::meta::search::Response::default();
+        resp.add_hit(&serde_json::json!({
+            "distinct_0": 1,
+            "distinct_1": 2,
+            "distinct_2": 3,
+            "distinct_3": 4
+        }));
+        let counts = parse_distinct_counts(&fields, &resp);
+        assert_eq!(counts.get("select"), Some(&1));
+        assert_eq!(counts.get("from"), Some(&2));
+        assert_eq!(counts.get("Status"), Some(&3));
+        assert_eq!(counts.get("status"), Some(&4));
+    }
+
+    #[test]
+    fn test_time_chunks() {
+        assert_eq!(time_chunks(0, 100, None), vec![(0, 100)]);
+        assert_eq!(time_chunks(0, 100, Some(0)), vec![(0, 100)]);
+        assert_eq!(
+            time_chunks(0, 100, Some(40)),
+            vec![(0, 40), (40, 80), (80, 100)]
+        );
+        assert_eq!(time_chunks(0, 100, Some(50)), vec![(0, 50), (50, 100)]);
+        assert_eq!(time_chunks(0, 100, Some(1000)), vec![(0, 100)]);
+        // an empty range is still queried once
+        assert_eq!(time_chunks(100, 100, Some(10)), vec![(100, 100)]);
+    }
+
+    #[test]
+    fn test_merge_distinct_counts() {
+        let mut counts = HashMap::new();
+        merge_distinct_counts(
+            &mut counts,
+            HashMap::from([("a".to_string(), 10), ("b".to_string(), 3)]),
+        );
+        merge_distinct_counts(
+            &mut counts,
+            HashMap::from([("a".to_string(), 7), ("b".to_string(), 5)]),
+        );
+        assert_eq!(counts.get("a"), Some(&10));
+        assert_eq!(counts.get("b"), Some(&5));
+    }
+
+    #[test]
+    fn test_parse_distinct_counts() {
+        let fields = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut resp = config::meta::search::Response::default();
+        resp.add_hit(&serde_json::json!({"distinct_0": 10, "distinct_1": "20"}));
+        let counts = parse_distinct_counts(&fields, &resp);
+        assert_eq!(counts.get("a"), Some(&10));
+        assert_eq!(counts.get("b"), Some(&20));
+        assert_eq!(counts.get("c"), Some(&0));
+
+        let counts = parse_distinct_counts(&fields, &config::meta::search::Response::default());
+        assert!(counts.values().all(|count| *count == 0));
+    }
+
+    #[test]
+    fn test_sample_values_sql() {
+        assert_eq!(
+            sample_values_sql("my stream", "k8s.pod", 5),
+            r#"SELECT DISTINCT "k8s.pod" AS sample_value FROM "my stream" WHERE "k8s.pod" IS NOT NULL LIMIT 5"#
+        );
+    }
+
+    #[test]
+    fn test_sensitive_fields_are_not_sampled() {
+        assert!(is_sensitive_field("password"));
+        assert!(is_sensitive_field("user_API_KEY"));
+        assert!(is_sensitive_field("http.Authorization"));
+        assert!(is_sensitive_field("refresh_token"));
+        assert!(!is_sensitive_field("status"));
+        assert!(!is_sensitive_field("author"));
+
+        let fields = vec![
+            "status".to_string(),
+            "db_password".to_string(),
+            "host".to_string(),
+        ];
+        assert!(sampled_fields(&fields, DistinctOptions::default()).is_empty());
+        let options = DistinctOptions {
+            sample_values: 5,
+            ..Default::default()
+        };
+        assert_eq!(
+            sampled_fields(&fields, options),
+            vec!["status".to_string(), "host".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_samples_attached_when_enabled() {
+        let mut resp = config::meta::search::Response::default();
+        resp.add_hit(&serde_json::json!({"sample_value": "GET"}));
+        resp.add_hit(&serde_json::json!({"sample_value": 404}));
+        resp.add_hit(&serde_json::json!({"sample_value": null}));
+        resp.add_hit(&serde_json::json!({"other": "x"}));
+        let samples = parse_sample_values(&resp);
+        assert_eq!(samples, vec!["GET".to_string(), "404".to_string()]);
+
+        let counts = HashMap::from([("method".to_string(), 4), ("password".to_string(), 100)]);
+        let distinct = attach_samples(counts, HashMap::from([("method".to_string(), samples)]));
+        assert_eq!(
+            distinct["method"],
+            FieldDistinct {
+                distinct_count: 4,
+                sample_values: vec!["GET".to_string(), "404".to_string()],
+            }
+        );
+        assert!(distinct["password"].sample_values.is_empty());
+        assert_eq!(
+            serde_json::to_value(&distinct["method"]).unwrap(),
+            serde_json::json!({"distinct_count": 4, "sample_values": ["GET", "404"]})
+        );
+        assert_eq!(
+            serde_json::to_value(&distinct["password"]).unwrap(),
+            serde_json::json!({"distinct_count": 100})
+        );
+    }
+
     #[test]
     fn test_generate_cache_key() {
         let key = generate_cache_key("test_org", StreamType::Logs, "test_stream", "test_field");
@@ -562,13 +562,8 @@ pub async fn check_work_group(
     let user_id = user_id.as_deref();
 
     // 1. get work group
-    // Check if this is a background task (alerts, reports, derived streams)
-    let is_background_task = req
-        .search_event_type
-        .as_ref()
-        .and_then(|st| config::meta::search::SearchEventType::try_from(st.as_str()).ok())
-        .map(|st| st.is_background())
-        .unwrap_or(false);
+    // Check if this is a background task (alerts, reports, derived streams, low priority searches)
+    let is_background_task = req.is_background_task();
 
     let work_group: Option<o2_enterprise::enterprise::search::WorkGroup> =
         Some(o2_enterprise::enterprise::search::work_group::predict(
@@ -52,6 +52,8 @@ pub async fn search(
     let trace_id = req.trace_id.clone();
     let query_type = query.query_type.to_lowercase();
     let track_total_hits = query.track_total_hits;
+    let track_total_hits_mode =
+        search::TrackTotalHits::from_proto(query.track_total_hits, query.track_total_hits_limit);
 
     // handle request time range
     let meta = Sql::new_from_req(&req, &query).await?;
@@ -259,6 +261,7 @@ pub async fn search(
     let took_time = start.elapsed().as_millis() as usize;
 
     result.set_total(total);
+    result.set_total_exact(track_total_hits_mode.is_exact(total));
     result.set_histogram_interval(sql.histogram_interval);
     result.set_partial(is_partial, partial_err);
     result.set_took(took_time);
@@ -329,6 +329,7 @@ mod tests {
             action_id: "".to_string(),
             histogram_interval: 5,
             sampling_ratio: None,
+            track_total_hits_limit: 0,
         };
         Sql::new(&query, "parse_test", StreamType::Logs, None)
             .await
@@ -147,6 +147,22 @@ pub async fn search(
     let started_at = now_micros();
     let cfg = get_config();
 
+    // HTTP handlers decode the request themselves, but internal callers may pass it encoded
+    let decoded_req;
+    let in_req = if in_req.encoding == search::RequestEncoding::Base64 {
+        let mut req = in_req.clone();
+        req.decode().map_err(|e| {
+            Error::ErrorCode(ErrorCodes::SearchSQLNotValid(format!(
+                "decode sql error: {e}"
+            )))
+        })?;
+        decoded_req = req;
+        &decoded_req
+    } else {
+        in_req
+    };
+    utils::validate_time_range(in_req.query.start_time, in_req.query.end_time)?;
+
     let trace_id = if trace_id.is_empty() {
         if cfg.common.tracing_enabled || cfg.common.tracing_search_enabled {
             let ctx = tracing::Span::current().context();
@@ -167,7 +183,17 @@ pub async fn search(
     #[cfg(feature = "enterprise")]
     let req_clusters = in_req.clusters.clone();
 
-    let query: SearchQuery = in_req.query.clone().into();
+    let mut in_query = in_req.query.clone();
+    let size_capped = utils::cap_result_size(&mut in_query.size, cfg.limit.query_max_result_size);
+    if size_capped {
+        log::warn!(
+            "[trace_id {trace_id}] search size {} capped to {}, the max result size is {}",
+            in_req.query.size,
+            in_query.size,
+            cfg.limit.query_max_result_size
+        );
+    }
+    let query: SearchQuery = in_query.into();
     let req_query = query.clone();
     let mut request = config::datafusion::request::Request::new(
         trace_id.clone(),
@@ -180,13 +206,14 @@ pub async fn search(
         in_req.query.histogram_interval,
         in_req.clear_cache,
     );
-    if in_req.query.streaming_output && !in_req.query.track_total_hits {
+    if in_req.query.streaming_output && !in_req.query.track_total_hits.is_enabled() {
         request.set_streaming_output(true, in_req.query.streaming_id.clone());
     }
     if let Some(v) = in_req.local_mode {
         request.set_local_mode(Some(v));
     }
     request.set_use_cache(in_req.use_cache);
+    request.set_search_priority(in_req.search_priority);
     let meta = Sql::new_from_req(&request, &query).await?;
 
     #[cfg(feature = "enterprise")]
@@ -271,6 +298,10 @@ pub async fn search(
                 res = crate::service::search::streaming::order_search_results(res, None);
             }
             res.set_work_group(_work_group.clone());
+            if size_capped {
+                res.function_error
+                    .push(crate::common::meta::search::MAX_RESULT_SIZE_CAPPED_MSG.to_string());
+            }
             let time = start.elapsed().as_secs_f64();
             let (report_usage, search_type, search_event_context) = match in_req.search_type {
                 Some(search_type) => {
@@ -354,6 +385,62 @@ pub async fn search(
     }
 }
 
+/// System source of internal searches that don't name one of their own.
+pub const INTERNAL_SEARCH_SOURCE: &str = "internal";
+
+/// Runs a system search of `sql` over `[start_time, end_time)`, returning every matching row.
+/// See [`internal_search_request`].
+pub async fn internal_search(
+    org_id: &str,
+    stream_type: StreamType,
+    sql: impl Into<String>,
+    start_time: i64,
+    end_time: i64,
+) -> Result<search::Response, Error> {
+    internal_search_request(
+        org_id,
+        stream_type,
+        None,
+        search::Request::internal(sql, start_time, end_time),
+    )
+    .await
+}
+
+/// Runs `req` as a system search tagged with [`INTERNAL_SEARCH_SOURCE`], as `user_id`. Internal
+/// callers, such as background jobs, should issue their searches through here, so they all run
+/// the same way. The SQL must pass [`utils::validate_system_query`]. The trace id generated for
+/// the search is set in the response.
+pub async fn internal_search_request(
+    org_id: &str,
+    stream_type: StreamType,
+    user_id: Option<String>,
+    req: search::Request,
+) -> Result<search::Response, Error> {
+    utils::validate_system_query(&req.query.sql, &[])?;
+    let (trace_id, req) = prepare_internal_search(req, INTERNAL_SEARCH_SOURCE);
+    let mut resp = search(&trace_id, org_id, stream_type, user_id, &req).await?;
+    resp.set_trace_id(trace_id);
+    Ok(resp)
+}
+
+/// Prepares `req` to run as a system search on behalf of `source`, returning the trace id to run
+/// it with.
+pub fn prepare_internal_search(req: search::Request, source: &str) -> (String, search::Request) {
+    (ider::generate_trace_id(), internal_request(req, source))
+}
+
+/// Marks `req` as a system search on behalf of `source`. The search type is forced to
+/// [`search::SearchEventType::Other`], which is never reported as search usage, and the search
+/// event context tags the search with `source` wherever it is tracked, such as the running
+/// queries list. System searches run at [`search::SearchPriority::Low`], so they yield to the
+/// searches of users.
+pub fn internal_request(mut req: search::Request, source: &str) -> search::Request {
+    req.search_type = Some(search::SearchEventType::Other);
+    req.search_event_context = Some(search::SearchEventContext::with_system(source));
+    req.search_priority = search::SearchPriority::Low;
+    req
+}
+
 /// Returns Error if the first query is failed, otherwise returns the partial results.
 /// In case one query fails, the remaining queries are not executed.
 #[tracing::instrument(name = "service:search_multi:enter", skip(multi_req))]
@@ -610,6 +697,73 @@ pub async fn search_multi(
     Ok(multi_res)
 }
 
+/// Estimates the cost of running `req` without running it. Only stream metadata is read: the
+/// records and bytes scanned are approximated from the stats of each stream the query reads,
+/// and the partitions from the scan time on the online queriers, as `search_partition` does when
+/// it approximates from stream stats.
+#[tracing::instrument(name = "service:search:estimate", skip(req))]
+pub async fn estimate(
+    org_id: &str,
+    stream_type: StreamType,
+    req: &search::Request,
+) -> Result<search::QueryCostEstimate, Error> {
+    let cfg = get_config();
+    let mut req = req.clone();
+    if req.encoding == search::RequestEncoding::Base64 {
+        req.decode().map_err(|e| {
+            Error::ErrorCode(ErrorCodes::SearchSQLNotValid(format!(
+                "decode sql error: {e}"
+            )))
+        })?;
+    }
+    utils::validate_time_range(req.query.start_time, req.query.end_time)?;
+
+    let query = cluster_rpc::SearchQuery {
+        start_time: req.query.start_time,
+        end_time: req.query.end_time,
+        sql: req.query.sql.to_string(),
+        ..Default::default()
+    };
+    let sql = Sql::new(&query, org_id, stream_type, req.search_type).await?;
+    let (start_time, end_time) = sql
+        .time_range
+        .unwrap_or((req.query.start_time, req.query.end_time));
+    // data duration in seconds
+    let query_duration = (end_time - start_time) / 1000 / 1000;
+
+    let now = Utc::now().timestamp_micros();
+    let mut records = 0;
+    let mut original_size = 0;
+    for (stream, schema) in sql.schemas.iter() {
+        let stream_type = stream.get_stream_type(stream_type);
+        let stream_settings = unwrap_stream_settings(schema.schema()).unwrap_or_default();
+        let stats = stats::get_stream_stats(org_id, &stream.stream_name(), stream_type);
+        let data_retention = if stream_settings.data_retention > 0 {
+            stream_settings.data_retention
+        } else {
+            cfg.compact.data_retention_days
+        };
+        let (stream_records, stream_size) =
+            utils::approximate_stream_scan(&stats, data_retention, query_duration, now);
+        records += stream_records.max(0) as usize;
+        original_size += stream_size.max(0) as usize;
+    }
+
+    let cpu_cores = infra_cluster::get_cached_online_querier_nodes(Some(RoleGroup::Interactive))
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|n| n.cpu_num)
+        .sum::<u64>() as usize;
+    let total_secs = utils::scan_secs(original_size, cpu_cores, cfg.limit.query_group_base_speed);
+    Ok(search::QueryCostEstimate {
+        streams: sql.schemas.len(),
+        records,
+        original_size,
+        partitions: utils::partition_count(total_secs, cfg.limit.query_partition_by_secs),
+    })
+}
+
 #[allow(clippy::too_many_arguments)]
 #[tracing::instrument(name = "service:search_partition", skip(req))]
 pub async fn search_partition(
@@ -743,30 +897,16 @@ pub async fn search_partition(
             } else {
                 cfg.compact.data_retention_days
             };
-            let mut data_retention = data_retention * 24 * 60 * 60;
             // data duration in seconds
             let query_duration = (req.end_time - req.start_time) / 1000 / 1000;
-
-            // if stats.doc_time_max is 0, handle the case by using current time
-            let data_end_time = if stats.doc_time_max == 0 {
-                Utc::now().timestamp_micros()
-            } else {
-                std::cmp::min(Utc::now().timestamp_micros(), stats.doc_time_max)
-            };
-
-            let data_retention_based_on_stats = (data_end_time - stats.doc_time_min) / 1000 / 1000;
-
-            if data_retention_based_on_stats > 0 {
-                data_retention = std::cmp::min(data_retention, data_retention_based_on_stats);
-            };
-            if data_retention == 0 {
-                log::warn!("Data retention is zero, setting to 1 to prevent division by zero");
-                data_retention = 1;
-            }
-            let records = (stats.doc_num as i64 / data_retention) * query_duration;
-            let original_size = (stats.storage_size as i64 / data_retention) * query_duration;
+            let (records, original_size) = utils::approximate_stream_scan(
+                &stats,
+                data_retention,
+                query_duration,
+                Utc::now().timestamp_micros(),
+            );
             log::info!(
-                "[trace_id {trace_id}] using approximation: stream: {stream_name}, records: {records}, original_size: {original_size}, data_retention in seconds: {data_retention}",
+                "[trace_id {trace_id}] using approximation: stream: {stream_name}, records: {records}, original_size: {original_size}, data_retention in days: {data_retention}",
             );
             files.push(infra::file_list::FileId {
                 id: Utc::now().timestamp_micros(),
@@ -881,10 +1021,11 @@ pub async fn search_partition(
     }
 
     // Calculate original step with all factors considered
-    let mut total_secs = resp.original_size / cfg.limit.query_group_base_speed / cpu_cores;
-    if total_secs * cfg.limit.query_group_base_speed * cpu_cores < resp.original_size {
-        total_secs += 1;
-    }
+    let total_secs = utils::scan_secs(
+        resp.original_size,
+        cpu_cores,
+        cfg.limit.query_group_base_speed,
+    );
 
     // If total secs is <= aggs_min_num_partition_secs (default 3 seconds), then disable
     // partitioning even if streaming aggs is true. This optimization avoids partition overhead
@@ -902,14 +1043,7 @@ pub async fn search_partition(
         return Ok(resp);
     }
 
-    let mut part_num = max(1, total_secs / cfg.limit.query_partition_by_secs);
-    if part_num * cfg.limit.query_partition_by_secs < total_secs {
-        part_num += 1;
-    }
-    // if the partition number is too large, we limit it to 1000
-    if part_num > 1000 {
-        part_num = 1000;
-    }
+    let part_num = utils::partition_count(total_secs, cfg.limit.query_partition_by_secs);
 
     log::info!(
         "[trace_id {trace_id}] search_partition: original_size: {}, cpu_cores: {}, base_speed: {}, partition_secs: {}, part_num: {}",
@@ -1570,3 +1704,29 @@ pub fn check_search_allowed(_org_id: &str, _stream: Option<&str>) -> Result<(),
     #[cfg(not(feature = "enterprise"))]
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_internal_search() {
+        let req = search::Request {
+            search_type: Some(search::SearchEventType::UI),
+            ..search::Request::internal("SELECT * FROM t", 0, 10)
+        };
+        let (trace_id, req) = prepare_internal_search(req, INTERNAL_SEARCH_SOURCE);
+        assert!(!trace_id.is_empty());
+        assert_eq!(req.search_type, Some(search::SearchEventType::Other));
+        assert_eq!(req.search_priority, search::SearchPriority::Low);
+        assert_eq!(
+            req.search_event_context
+                .and_then(|ctx| ctx.system_source)
+                .as_deref(),
+            Some(INTERNAL_SEARCH_SOURCE)
+        );
+
+        let (other_trace_id, _) = prepare_internal_search(search::Request::default(), "test");
+        assert_ne!(trace_id, other_trace_id);
+    }
+}
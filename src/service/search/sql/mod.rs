@@ -146,7 +146,11 @@ impl Sql {
         //********************Change the sql start*********************************//
         // 2. rewrite track_total_hits
         if query.track_total_hits {
-            let mut trace_total_hits_visitor = TrackTotalHitsVisitor::new();
+            let mut trace_total_hits_visitor = if query.track_total_hits_limit > 0 {
+                TrackTotalHitsVisitor::with_limit(query.track_total_hits_limit)
+            } else {
+                TrackTotalHitsVisitor::new()
+            };
             let _ = statement.visit(&mut trace_total_hits_visitor);
         }
 
@@ -17,15 +17,23 @@ use std::ops::ControlFlow;
 
 use sqlparser::ast::{
     Expr, Function, FunctionArg, FunctionArgExpr, FunctionArgumentList, FunctionArguments,
-    GroupByExpr, Ident, ObjectName, ObjectNamePart, Query, Select, SelectFlavor, SelectItem,
-    SetExpr, TableFactor, TableWithJoins, VisitorMut, helpers::attached_token::AttachedToken,
+    GroupByExpr, Ident, LimitClause, ObjectName, ObjectNamePart, Query, Select, SelectFlavor,
+    SelectItem, SetExpr, TableFactor, TableWithJoins, Value, VisitorMut,
+    helpers::attached_token::AttachedToken,
 };
 
-pub struct TrackTotalHitsVisitor {}
+pub struct TrackTotalHitsVisitor {
+    limit: Option<i64>,
+}
 
 impl TrackTotalHitsVisitor {
     pub fn new() -> Self {
-        Self {}
+        Self { limit: None }
+    }
+
+    /// Counts at most `limit` hits, which stops the scan early on large streams.
+    pub fn with_limit(limit: i64) -> Self {
+        Self { limit: Some(limit) }
     }
 }
 
@@ -33,159 +41,47 @@ impl VisitorMut for TrackTotalHitsVisitor {
     type Break = ();
 
     fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        if let Some(limit) = self.limit {
+            let mut subquery = query.clone();
+            if let SetExpr::Select(select) = subquery.body.as_mut()
+                && select.distinct.is_none()
+            {
+                select.group_by = GroupByExpr::Expressions(vec![], vec![]);
+                select.having = None;
+                select.sort_by = vec![];
+                select.projection = vec![SelectItem::UnnamedExpr(Expr::value(Value::Number(
+                    "1".to_string(),
+                    false,
+                )))];
+            }
+            subquery.order_by = None;
+            subquery.limit_clause = Some(LimitClause::LimitOffset {
+                limit: Some(Expr::value(Value::Number(limit.to_string(), false))),
+                offset: None,
+                limit_by: vec![],
+            });
+            *query = count_query(subquery);
+            return ControlFlow::Break(());
+        }
+
         match query.body.as_mut() {
             SetExpr::Select(select) => {
                 if select.distinct.is_some() {
                     // For DISTINCT queries, we need to wrap the query in a subquery
                     // and count the results, since DataFusion doesn't support COUNT DISTINCT with
                     // multiple arguments
-                    let original_query = query.clone();
-                    let subquery = Box::new(SetExpr::Select(Box::new(Select {
-                        select_token: AttachedToken::empty(),
-                        distinct: None,
-                        top: None,
-                        top_before_distinct: false,
-                        projection: vec![SelectItem::ExprWithAlias {
-                            expr: Expr::Function(Function {
-                                name: ObjectName(vec![ObjectNamePart::Identifier(Ident::new(
-                                    "count",
-                                ))]),
-                                parameters: FunctionArguments::None,
-                                args: FunctionArguments::List(FunctionArgumentList {
-                                    args: vec![FunctionArg::Unnamed(FunctionArgExpr::Wildcard)],
-                                    duplicate_treatment: None,
-                                    clauses: vec![],
-                                }),
-                                filter: None,
-                                null_treatment: None,
-                                over: None,
-                                within_group: vec![],
-                                uses_odbc_syntax: false,
-                            }),
-                            alias: Ident::new("zo_sql_num"),
-                        }],
-                        into: None,
-                        from: vec![TableWithJoins {
-                            relation: TableFactor::Derived {
-                                lateral: false,
-                                subquery: Box::new(original_query),
-                                alias: None,
-                            },
-                            joins: vec![],
-                        }],
-                        lateral_views: vec![],
-                        selection: None,
-                        group_by: GroupByExpr::Expressions(vec![], vec![]),
-                        having: None,
-                        prewhere: None,
-                        sort_by: vec![],
-                        cluster_by: vec![],
-                        distribute_by: vec![],
-                        named_window: vec![],
-                        qualify: None,
-                        window_before_qualify: false,
-                        connect_by: None,
-                        value_table_mode: None,
-                        exclude: None,
-                        flavor: SelectFlavor::Standard,
-                    })));
-                    *query = Query {
-                        with: None,
-                        body: subquery,
-                        order_by: None,
-                        limit_clause: None,
-                        fetch: None,
-                        for_clause: None,
-                        locks: vec![],
-                        settings: None,
-                        format_clause: None,
-                        pipe_operators: vec![],
-                    };
+                    *query = count_query(query.clone());
                 } else {
                     // For non-DISTINCT queries, use the original approach
                     select.group_by = GroupByExpr::Expressions(vec![], vec![]);
                     select.having = None;
                     select.sort_by = vec![];
-                    select.projection = vec![SelectItem::ExprWithAlias {
-                        expr: Expr::Function(Function {
-                            name: ObjectName(vec![ObjectNamePart::Identifier(Ident::new("count"))]),
-                            parameters: FunctionArguments::None,
-                            args: FunctionArguments::List(FunctionArgumentList {
-                                args: vec![FunctionArg::Unnamed(FunctionArgExpr::Wildcard)],
-                                duplicate_treatment: None,
-                                clauses: vec![],
-                            }),
-                            filter: None,
-                            null_treatment: None,
-                            over: None,
-                            within_group: vec![],
-                            uses_odbc_syntax: false,
-                        }),
-                        alias: Ident::new("zo_sql_num"),
-                    }];
+                    select.projection = vec![count_projection()];
                     query.order_by = None;
                 }
             }
             SetExpr::SetOperation { .. } => {
-                let select = Box::new(SetExpr::Select(Box::new(Select {
-                    select_token: AttachedToken::empty(),
-                    distinct: None,
-                    top: None,
-                    top_before_distinct: false,
-                    projection: vec![SelectItem::ExprWithAlias {
-                        expr: Expr::Function(Function {
-                            name: ObjectName(vec![ObjectNamePart::Identifier(Ident::new("count"))]),
-                            parameters: FunctionArguments::None,
-                            args: FunctionArguments::List(FunctionArgumentList {
-                                args: vec![FunctionArg::Unnamed(FunctionArgExpr::Wildcard)],
-                                duplicate_treatment: None,
-                                clauses: vec![],
-                            }),
-                            filter: None,
-                            null_treatment: None,
-                            over: None,
-                            within_group: vec![],
-                            uses_odbc_syntax: false,
-                        }),
-                        alias: Ident::new("zo_sql_num"),
-                    }],
-                    into: None,
-                    from: vec![TableWithJoins {
-                        relation: TableFactor::Derived {
-                            lateral: false,
-                            subquery: Box::new(query.clone()),
-                            alias: None,
-                        },
-                        joins: vec![],
-                    }],
-                    lateral_views: vec![],
-                    selection: None,
-                    group_by: GroupByExpr::Expressions(vec![], vec![]),
-                    having: None,
-                    prewhere: None,
-                    sort_by: vec![],
-                    cluster_by: vec![],
-                    distribute_by: vec![],
-                    named_window: vec![],
-                    qualify: None,
-                    window_before_qualify: false,
-                    connect_by: None,
-                    value_table_mode: None,
-                    exclude: None,
-                    flavor: SelectFlavor::Standard,
-                })));
-                *query = Query {
-                    with: None,
-                    body: select,
-                    order_by: None,
-                    limit_clause: None,
-                    fetch: None,
-                    for_clause: None,
-                    locks: vec![],
-                    settings: None,
-                    format_clause: None,
-                    pipe_operators: vec![],
-                };
+                *query = count_query(query.clone());
             }
             _ => {}
         }
@@ -193,6 +89,74 @@ impl VisitorMut for TrackTotalHitsVisitor {
     }
 }
 
+/// `count(*) AS zo_sql_num`
+fn count_projection() -> SelectItem {
+    SelectItem::ExprWithAlias {
+        expr: Expr::Function(Function {
+            name: ObjectName(vec![ObjectNamePart::Identifier(Ident::new("count"))]),
+            parameters: FunctionArguments::None,
+            args: FunctionArguments::List(FunctionArgumentList {
+                args: vec![FunctionArg::Unnamed(FunctionArgExpr::Wildcard)],
+                duplicate_treatment: None,
+                clauses: vec![],
+            }),
+            filter: None,
+            null_treatment: None,
+            over: None,
+            within_group: vec![],
+            uses_odbc_syntax: false,
+        }),
+        alias: Ident::new("zo_sql_num"),
+    }
+}
+
+/// Wraps `subquery` in `SELECT count(*) AS zo_sql_num FROM (subquery)`.
+fn count_query(subquery: Query) -> Query {
+    let select = Box::new(SetExpr::Select(Box::new(Select {
+        select_token: AttachedToken::empty(),
+        distinct: None,
+        top: None,
+        top_before_distinct: false,
+        projection: vec![count_projection()],
+        into: None,
+        from: vec![TableWithJoins {
+            relation: TableFactor::Derived {
+                lateral: false,
+                subquery: Box::new(subquery),
+                alias: None,
+            },
+            joins: vec![],
+        }],
+        lateral_views: vec![],
+        selection: None,
+        group_by: GroupByExpr::Expressions(vec![], vec![]),
+        having: None,
+        prewhere: None,
+        sort_by: vec![],
+        cluster_by: vec![],
+        distribute_by: vec![],
+        named_window: vec![],
+        qualify: None,
+        window_before_qualify: false,
+        connect_by: None,
+        value_table_mode: None,
+        exclude: None,
+        flavor: SelectFlavor::Standard,
+    })));
+    Query {
+        with: None,
+        body: select,
+        order_by: None,
+        limit_clause: None,
+        fetch: None,
+        for_clause: None,
+        locks: vec![],
+        settings: None,
+        format_clause: None,
+        pipe_operators: vec![],
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use sqlparser::{ast::VisitMut, dialect::GenericDialect};
@@ -335,4 +299,44 @@ mod tests {
         let expected_sql = "SELECT count(*) AS zo_sql_num FROM (SELECT DISTINCT unique_id, continent, bronze_medals FROM oly WHERE continent = 'ASI')";
         assert_eq!(statement.to_string(), expected_sql);
     }
+
+    #[test]
+    fn test_track_total_hits_with_limit() {
+        let sql = "SELECT name, count(*) FROM t WHERE name = 'a' group by name order by name";
+        let mut statement = sqlparser::parser::Parser::parse_sql(&GenericDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let mut track_total_hits_visitor = TrackTotalHitsVisitor::with_limit(1000);
+        let _ = statement.visit(&mut track_total_hits_visitor);
+        let expected_sql =
+            "SELECT count(*) AS zo_sql_num FROM (SELECT 1 FROM t WHERE name = 'a' LIMIT 1000)";
+        assert_eq!(statement.to_string(), expected_sql);
+    }
+
+    #[test]
+    fn test_track_total_hits_with_limit_distinct() {
+        let sql = "SELECT DISTINCT name FROM t WHERE name = 'a' order by name limit 10";
+        let mut statement = sqlparser::parser::Parser::parse_sql(&GenericDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let mut track_total_hits_visitor = TrackTotalHitsVisitor::with_limit(1000);
+        let _ = statement.visit(&mut track_total_hits_visitor);
+        let expected_sql = "SELECT count(*) AS zo_sql_num FROM (SELECT DISTINCT name FROM t WHERE name = 'a' LIMIT 1000)";
+        assert_eq!(statement.to_string(), expected_sql);
+    }
+
+    #[test]
+    fn test_track_total_hits_with_limit_union() {
+        let sql = "SELECT name from t1 union select name from t2";
+        let mut statement = sqlparser::parser::Parser::parse_sql(&GenericDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let mut track_total_hits_visitor = TrackTotalHitsVisitor::with_limit(1000);
+        let _ = statement.visit(&mut track_total_hits_visitor);
+        let expected_sql = "SELECT count(*) AS zo_sql_num FROM (SELECT name FROM t1 UNION SELECT name FROM t2 LIMIT 1000)";
+        assert_eq!(statement.to_string(), expected_sql);
+    }
 }
@@ -82,6 +82,10 @@ macro_rules! intervals {
     (@unit m) => { chrono::Duration::minutes };
 }
 
+/// Picks a histogram bucket size from the span of `time_range`, used when a
+/// query asks for a histogram without an explicit interval (or with 0).
+///
+/// Wider time ranges always get an equal or larger bucket size.
 pub fn generate_histogram_interval(time_range: Option<(i64, i64)>) -> &'static str {
     let Some((start, end)) = time_range else {
         return "1 hour";
@@ -414,4 +418,72 @@ mod tests {
         // Should return default interval of 1 hour (3600 seconds)
         assert_eq!(histogram_interval_visitor.interval, Some(3600));
     }
+
+    #[test]
+    fn test_histogram_interval_visitor_without_interval() {
+        let sql = "SELECT histogram(_timestamp) FROM logs";
+        let mut statement = sqlparser::parser::Parser::parse_sql(&GenericDialect {}, sql)
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        // 1 day span picks 1 hour buckets
+        let time_range = Some((1640995200000000, 1641081600000000));
+        let mut histogram_interval_visitor = HistogramIntervalVisitor::new(time_range);
+        let _ = statement.visit(&mut histogram_interval_visitor);
+
+        assert!(histogram_interval_visitor.is_histogram);
+        assert_eq!(histogram_interval_visitor.interval, Some(3600));
+    }
+
+    #[test]
+    fn test_generate_histogram_interval_is_monotonic() {
+        let minute = 60 * 1_000_000;
+        let hour = 60 * minute;
+        let day = 24 * hour;
+        let spans = [
+            minute,
+            15 * minute,
+            30 * minute,
+            hour,
+            2 * hour,
+            6 * hour,
+            day,
+            15 * day,
+            21 * day,
+            28 * day,
+            30 * day,
+            60 * day,
+            365 * day,
+        ];
+
+        let mut previous = 0;
+        for span in spans {
+            let interval = generate_histogram_interval(Some((0, span)));
+            let seconds = convert_histogram_interval_to_seconds(interval).unwrap();
+            assert!(
+                seconds >= previous,
+                "span {span} picked {interval}, smaller than previous {previous}s"
+            );
+            previous = seconds;
+        }
+        assert_eq!(previous, 86400);
+    }
+
+    #[test]
+    fn test_validate_and_adjust_histogram_interval_zero_uses_time_range() {
+        let hour = 3600 * 1_000_000;
+        assert_eq!(
+            validate_and_adjust_histogram_interval(0, Some((0, hour))),
+            30
+        );
+        assert_eq!(
+            validate_and_adjust_histogram_interval(0, Some((0, 24 * hour))),
+            3600
+        );
+        assert_eq!(
+            validate_and_adjust_histogram_interval(0, Some((0, 90 * 24 * hour))),
+            86400
+        );
+    }
 }
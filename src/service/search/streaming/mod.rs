@@ -218,7 +218,10 @@ pub async fn process_search_stream_request(
     // partition.
     let mut hits_to_skip = req.query.from;
 
-    if req.query.from == 0 && !req.query.track_total_hits && req.query.streaming_id.is_none() {
+    if req.query.from == 0
+        && !req.query.track_total_hits.is_enabled()
+        && req.query.streaming_id.is_none()
+    {
         // check cache for the first page
         let (c_resp, _should_exec_query) = match search_cache::prepare_cache_response(
             &trace_id,
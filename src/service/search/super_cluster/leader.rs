@@ -95,7 +95,17 @@ pub async fn search(
                 .map(RoleGroup::from)
         })
         .unwrap_or(Some(RoleGroup::Interactive));
+    let requested = (!req_regions.is_empty() || !req_clusters.is_empty())
+        .then(|| format!("regions {req_regions:?}, clusters {req_clusters:?}"));
     let clusters = get_cluster_nodes(trace_id, req_regions, req_clusters, role_group).await?;
+    if clusters.is_empty()
+        && let Some(requested) = requested
+    {
+        // don't fall back to the local cluster when the caller asked for specific ones
+        return Err(Error::ErrorCode(ErrorCodes::InvalidParams(format!(
+            "no cluster found for {requested}"
+        ))));
+    }
     let clusters_num = clusters.len();
     log::info!(
         "{}",
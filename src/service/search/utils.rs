@@ -18,13 +18,23 @@ use std::{future::Future, pin::Pin, sync::Arc};
 use config::meta::{
     inverted_index::UNKNOWN_NAME,
     search::{PARTIAL_ERROR_RESPONSE_MESSAGE, ScanStats},
+    sql::{TableReferenceExt, resolve_stream_names_with_type},
+    stream::{StreamStats, StreamType},
 };
 use datafusion::physical_plan::{ExecutionPlan, ExecutionPlanVisitor};
-use sqlparser::ast::{BinaryOperator, Expr};
+use infra::errors::{Error, ErrorCodes};
+use sqlparser::{
+    ast::{BinaryOperator, Expr, SetExpr, Statement},
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
 use tokio::sync::Mutex;
 
 use super::{DATAFUSION_RUNTIME, datafusion::distributed_plan::remote_scan_exec::RemoteScanExec};
-use crate::{common::meta::search::CAPPED_RESULTS_MSG, service::search::sql::Sql};
+use crate::{
+    common::meta::search::{CAPPED_RESULTS_MSG, MAX_RESULT_SIZE_CAPPED_MSG},
+    service::search::sql::Sql,
+};
 
 type Cleanup = Pin<Box<dyn Future<Output = ()> + Send>>;
 
@@ -202,6 +212,149 @@ pub fn is_default_query_limit_exceeded(num_rows: usize, sql: &Sql) -> bool {
     }
 }
 
+/// Clamps the requested result `size` to `max`, and returns true if it was changed. A `max` of 0
+/// or below disables the limit.
+///
+/// Sizes of 0 or below fall back to the default query limit and are left as is, as some callers
+/// tell searches apart by a size of -1. The exception is [`config::QUERY_WITH_NO_LIMIT`], which
+/// internal callers set to disable the limits. Sizes below it are clamped to 0: the search treats
+/// them as unlimited too, which would otherwise let a request bypass both limits.
+pub fn cap_result_size(size: &mut i64, max: i64) -> bool {
+    if *size < config::QUERY_WITH_NO_LIMIT {
+        *size = 0;
+        true
+    } else if max > 0 && *size > max {
+        *size = max;
+        true
+    } else {
+        false
+    }
+}
+
+/// Rejects negative timestamps and time ranges where `start_time` is not before `end_time`, which
+/// would otherwise silently return no results. Both times being 0 means the query has no time
+/// range and is allowed.
+pub fn validate_time_range(start_time: i64, end_time: i64) -> Result<(), Error> {
+    if (start_time, end_time) == (0, 0) {
+        return Ok(());
+    }
+    if start_time < 0 || end_time < 0 {
+        return Err(Error::ErrorCode(ErrorCodes::InvalidParams(format!(
+            "start_time ({start_time}) and end_time ({end_time}) must not be negative"
+        ))));
+    }
+    if start_time >= end_time {
+        return Err(Error::ErrorCode(ErrorCodes::InvalidParams(format!(
+            "start_time ({start_time}) must be before end_time ({end_time})"
+        ))));
+    }
+    Ok(())
+}
+
+/// Checks a query the server built for a system search before running it, and returns the
+/// stream it reads. System queries must be a single read-only SELECT, which rules out statements
+/// that write or change anything as well as `SELECT ... INTO`, and must read exactly one stream,
+/// so that a misconfigured filter can't make them join or scan other streams. When
+/// `allowed_streams` isn't empty, the stream must also be one of them.
+pub fn validate_system_query(
+    sql: &str,
+    allowed_streams: &[&str],
+) -> Result<(StreamType, String), Error> {
+    let invalid = |reason: String| {
+        Error::ErrorCode(ErrorCodes::SearchSQLNotValid(format!(
+            "system query rejected, {reason}: {sql}"
+        )))
+    };
+    let statements = Parser::parse_sql(&PostgreSqlDialect {}, sql)
+        .map_err(|e| invalid(format!("it can't be parsed ({e})")))?;
+    let [Statement::Query(query)] = statements.as_slice() else {
+        return Err(invalid("it must be a single SELECT".to_string()));
+    };
+    if has_select_into(&query.body) {
+        return Err(invalid("SELECT ... INTO is not allowed".to_string()));
+    }
+
+    let mut streams = resolve_stream_names_with_type(sql)
+        .map_err(|e| invalid(format!("its streams can't be resolved ({e})")))?
+        .iter()
+        .map(|table| (table.get_stream_type(StreamType::Logs), table.stream_name()))
+        .collect::<Vec<_>>();
+    streams.sort_by(|a, b| (a.0.as_str(), &a.1).cmp(&(b.0.as_str(), &b.1)));
+    streams.dedup();
+    let [(stream_type, stream_name)] = streams.as_slice() else {
+        return Err(invalid(format!(
+            "it must read exactly one stream, not {}",
+            streams.len()
+        )));
+    };
+    if !allowed_streams.is_empty() && !allowed_streams.contains(&stream_name.as_str()) {
+        return Err(invalid(format!("stream {stream_name} is not allowed")));
+    }
+    Ok((*stream_type, stream_name.clone()))
+}
+
+fn has_select_into(body: &SetExpr) -> bool {
+    match body {
+        SetExpr::Select(select) => select.into.is_some(),
+        SetExpr::Query(query) => has_select_into(&query.body),
+        SetExpr::SetOperation { left, right, .. } => {
+            has_select_into(left) || has_select_into(right)
+        }
+        _ => false,
+    }
+}
+
+/// Approximates the records and uncompressed bytes that a query over `query_duration` seconds
+/// reads from a stream with `stats`, assuming the data is spread evenly over the stream's
+/// retention, in days, or over the time it actually holds data if that is shorter.
+pub fn approximate_stream_scan(
+    stats: &StreamStats,
+    data_retention_days: i64,
+    query_duration: i64,
+    now: i64,
+) -> (i64, i64) {
+    let mut data_retention = data_retention_days * 24 * 60 * 60;
+    // if stats.doc_time_max is 0, handle the case by using current time
+    let data_end_time = if stats.doc_time_max == 0 {
+        now
+    } else {
+        std::cmp::min(now, stats.doc_time_max)
+    };
+    let data_retention_based_on_stats = (data_end_time - stats.doc_time_min) / 1000 / 1000;
+    if data_retention_based_on_stats > 0 {
+        data_retention = std::cmp::min(data_retention, data_retention_based_on_stats);
+    };
+    if data_retention <= 0 {
+        log::warn!("Data retention is zero, setting to 1 to prevent division by zero");
+        data_retention = 1;
+    }
+    let records = (stats.doc_num / data_retention) * query_duration;
+    let original_size = (stats.storage_size as i64 / data_retention) * query_duration;
+    (records, original_size)
+}
+
+/// Returns how many seconds `cpu_cores` take to scan `original_size` bytes at `base_speed` per
+/// core, rounded up.
+pub fn scan_secs(original_size: usize, cpu_cores: usize, base_speed: usize) -> usize {
+    let cpu_cores = std::cmp::max(1, cpu_cores);
+    let mut total_secs = original_size / base_speed / cpu_cores;
+    if total_secs * base_speed * cpu_cores < original_size {
+        total_secs += 1;
+    }
+    total_secs
+}
+
+/// Returns how many partitions a query scanning for `total_secs` is split into, so each takes
+/// about `partition_secs`. There are at least 1 and at most 1000 partitions.
+pub fn partition_count(total_secs: usize, partition_secs: usize) -> usize {
+    let mut part_num = std::cmp::max(1, total_secs / partition_secs);
+    if part_num * partition_secs < total_secs {
+        part_num += 1;
+    }
+    // if the partition number is too large, we limit it to 1000
+    std::cmp::min(part_num, 1000)
+}
+
 pub fn is_permissable_function_error(function_error: &[String]) -> bool {
     if function_error.is_empty() {
         return true;
@@ -215,7 +368,9 @@ pub fn is_permissable_function_error(function_error: &[String]) -> bool {
         }
 
         // Check if error contains only cachable messages
-        error.contains(CAPPED_RESULTS_MSG) || error.contains(PARTIAL_ERROR_RESPONSE_MESSAGE)
+        error.contains(CAPPED_RESULTS_MSG)
+            || error.contains(MAX_RESULT_SIZE_CAPPED_MSG)
+            || error.contains(PARTIAL_ERROR_RESPONSE_MESSAGE)
     })
 }
 
@@ -225,6 +380,159 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_validate_system_query_allows_single_stream_select() {
+        let sql = "SELECT org_id, count(*) AS queries FROM \"usage\" WHERE event = 'Search' GROUP BY org_id";
+        assert_eq!(
+            validate_system_query(sql, &["usage"]).unwrap(),
+            (StreamType::Logs, "usage".to_string())
+        );
+        assert!(validate_system_query(sql, &[]).is_ok());
+        // reading a stream several times is still a single stream
+        let sql = "SELECT * FROM app WHERE status IN (SELECT status FROM app LIMIT 1)";
+        assert!(validate_system_query(sql, &[]).is_ok());
+        assert_eq!(
+            validate_system_query("SELECT * FROM metrics.cpu", &[]).unwrap(),
+            (StreamType::Metrics, "cpu".to_string())
+        );
+    }
+
+    #[test]
+    fn test_validate_system_query_rejects_cross_stream_query() {
+        for sql in [
+            "SELECT * FROM \"usage\" u JOIN app a ON u.org_id = a.org_id",
+            "SELECT * FROM \"usage\" UNION ALL SELECT * FROM app",
+            "SELECT * FROM \"usage\" WHERE org_id IN (SELECT org_id FROM secrets)",
+        ] {
+            let err = validate_system_query(sql, &["usage"]).unwrap_err();
+            assert!(
+                err.to_string().contains("exactly one stream"),
+                "{sql}: {err}"
+            );
+        }
+        assert!(validate_system_query("SELECT * FROM app", &["usage"]).is_err());
+        assert!(validate_system_query("SELECT 1", &[]).is_err());
+    }
+
+    #[test]
+    fn test_validate_system_query_rejects_non_select() {
+        for sql in [
+            "DELETE FROM \"usage\"",
+            "DROP TABLE \"usage\"",
+            "INSERT INTO app SELECT * FROM \"usage\"",
+            "SELECT * INTO copy FROM \"usage\"",
+            "SELECT * FROM \"usage\"; SELECT * FROM \"usage\"",
+            "EXPLAIN SELECT * FROM \"usage\"",
+        ] {
+            assert!(validate_system_query(sql, &[]).is_err(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_approximate_stream_scan() {
+        let day = 24 * 3600;
+        let now = 100 * day * 1_000_000;
+
+        // 10 days of data kept for 30 days: spread over the 10 days
+        let stats = StreamStats {
+            doc_time_min: now - 10 * day * 1_000_000,
+            doc_time_max: now,
+            doc_num: 10 * day,
+            storage_size: (100 * day) as f64,
+            ..Default::default()
+        };
+        assert_eq!(
+            approximate_stream_scan(&stats, 30, 3600, now),
+            (3600, 36000)
+        );
+
+        // 10 days of data kept for 2 days: spread over the retention
+        assert_eq!(
+            approximate_stream_scan(&stats, 2, 3600, now),
+            (18000, 180000)
+        );
+
+        // empty stream
+        let stats = StreamStats::default();
+        assert_eq!(approximate_stream_scan(&stats, 30, 3600, now), (0, 0));
+
+        // zero retention doesn't divide by zero
+        let stats = StreamStats {
+            doc_time_min: now,
+            doc_time_max: now,
+            doc_num: 100,
+            ..Default::default()
+        };
+        assert_eq!(approximate_stream_scan(&stats, 0, 10, now), (1000, 0));
+    }
+
+    #[test]
+    fn test_partition_count() {
+        assert_eq!(scan_secs(0, 4, 100), 0);
+        assert_eq!(scan_secs(400, 4, 100), 1);
+        assert_eq!(scan_secs(401, 4, 100), 2);
+        // no cores still scans
+        assert_eq!(scan_secs(400, 0, 100), 4);
+
+        assert_eq!(partition_count(0, 30), 1);
+        assert_eq!(partition_count(30, 30), 1);
+        assert_eq!(partition_count(31, 30), 2);
+        assert_eq!(partition_count(30 * 5000, 30), 1000);
+    }
+
+    #[test]
+    fn test_validate_time_range() {
+        assert!(validate_time_range(10, 20).is_ok());
+        assert!(validate_time_range(0, 20).is_ok());
+        // no time range
+        assert!(validate_time_range(0, 0).is_ok());
+
+        // inverted
+        assert!(validate_time_range(20, 10).is_err());
+        // zero-width
+        assert!(validate_time_range(10, 10).is_err());
+        // negative
+        assert!(validate_time_range(-10, 20).is_err());
+        assert!(validate_time_range(-20, -10).is_err());
+    }
+
+    #[test]
+    fn test_cap_result_size() {
+        let mut size = 5000;
+        assert!(cap_result_size(&mut size, 1000));
+        assert_eq!(size, 1000);
+
+        let mut size = 500;
+        assert!(!cap_result_size(&mut size, 1000));
+        assert_eq!(size, 500);
+
+        let mut size = 5000;
+        assert!(!cap_result_size(&mut size, 0));
+        assert_eq!(size, 5000);
+
+        let mut size = 0;
+        assert!(!cap_result_size(&mut size, 1000));
+        assert_eq!(size, 0);
+
+        let mut size = -1;
+        assert!(!cap_result_size(&mut size, 1000));
+        assert_eq!(size, -1);
+
+        // sizes below the no limit marker fall back to the default limit, even with the max
+        // disabled
+        let mut size = -5000;
+        assert!(cap_result_size(&mut size, 1000));
+        assert_eq!(size, 0);
+
+        let mut size = config::QUERY_WITH_NO_LIMIT - 1;
+        assert!(cap_result_size(&mut size, 0));
+        assert_eq!(size, 0);
+
+        let mut size = config::QUERY_WITH_NO_LIMIT;
+        assert!(!cap_result_size(&mut size, 1000));
+        assert_eq!(size, config::QUERY_WITH_NO_LIMIT);
+    }
+
     #[test]
     fn test_is_cachable_function_error() {
         let error = vec![];
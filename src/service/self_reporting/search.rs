@@ -19,7 +19,8 @@ use config::{
     meta::{
         cluster::RoleGroup,
         search::{
-            Query, Request as SearchRequest, RequestEncoding, SearchEventType, default_use_cache,
+            Query, Request as SearchRequest, RequestEncoding, SearchEventType, TrackTotalHits,
+            default_use_cache,
         },
         stream::StreamType,
     },
@@ -38,7 +39,7 @@ pub async fn get_usage(sql: String, start_time: i64, end_time: i64) -> Result<Ve
             end_time,
             quick_mode: false,
             query_type: "".to_string(),
-            track_total_hits: false,
+            track_total_hits: TrackTotalHits::Off,
             action_id: None,
             uses_zo_fn: false,
             query_fn: None,
@@ -58,6 +59,7 @@ pub async fn get_usage(sql: String, start_time: i64, end_time: i64) -> Result<Ve
         use_cache: default_use_cache(),
         clear_cache: false,
         local_mode: None,
+        search_priority: Default::default(),
     };
 
     let trace_id = ider::uuid();
@@ -13,16 +13,66 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use infra::table::sessions::SessionInfo;
+
 use super::db;
 
 pub async fn get_session(session_id: &str) -> Option<String> {
     db::session::get(session_id).await.ok()
 }
 
-pub async fn set_session(session_id: &str, val: &str) -> Option<()> {
-    db::session::set(session_id, val).await.ok()
+pub async fn set_session(
+    session_id: &str,
+    val: &str,
+    user_id: Option<&str>,
+    user_agent: Option<&str>,
+) -> Option<()> {
+    db::session::set(session_id, val, user_id, user_agent)
+        .await
+        .ok()
+}
+
+/// Lists the active sessions of a user, e.g. to show the devices a user is logged in from.
+pub async fn list_sessions_for_user(user_id: &str) -> Result<Vec<SessionInfo>, anyhow::Error> {
+    Ok(infra::table::sessions::list_for_user(user_id).await?)
 }
 
 pub async fn remove_session(session_id: &str) {
     let _ = db::session::delete(session_id).await;
 }
+
+/// Marks a session as used by an authenticated request. Updates are throttled, so this is cheap
+/// to call on every request.
+pub async fn touch_session(session_id: &str) {
+    if let Err(e) = db::session::touch(session_id).await {
+        log::warn!("[SESSION] Failed to update last_used_at for session {session_id}: {e}");
+    }
+}
+
+/// Revokes a single session, for example on logout or a forced logout by an admin.
+///
+/// Returns whether a session was actually removed.
+pub async fn revoke_session(session_id: &str) -> Result<bool, anyhow::Error> {
+    let revoked = db::session::revoke(session_id).await?;
+    if revoked {
+        log::info!("[AUDIT] session revoked: session_id={session_id}");
+    }
+    Ok(revoked)
+}
+
+/// Revokes every session holding the given access token, for example after the token is rotated.
+///
+/// Returns the session_ids that were removed.
+pub async fn revoke_all_sessions_for_token(
+    access_token: &str,
+) -> Result<Vec<String>, anyhow::Error> {
+    let session_ids = db::session::revoke_all_for_token(access_token).await?;
+    if !session_ids.is_empty() {
+        log::info!(
+            "[AUDIT] {} session(s) revoked for rotated token: session_ids={}",
+            session_ids.len(),
+            session_ids.join(",")
+        );
+    }
+    Ok(session_ids)
+}
@@ -152,7 +152,7 @@ async fn query_edges_from_stream(
             end_time: now,
             quick_mode: false,
             query_type: "".to_string(),
-            track_total_hits: false,
+            track_total_hits: config::meta::search::TrackTotalHits::Off,
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
@@ -172,6 +172,7 @@ async fn query_edges_from_stream(
         use_cache: false,
         clear_cache: false,
         local_mode: Some(false),
+        search_priority: Default::default(),
     };
 
     // Check if stream exists (using Logs type since we write as logs stream)
@@ -277,7 +277,7 @@ async fn process_stream(
             end_time,
             quick_mode: false,
             query_type: "".to_string(),
-            track_total_hits: false,
+            track_total_hits: config::meta::search::TrackTotalHits::Off,
             uses_zo_fn: false,
             query_fn: None,
             skip_wal: false,
@@ -297,6 +297,7 @@ async fn process_stream(
         use_cache: false,
         clear_cache: false,
         local_mode: Some(false),
+        search_priority: Default::default(),
     };
 
     let trace_id = config::ider::generate();